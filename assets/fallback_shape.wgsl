@@ -0,0 +1,8 @@
+#define_import_path smud::fallback_shape
+
+#import smud
+
+// Fills the bounds, so shapes with broken shaders are easy to spot
+fn sdf(input: smud::SdfInput) -> f32 {
+    return smud::sd_box(input.pos, input.bounds);
+}
@@ -0,0 +1,16 @@
+#define_import_path smud::fill_texture
+
+#import smud
+
+// The image of the shape, or a white fallback image if it doesn't have one
+@group(2) @binding(0) var fill_texture: texture_2d<f32>;
+@group(2) @binding(1) var fill_sampler: sampler;
+
+// Texture coordinates spanning the bounds of the shape, with (0, 0) in the top left corner
+fn uv(input: smud::FillInput) -> vec2<f32> {
+    return vec2<f32>(input.pos.x, -input.pos.y) / input.bounds * 0.5 + 0.5;
+}
+
+fn sample(uv: vec2<f32>) -> vec4<f32> {
+    return textureSample(fill_texture, fill_sampler, uv);
+}
@@ -0,0 +1,12 @@
+#define_import_path smud::fallback_fill
+
+#import smud
+
+// Magenta and black checkers
+fn fill(input: smud::FillInput) -> vec4<f32> {
+    let cell = vec2<i32>(floor(input.pos / 8.));
+    let magenta = ((cell.x + cell.y) & 1) == 0;
+    let color = select(vec3<f32>(0.), vec3<f32>(1., 0., 1.), magenta);
+    let a = smud::sd_fill_alpha_fwidth(input.distance);
+    return vec4<f32>(color, a);
+}
@@ -0,0 +1,25 @@
+#import smud
+#import bevy_render::globals::Globals
+
+@group(0) @binding(1) var<uniform> globals: Globals;
+
+// Bindings from the FireFill material, see examples/material.rs
+@group(3) @binding(0) var<uniform> speed: f32;
+@group(3) @binding(1) var palette: texture_2d<f32>;
+@group(3) @binding(2) var palette_sampler: sampler;
+
+fn fill(input: smud::FillInput) -> vec4<f32> {
+    let t = globals.time * speed;
+    let p = input.pos;
+
+    // Cheap flickering by wobbling the distance
+    let flicker = sin(p.x * 0.05 + t * 3.) * sin(p.y * 0.07 - t * 5.) * 6.;
+    let d = input.distance + flicker;
+
+    // Hotter towards the inside and the bottom of the shape
+    let heat = saturate(-d * 0.02 - p.y * 0.002 + 0.3);
+    let color = textureSample(palette, palette_sampler, vec2<f32>(heat, 0.5));
+
+    let a = smud::sd_fill_alpha_fwidth(d);
+    return vec4<f32>(color.rgb, color.a * a * input.color.a);
+}
@@ -0,0 +1,59 @@
+#define_import_path smud::shape_params
+
+#import smud
+
+#ifdef SMUD_PARAMS_UNIFORM
+// Storage buffers are not available on WebGL2, so fall back to a fixed size uniform array
+@group(1) @binding(0) var<uniform> params_buffer: array<vec4<f32>, 1024>;
+#else
+@group(1) @binding(0) var<storage, read> params_buffer: array<vec4<f32>>;
+#endif
+
+// Number of extra parameters of the shape
+fn count(extra_params: vec2<u32>) -> u32 {
+    return extra_params.y;
+}
+
+// Extra parameter number `i` of the shape, or zero if out of range
+fn get(extra_params: vec2<u32>, i: u32) -> vec4<f32> {
+    if (i >= extra_params.y) {
+        return vec4<f32>(0.);
+    }
+#ifdef SMUD_PARAMS_UNIFORM
+    return params_buffer[min(extra_params.x + i, 1023u)];
+#else
+    return params_buffer[extra_params.x + i];
+#endif
+}
+
+// Signed distance to the polygon with the extra params of the shape as vertices, in xy
+fn sd_polygon(p: vec2<f32>, extra_params: vec2<u32>) -> f32 {
+    let n = count(extra_params);
+    if (n == 0u) {
+        return 1e10;
+    }
+    var ds = vec2<f32>(smud::dot2_(p - get(extra_params, 0u).xy), 1.);
+    var previous = get(extra_params, n - 1u).xy;
+    for (var i = 0u; i < n; i++) {
+        let vertex = get(extra_params, i).xy;
+        ds = smud::sd_polygon_edge(p, previous, vertex, ds);
+        previous = vertex;
+    }
+    return ds.y * sqrt(ds.x);
+}
+
+// Distance to the open line through the extra params of the shape, in xy
+fn sd_polyline(p: vec2<f32>, extra_params: vec2<u32>) -> f32 {
+    let n = count(extra_params);
+    if (n == 0u) {
+        return 1e10;
+    }
+    var previous = get(extra_params, 0u).xy;
+    var d = length(p - previous);
+    for (var i = 1u; i < n; i++) {
+        let vertex = get(extra_params, i).xy;
+        d = min(d, smud::sd_segment(p, previous, vertex));
+        previous = vertex;
+    }
+    return d;
+}
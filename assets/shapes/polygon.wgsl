@@ -0,0 +1,10 @@
+#define_import_path smud::shapes::polygon
+
+#import smud
+#import smud::shape_params
+
+// Polygon SDF with the vertices in the extra params of the shape
+// xy of each extra param is a vertex, see SmudParams
+fn sdf(input: smud::SdfInput) -> f32 {
+    return shape_params::sd_polygon(input.pos, input.extra_params);
+}
@@ -0,0 +1,11 @@
+#define_import_path smud::shapes::polyline
+
+#import smud
+#import smud::shape_params
+
+// Polyline SDF with the vertices in the extra params of the shape
+// xy of each extra param is a vertex, see SmudParams
+// params.x contains half the thickness of the line
+fn sdf(input: smud::SdfInput) -> f32 {
+    return shape_params::sd_polyline(input.pos, input.extra_params) - input.params.x;
+}
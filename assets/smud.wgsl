@@ -8,14 +8,19 @@ struct SdfInput {
     pos: vec2<f32>,      // Position in shape space
     params: vec4<f32>,   // User-defined parameters
     bounds: vec2<f32>,   // Half-extents (width/2, height/2) of the node bounds
+    extra_params: vec2<u32>, // Offset and count of extra params, see smud::shape_params
 }
 
 // Input struct for fill functions containing all available data
 struct FillInput {
     pos: vec2<f32>,      // Position in shape space
-    params: vec4<f32>,   // User-defined parameters for fill
+    params: vec4<f32>,   // User-defined parameters for the sdf (params on the shape)
+    fill_params: vec4<f32>, // User-defined parameters for the fill (fill_params on the shape)
     distance: f32,
     color: vec4<f32>,
+    extra_params: vec2<u32>, // Offset and count of extra params, see smud::shape_params
+    bounds: vec2<f32>,   // Half size of the shape, see smud::fill_texture::uv
+    gradient: vec2<f32>, // Direction away from the shape in shape space, see smud::distance_gradient
 }
 
 fn sd_circle(p: vec2<f32>, r: f32) -> f32 {
@@ -410,25 +415,23 @@ fn sd_rounded_x(p_in: vec2<f32>, w: f32, r: f32) -> f32 {
     return length(p - min(p.x + p.y, w) * 0.5) - r;
 }
 
-// fn sd_polygon(vec2[N] v, p: vec2<f32>) -> f32 {
-//     var d = dot(p - v[0], p - v[0]);
-//     var s = 1.;
-//     for(int i = 0, j = N - 1; i < N; j = i, i++) {
-//         let e = v[j] - v[i];
-//         let w = p - v[i];
-//         let b = w - e * clamp(dot(w, e) / dot(e, e), 0., 1.);
-//         d = min(d, dot(b, b));
-//         let c = vec3<bool>(
-//             p.y >= v[i].y,
-//             p.y < v[j].y,
-//             e.x * w.y > e.y * w.x
-//         );
-//         if (all(c) || all(not(c))) {
-//             s *= -1.;
-//         }
-//     }
-//     return s * sqrt(d);
-// }
+// One edge of a polygon, from `previous` to `vertex`
+//
+// WGSL functions can't take arrays of any length, so polygons are measured one edge at a time,
+// see shape_params::sd_polygon. `ds` is the squared distance and the sign so far, start with
+// vec2(dot2_(p - first_vertex), 1.), and the distance is ds.y * sqrt(ds.x) after the last edge.
+fn sd_polygon_edge(p: vec2<f32>, previous: vec2<f32>, vertex: vec2<f32>, ds: vec2<f32>) -> vec2<f32> {
+    let e = previous - vertex;
+    let w = p - vertex;
+    let ee = dot(e, e);
+    let h = select(0., clamp(dot(w, e) / ee, 0., 1.), ee > 0.);
+    let b = w - e * h;
+    let c1 = p.y >= vertex.y;
+    let c2 = p.y < previous.y;
+    let c3 = e.x * w.y > e.y * w.x;
+    let crosses = (c1 && c2 && c3) || (!c1 && !c2 && !c3);
+    return vec2<f32>(min(ds.x, dot(b, b)), select(ds.y, -ds.y, crosses));
+}
 
 // https://www.iquilezles.org/www/articles/distfunctions2d/distfunctions2d.htm
 // Has huge issues with instability when close to a circle or very eccentric
@@ -634,6 +637,54 @@ fn sd_moon(p_in: vec2<f32>, d: f32, ra: f32, rb: f32) -> f32 {
     );
 }
 
+// Gradients
+//
+// These return the distance in x, and its gradient in yz, like iq's sdgXXX functions,
+// https://iquilezles.org/articles/distgradfunctions2d/
+// The gradient is zero where it isn't defined, like at the center of a circle.
+
+fn sdg_circle(p: vec2<f32>, r: f32) -> vec3<f32> {
+    let l = length(p);
+    let g = select(vec2<f32>(0.), p / l, l > 0.);
+    return vec3<f32>(l - r, g);
+}
+
+fn sdg_annulus(p: vec2<f32>, outer_radius: f32, inner_radius: f32) -> vec3<f32> {
+    let middle_radius = (outer_radius + inner_radius) * 0.5;
+    let thickness = (outer_radius - inner_radius) * 0.5;
+    let c = sdg_circle(p, middle_radius);
+    return vec3<f32>(abs(c.x) - thickness, c.yz * sign(c.x));
+}
+
+fn sdg_box(p: vec2<f32>, b: vec2<f32>) -> vec3<f32> {
+    let w = abs(p) - b;
+    let s = select(vec2<f32>(1.), vec2<f32>(-1.), p < vec2<f32>(0.));
+    let g = max(w.x, w.y);
+    let q = max(w, vec2<f32>(0.));
+    let l = length(q);
+    if (g > 0.) {
+        return vec3<f32>(l, s * q / l);
+    }
+    return vec3<f32>(g, s * select(vec2<f32>(0., 1.), vec2<f32>(1., 0.), w.x > w.y));
+}
+
+fn sdg_segment(p: vec2<f32>, a: vec2<f32>, b: vec2<f32>) -> vec3<f32> {
+    let pa = p - a;
+    let ba = b - a;
+    let baba = dot(ba, ba);
+    // A segment with a == b is a point
+    let h = select(0., clamp(dot(pa, ba) / baba, 0., 1.), baba > 0.);
+    let q = pa - ba * h;
+    let d = length(q);
+    let g = select(vec2<f32>(0.), q / d, d > 0.);
+    return vec3<f32>(d, g);
+}
+
+fn sdg_capsule(p: vec2<f32>, radius: f32, half_length: f32) -> vec3<f32> {
+    let s = sdg_segment(p, vec2<f32>(0., -half_length), vec2<f32>(0., half_length));
+    return vec3<f32>(s.x - radius, s.yz);
+}
+
 fn renormalize_uv(uv: vec2<f32>) -> vec2<f32> {
     return uv * 2. - vec2<f32>(1., 1.);
 }
@@ -652,6 +703,49 @@ fn exponential_falloff_3_(d: f32, size: f32) -> f32 {
     return a;
 }
 
+// Corrects a distance measured in shape space for non-uniform scale and skew.
+//
+// `transform` is the 2x2 matrix from shape space to world space (x axis in xy,
+// y axis in zw). For shapes in 3d, it's the matrix that scales and skews like the
+// transform does within the plane of the shape. The local gradient of the distance is reconstructed from screen
+// space derivatives, and the distance is rescaled so that it's measured in
+// uniformly scaled shape units. For uniformly scaled shapes, this returns `d`.
+fn correct_distance(d: f32, pos: vec2<f32>, transform: vec4<f32>) -> f32 {
+    // derivatives first, they need uniform control flow
+    let dx = dpdx(pos);
+    let dy = dpdy(pos);
+    let dd = vec2<f32>(dpdx(d), dpdy(d));
+
+    let j_det = dx.x * dy.y - dx.y * dy.x;
+    let g = vec2<f32>(dy.y * dd.x - dx.y * dd.y, -dy.x * dd.x + dx.x * dd.y) / j_det;
+
+    let det = transform.x * transform.w - transform.z * transform.y;
+    // inverse transpose of the transform applied to the local gradient direction
+    let n = normalize(g);
+    let w = vec2<f32>(transform.w * n.x - transform.y * n.y, -transform.z * n.x + transform.x * n.y) / det;
+    let corrected = d / (sqrt(abs(det)) * length(w));
+
+    let valid = abs(j_det) > 1e-12 && abs(det) > 1e-12 && dot(g, g) > 1e-12;
+    return select(d, corrected, valid);
+}
+
+// Direction in which the distance grows the fastest, in shape space
+//
+// Works for any distance, since the gradient is reconstructed from screen space derivatives
+// like in correct_distance. Returns zero where it can't be determined, like in the middle of
+// a shape. Needs to be called in uniform control flow.
+fn distance_gradient(d: f32, pos: vec2<f32>) -> vec2<f32> {
+    let dx = dpdx(pos);
+    let dy = dpdy(pos);
+    let dd = vec2<f32>(dpdx(d), dpdy(d));
+
+    let j_det = dx.x * dy.y - dx.y * dy.x;
+    let g = vec2<f32>(dy.y * dd.x - dx.y * dd.y, -dy.x * dd.x + dx.x * dd.y) / j_det;
+
+    let valid = abs(j_det) > 1e-12 && dot(g, g) > 1e-12;
+    return select(vec2<f32>(0.), normalize(g), valid);
+}
+
 fn sd_fill_alpha_fwidth(distance: f32) -> f32 {
     let aaf = 0.71 * fwidth(distance);
     return smoothstep(aaf, -aaf, distance);
@@ -732,6 +826,63 @@ fn op_smooth_intersect(d1: f32, d2: f32, k: f32) -> f32 {
     return mix(d2, d1, h) + k * h * (1. - h);
 }
 
+// Domain operations
+//
+// These transform the position before it's passed to a distance function, e.g.
+// smud::sd_circle(smud::op_repeat(p, vec2<f32>(50.)), 10.)
+
+// Infinite copies on a grid with the given spacing. Only exact if the shape fits in a cell.
+fn op_repeat(p: vec2<f32>, spacing: vec2<f32>) -> vec2<f32> {
+    return p - spacing * round(p / spacing);
+}
+
+// Like op_repeat, but only `limit` copies on each side of the original, per axis
+fn op_repeat_limited(p: vec2<f32>, spacing: vec2<f32>, limit: vec2<f32>) -> vec2<f32> {
+    return p - spacing * clamp(round(p / spacing), -limit, limit);
+}
+
+// Mirrors the right half of a shape onto the left half
+fn op_mirror_x(p: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(abs(p.x), p.y);
+}
+
+// Mirrors the top half of a shape onto the bottom half
+fn op_mirror_y(p: vec2<f32>) -> vec2<f32> {
+    return vec2<f32>(p.x, abs(p.y));
+}
+
+// `count` copies around the origin of the slice of a shape around the positive x axis
+fn op_polar_repeat(p: vec2<f32>, count: i32) -> vec2<f32> {
+    let sector = 6.283185 / f32(count);
+    let angle = modulo(atan2(p.y, p.x) + sector * 0.5, sector) - sector * 0.5;
+    return vec2<f32>(cos(angle), sin(angle)) * length(p);
+}
+
+// Stretches a shape by `h` in both directions from the origin. Only exact outside the shape.
+fn op_elongate(p: vec2<f32>, h: vec2<f32>) -> vec2<f32> {
+    return p - clamp(p, -h, h);
+}
+
+// Distance operations
+//
+// These change the distance returned by a distance function
+
+// Hollows out a shape, leaving `thickness` on each side of its edge
+fn op_onion(d: f32, thickness: f32) -> f32 {
+    return abs(d) - thickness;
+}
+
+// Grows a shape by `radius`, rounding its corners
+fn op_round(d: f32, radius: f32) -> f32 {
+    return d - radius;
+}
+
+// Adds a displacement, like noise or a sine wave, to a distance. The result is usually not an
+// exact distance anymore, so keep the displacement small and smooth.
+fn op_displace(d: f32, displacement: f32) -> f32 {
+    return d + displacement;
+}
+
 // // complex (and sometimes inexact shapes:)
 
 // fn sd_arrow_head(p: vec2<f32>, w: f32, h: f32) -> f32 {
@@ -1,9 +1,7 @@
 #define_import_path smud::vertex
 
-struct View {
-    view_proj: mat4x4<f32>,
-    world_position: vec3<f32>,
-};
+#import bevy_render::view::View
+
 @group(0) @binding(0)
 var<uniform> view: View;
 
@@ -12,10 +10,21 @@ struct Vertex {
     @location(0) position: vec3<f32>,
     @location(1) color: vec4<f32>,
     @location(2) params: vec4<f32>,
-    @location(3) rotation: vec2<f32>,
-    @location(4) scale: f32,
+    // 2x2 matrix with rotation, scale and skew, x axis in xy, y axis in zw
+    @location(3) transform: vec4<f32>,
     // z and w contain extra padding
     @location(5) bounds: vec4<f32>,
+    // offset and count in the extra params buffer
+    @location(6) extra_params: vec2<u32>,
+    @location(7) fill_params: vec4<f32>,
+#ifdef VIEW_3D
+    // z components of the x and y axes of the transform
+    @location(8) transform_z: vec2<f32>,
+    // offset of the quad center from the entity origin, before transform
+    @location(9) anchor_offset: vec2<f32>,
+    // 2x2 matrix that scales and skews like the transform does within the plane of the shape
+    @location(10) plane_transform: vec4<f32>,
+#endif
 };
 
 struct VertexOutput {
@@ -24,6 +33,9 @@ struct VertexOutput {
     @location(1) pos: vec2<f32>,
     @location(2) params: vec4<f32>,
     @location(3) bounds: vec2<f32>,
+    @location(4) @interpolate(flat) transform: vec4<f32>,
+    @location(5) @interpolate(flat) extra_params: vec2<u32>,
+    @location(6) fill_params: vec4<f32>,
 };
 
 @vertex
@@ -34,25 +46,50 @@ fn vertex(
     var out: VertexOutput;
     let x = select(-1., 1., i % 2u == 0u);
     let y = select(-1., 1., (i / 2u) % 2u == 0u);
-    let c = vertex.rotation.x;
-    let s = vertex.rotation.y;
+    let transform = mat2x2<f32>(vertex.transform.xy, vertex.transform.zw);
     // Scale by bounds first to get the rectangle shape
     let bounds_with_padding = vertex.bounds.xy + vertex.bounds.zw;
     let corner = vec2<f32>(x, y) * bounds_with_padding;
-    // Then rotate the rectangle
-    let rotated = vec2<f32>(corner.x * c - corner.y * s, corner.x * s + corner.y * c);
-    // Then apply scale
-    let pos = vertex.position + vec3<f32>(rotated * vertex.scale, 0.);
+#ifdef VIEW_3D
+    // position already contains the anchor offset in the xy plane, so start from the origin
+    let origin = vertex.position - vec3<f32>(transform * vertex.anchor_offset, 0.);
+    let local = transform * (corner + vertex.anchor_offset);
+#ifdef BILLBOARD
+    // Lay the shape out in the plane of the camera
+    let right = view.world_from_view[0].xyz;
+    let up = view.world_from_view[1].xyz;
+    let pos = origin + right * local.x + up * local.y;
+#else
+    let pos = origin + vec3<f32>(local, dot(vertex.transform_z, corner + vertex.anchor_offset));
+#endif
+#else
+    // Then apply rotation, scale and skew
+    let pos = vertex.position + vec3<f32>(transform * corner, 0.);
+#endif
     // Project the world position of the mesh into screen position
-    out.clip_position = view.view_proj * vec4<f32>(pos, 1.);
+    out.clip_position = view.clip_from_world * vec4<f32>(pos, 1.);
     out.color = vertex.color;
     out.params = vertex.params;
 #ifdef Y_DOWN
     // ui shaders have y down, so we need to flip the y coordinate
     out.pos = vec2<f32>(corner.x, -corner.y);
+    // ...and the transform should map from the flipped coordinates
+    out.transform = vec4<f32>(vertex.transform.xy, -vertex.transform.zw);
 #else
     out.pos = corner;
+#ifdef VIEW_3D
+#ifdef BILLBOARD
+    // The billboard basis is orthonormal, so the shape scales and skews like in 2d
+    out.transform = vertex.transform;
+#else
+    out.transform = vertex.plane_transform;
+#endif
+#else
+    out.transform = vertex.transform;
+#endif
 #endif
     out.bounds = vertex.bounds.xy;
+    out.extra_params = vertex.extra_params;
+    out.fill_params = vertex.fill_params;
     return out;
 }
\ No newline at end of file
@@ -1,8 +1,13 @@
-//! This example shows additive blending for shapes
+//! This example shows the different blend modes for shapes
 //!
 //! Additive blending adds the colors together, resulting in brighter areas
 //! where shapes overlap. This is useful for glow effects, light sources,
 //! or other luminous objects.
+//!
+//! Multiply, screen and subtract are useful for shadows, tinting overlays and
+//! light cookies, so they are drawn on top of a gray background to show the effect.
+//!
+//! Hold space to animate the shapes.
 
 use bevy::color::palettes::css;
 use bevy::prelude::*;
@@ -18,6 +23,18 @@ fn main() {
         .run();
 }
 
+const BLEND_MODES: [BlendMode; 7] = [
+    BlendMode::Alpha,
+    BlendMode::Additive,
+    BlendMode::PremultipliedAlpha,
+    BlendMode::Opaque,
+    BlendMode::Multiply,
+    BlendMode::Screen,
+    BlendMode::Subtract,
+];
+
+const ROW_SPACING: f32 = 170.;
+
 fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
     // add_sdf_expr expects a wgsl expression
     // p is the position of a fragment within the sdf shape, with 0, 0 at the center.
@@ -25,100 +42,74 @@ fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
     // radius as a parameter.
     let circle = shaders.add_sdf_expr("smud::sd_circle(input.pos, 70.)");
 
-    // Create overlapping circles with normal alpha blending
-    commands.spawn((
-        Transform::from_xyz(-150.0, 100.0, 0.0),
-        SmudShape {
-            color: css::RED.with_alpha(0.8).into(),
-            sdf: circle.clone(),
-            bounds: Rectangle::from_length(160.),
-            blend_mode: BlendMode::Alpha, // Normal alpha blending
-            ..default()
-        },
-        AlphaBlendedShape,
-    ));
-
-    commands.spawn((
-        Transform::from_xyz(-50.0, 100.0, 0.0),
-        SmudShape {
-            color: css::GREEN.with_alpha(0.8).into(),
-            sdf: circle.clone(),
-            bounds: Rectangle::from_length(160.),
-            blend_mode: BlendMode::Alpha, // Normal alpha blending
-            ..default()
-        },
-        AlphaBlendedShape,
-    ));
+    // The default fill outputs straight alpha, so premultiply for PremultipliedAlpha
+    let premultiplied_fill = shaders.add_fill_body(
+        r"
+let a = smud::sd_fill_alpha_fwidth(d) * input.color.a;
+return vec4<f32>(input.color.rgb * a, a);
+",
+    );
 
+    // A gray background for the right column, so darkening modes are visible
     commands.spawn((
-        Transform::from_xyz(50.0, 100.0, 0.0),
+        Transform::from_xyz(300.0, 0.0, -1.0),
         SmudShape {
-            color: css::BLUE.with_alpha(0.8).into(),
-            sdf: circle.clone(),
-            bounds: Rectangle::from_length(160.),
-            blend_mode: BlendMode::Alpha, // Normal alpha blending
+            color: css::GRAY.into(),
+            sdf: shaders.add_sdf_expr("smud::sd_box(input.pos, vec2<f32>(250., 650.))"),
+            bounds: Rectangle::new(510., 1310.),
             ..default()
         },
-        AlphaBlendedShape,
     ));
 
-    // Create overlapping circles with additive blending
-    commands.spawn((
-        Transform::from_xyz(-150.0, -100.0, 0.0),
-        SmudShape {
-            color: css::RED.with_alpha(0.8).into(),
-            sdf: circle.clone(),
-            bounds: Rectangle::from_length(160.),
-            blend_mode: BlendMode::Additive, // Additive blending
-            ..default()
-        },
-        AdditiveBlendedShape,
-    ));
-
-    commands.spawn((
-        Transform::from_xyz(-50.0, -100.0, 0.0),
-        SmudShape {
-            color: css::GREEN.with_alpha(0.8).into(),
-            sdf: circle.clone(),
-            bounds: Rectangle::from_length(160.),
-            blend_mode: BlendMode::Additive, // Additive blending
-            ..default()
-        },
-        AdditiveBlendedShape,
-    ));
-
-    commands.spawn((
-        Transform::from_xyz(50.0, -100.0, 0.0),
-        SmudShape {
-            color: css::BLUE.with_alpha(0.8).into(),
-            sdf: circle.clone(),
-            bounds: Rectangle::from_length(160.),
-            blend_mode: BlendMode::Additive, // Additive blending
-            ..default()
-        },
-        AdditiveBlendedShape,
-    ));
+    for (row, blend_mode) in BLEND_MODES.into_iter().enumerate() {
+        // The first four modes go in the left column, the rest on the gray background
+        let (column_x, row_y) = if row < 4 {
+            (-300.0, (1.5 - row as f32) * ROW_SPACING)
+        } else {
+            (300.0, (5.0 - row as f32) * ROW_SPACING)
+        };
+
+        let fill = if blend_mode == BlendMode::PremultipliedAlpha {
+            premultiplied_fill.clone()
+        } else {
+            DEFAULT_FILL_HANDLE
+        };
+
+        for (i, color) in [css::RED, css::GREEN, css::BLUE].into_iter().enumerate() {
+            commands.spawn((
+                Transform::from_xyz(column_x + (i as f32 - 1.0) * 100.0, row_y, 0.0),
+                SmudShape {
+                    color: color.with_alpha(0.8).into(),
+                    sdf: circle.clone(),
+                    fill: fill.clone(),
+                    bounds: Rectangle::from_length(160.),
+                    blend_mode,
+                    ..default()
+                },
+                BlendRow {
+                    center: Vec2::new(column_x, row_y),
+                },
+            ));
+        }
+
+        commands.spawn((
+            Text2d::new(format!("{blend_mode:?}")),
+            Transform::from_xyz(column_x - 230.0, row_y, 1.0),
+        ));
+    }
 
     commands.spawn(Camera2d);
 }
 
 #[derive(Component)]
-struct AlphaBlendedShape;
-
-#[derive(Component)]
-struct AdditiveBlendedShape;
+struct BlendRow {
+    center: Vec2,
+}
 
 fn animate_shapes(
     time: Res<Time>,
     keys: Res<ButtonInput<KeyCode>>,
-    mut alpha_query: Query<
-        &mut Transform,
-        (With<AlphaBlendedShape>, Without<AdditiveBlendedShape>),
-    >,
-    mut additive_query: Query<
-        &mut Transform,
-        (With<AdditiveBlendedShape>, Without<AlphaBlendedShape>),
-    >,
+    mut shapes: Query<(&mut Transform, &BlendRow)>,
 ) {
     if !keys.pressed(KeyCode::Space) {
         return;
@@ -126,19 +117,10 @@ fn animate_shapes(
 
     let time_factor = time.elapsed_secs() * 2.0;
 
-    // Animate alpha blended shapes
-    for (i, mut transform) in alpha_query.iter_mut().enumerate() {
-        let angle = time_factor + i as f32 * 2.0 * std::f32::consts::PI / 3.0;
-        let radius = 50.0;
-        transform.translation.x = -100.0 + angle.cos() * radius;
-        transform.translation.y = 100.0 + angle.sin() * radius;
-    }
-
-    // Animate additive blended shapes
-    for (i, mut transform) in additive_query.iter_mut().enumerate() {
-        let angle = time_factor + i as f32 * 2.0 * std::f32::consts::PI / 3.0;
+    for (i, (mut transform, row)) in shapes.iter_mut().enumerate() {
+        let angle = time_factor + (i % 3) as f32 * 2.0 * std::f32::consts::PI / 3.0;
         let radius = 50.0;
-        transform.translation.x = -100.0 + angle.cos() * radius;
-        transform.translation.y = -100.0 + angle.sin() * radius;
+        transform.translation.x = row.center.x + angle.cos() * radius;
+        transform.translation.y = row.center.y + angle.sin() * radius;
     }
 }
@@ -0,0 +1,67 @@
+//! Shapes rotating around different anchor points
+//!
+//! The anchor is relative to the bounds of the shape, and is the point that ends up at the
+//! translation of the entity.
+
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_smud::prelude::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins((DefaultPlugins, SmudPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, rotate)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
+    let rect = shaders.add_sdf_expr("smud::sd_box(input.pos, input.bounds)");
+    let marker = shaders.add_sdf_expr("smud::sd_circle(input.pos, 5.)");
+
+    let anchors = [
+        (SmudAnchor::CENTER, css::ORANGE),
+        (SmudAnchor::BOTTOM_LEFT, css::TEAL),
+        (SmudAnchor::BOTTOM_CENTER, css::PINK),
+        (SmudAnchor(Vec2::new(0.25, 0.5)), css::LIME),
+    ];
+
+    for (i, (anchor, color)) in anchors.into_iter().enumerate() {
+        let translation = Vec3::new(i as f32 * 250. - 375., 0., 0.);
+
+        commands.spawn((
+            Transform::from_translation(translation),
+            SmudShape {
+                color: color.into(),
+                sdf: rect.clone(),
+                bounds: Rectangle::new(120., 60.),
+                ..default()
+            },
+            anchor,
+            Rotating,
+        ));
+
+        // Mark the anchor point
+        commands.spawn((
+            Transform::from_translation(translation + Vec3::Z),
+            SmudShape {
+                color: Color::WHITE,
+                sdf: marker.clone(),
+                bounds: Rectangle::from_length(12.),
+                ..default()
+            },
+        ));
+    }
+
+    commands.spawn(Camera2d);
+}
+
+#[derive(Component)]
+struct Rotating;
+
+fn rotate(time: Res<Time>, mut shapes: Query<&mut Transform, With<Rotating>>) {
+    for mut transform in &mut shapes {
+        transform.rotate_z(time.delta_secs());
+    }
+}
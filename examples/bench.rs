@@ -15,6 +15,7 @@ fn main() {
             LogDiagnosticsPlugin::default(),
             FrameTimeDiagnosticsPlugin::default(),
             SmudPlugin,
+            SmudDiagnosticsPlugin,
             PanCamPlugin,
             bevy_lospec::PalettePlugin,
         ))
@@ -0,0 +1,60 @@
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_pancam::*;
+use bevy_smud::prelude::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::srgb(0.2, 0.2, 0.2)))
+        .add_plugins((DefaultPlugins, SmudPlugin, PanCamPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut shaders: ResMut<Assets<Shader>>,
+) {
+    // `input.gradient` points away from the shape, so tilting the surface normal towards it
+    // near the edge makes the shape look raised. `fill_params.x` is the width of the bevel, and a
+    // negative `fill_params.y` presses the shape in instead.
+    let bevel_fill = shaders.add_fill_body(
+        r"
+let slope = smoothstep(-fill_params.x, 0., d) * fill_params.y;
+let normal = normalize(vec3<f32>(input.gradient * slope, 1.));
+let light = normalize(vec3<f32>(-1., 1., 1.));
+let shade = 0.3 + 0.7 * max(dot(normal, light), 0.);
+let a = smud::sd_fill_alpha_fwidth(d);
+return vec4<f32>(input.color.rgb * shade, a * input.color.a);
+",
+    );
+
+    commands.spawn(SmudShape {
+        color: css::TEAL.into(),
+        sdf: asset_server.load("bevy.wgsl"),
+        fill: bevel_fill.clone(),
+        fill_params: Vec4::new(20., 1.5, 0., 0.),
+        bounds: Rectangle::from_length(590.),
+        ..default()
+    });
+
+    commands.spawn((
+        Transform::from_translation(Vec3::X * 500.),
+        SmudShape::from(Circle::new(150.))
+            .with_color(css::ORANGE)
+            .with_fill(bevel_fill.clone())
+            .with_fill_params(Vec4::new(40., 1., 0., 0.)),
+    ));
+
+    // Emboss
+    commands.spawn((
+        Transform::from_translation(Vec3::X * -500.),
+        SmudShape::from(Rectangle::new(300., 200.))
+            .with_color(css::GOLD)
+            .with_fill(bevel_fill)
+            .with_fill_params(Vec4::new(15., -1., 0., 0.)),
+    ));
+
+    commands.spawn((Camera2d, PanCam::default(), Msaa::Off));
+}
@@ -62,6 +62,37 @@ fn setup(mut commands: Commands) {
         OriginalColor(css::AQUA.into()),
     ));
 
+    // Polygons and polylines keep their vertices in SmudParams
+    let arrow = Polygon::new([
+        Vec2::new(-30., -8.),
+        Vec2::new(5., -8.),
+        Vec2::new(5., -25.),
+        Vec2::new(30., 0.),
+        Vec2::new(5., 25.),
+        Vec2::new(5., 8.),
+        Vec2::new(-30., 8.),
+    ]);
+    commands.spawn((
+        Transform::from_translation(Vec3::new(-60., 0., 0.)),
+        SmudShape::from(arrow.clone()).with_color(css::SALMON),
+        SmudParams::from(arrow),
+        OriginalColor(css::SALMON.into()),
+    ));
+
+    let zigzag = Polyline2d::new([
+        Vec2::new(-30., -15.),
+        Vec2::new(-10., 15.),
+        Vec2::new(10., -15.),
+        Vec2::new(30., 15.),
+    ]);
+    commands.spawn((
+        Transform::from_translation(Vec3::new(60., 0., 0.)),
+        // Half the thickness of the line is 3
+        SmudShape::from_polyline(&zigzag, 3.).with_color(css::SPRING_GREEN),
+        SmudParams::from(zigzag),
+        OriginalColor(css::SPRING_GREEN.into()),
+    ));
+
     // Row 2: Animated bounds (shapes smoothly transition between tall and wide)
     commands.spawn((
         Transform::from_translation(Vec3::new(-420., -150., 0.)),
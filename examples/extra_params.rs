@@ -0,0 +1,64 @@
+//! This example shows how to pass more than four parameters to a shape
+//!
+//! Each shape is a smooth union of a variable number of circles, with the
+//! circles passed through the `SmudParams` component.
+
+use bevy::color::palettes::css;
+use bevy::prelude::*;
+use bevy_smud::prelude::*;
+use rand::random;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, SmudPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, wobble)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
+    // Each extra param is a circle, xy is the center and z is the radius
+    let blob = shaders.add_sdf_body(
+        r"
+var d = 1e10;
+let n = shape_params::count(input.extra_params);
+for (var i = 0u; i < n; i++) {
+    let circle = shape_params::get(input.extra_params, i);
+    d = smud::op_smooth_union(d, smud::sd_circle(p - circle.xy, circle.z), 15.);
+}
+return d;
+",
+    );
+
+    for (i, color) in [css::TOMATO, css::TEAL, css::GOLD].into_iter().enumerate() {
+        let circles = (0..3 + i * 3)
+            .map(|_| {
+                let center = (Vec2::new(random(), random()) - 0.5) * 120.;
+                Vec4::new(center.x, center.y, 15. + random::<f32>() * 20., 0.)
+            })
+            .collect();
+
+        commands.spawn((
+            Transform::from_xyz((i as f32 - 1.) * 250., 0., 0.),
+            SmudShape {
+                color: color.into(),
+                sdf: blob.clone(),
+                bounds: Rectangle::from_length(220.),
+                ..default()
+            },
+            SmudParams(circles),
+        ));
+    }
+
+    commands.spawn(Camera2d);
+}
+
+fn wobble(time: Res<Time>, mut params: Query<&mut SmudParams>) {
+    let t = time.elapsed_secs();
+    for mut params in &mut params {
+        for (i, circle) in params.iter_mut().enumerate() {
+            circle.w = (t * 2. + i as f32).sin();
+            circle.x += circle.w * 0.2;
+        }
+    }
+}
@@ -20,7 +20,7 @@ fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
         let gradient = (input.pos.x + input.pos.y) * 0.01 + 0.5;
 
         // Use fill_params for color modulation
-        let color_mod = input.params.x * 0.5 + 0.5;
+        let color_mod = input.fill_params.x * 0.5 + 0.5;
 
         let final_color = vec3<f32>(
             input.color.r * gradient,
@@ -36,7 +36,7 @@ fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
         color: Color::WHITE,
         sdf: circle,
         fill: position_test_fill,
-        params: Vec4::new(1.0, 0.0, 0.0, 0.0), // Red modulation
+        fill_params: Vec4::new(1.0, 0.0, 0.0, 0.0), // Red modulation
         bounds: Rectangle::from_length(110.),
         ..default()
     });
@@ -16,6 +16,8 @@ fn main() {
             PanCamPlugin,
             bevy_lospec::PalettePlugin,
         ))
+        // Shapes are all at the same z, so let them be grouped by shader to reduce draw calls
+        .insert_resource(SmudBatchSorting::default())
         .init_state::<GameState>()
         .add_loading_state(
             LoadingState::new(GameState::Loading)
@@ -0,0 +1,93 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    image::ImageSampler,
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+use bevy_pancam::*;
+use bevy_smud::prelude::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins((DefaultPlugins, SmudPlugin, PanCamPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // Sample the image of the shape, and use the sdf for anti-aliased edges
+    let image_fill = shaders.add_fill_body(
+        r"
+let texel = fill_texture::sample(fill_texture::uv(input));
+let a = smud::sd_fill_alpha_fwidth(d);
+return vec4<f32>(texel.rgb * input.color.rgb, a * texel.a * input.color.a);
+",
+    );
+
+    let checkerboard_image = images.add(checkerboard(8, 16));
+
+    commands.spawn(
+        SmudShape {
+            color: Color::WHITE,
+            sdf: asset_server.load("bevy.wgsl"),
+            fill: image_fill.clone(),
+            bounds: Rectangle::from_length(590.),
+            ..default()
+        }
+        .with_image(checkerboard_image),
+    );
+
+    // The same image with a different sampler
+    let mut smooth_checkerboard = checkerboard(8, 16);
+    smooth_checkerboard.sampler = ImageSampler::linear();
+
+    commands.spawn((
+        Transform::from_translation(Vec3::X * 600.),
+        SmudShape {
+            color: Color::srgb(1.0, 0.8, 0.6),
+            sdf: shaders.add_sdf_expr("smud::sd_circle(input.pos, 250.)"),
+            fill: image_fill,
+            bounds: Rectangle::from_length(520.),
+            ..default()
+        }
+        .with_image(images.add(smooth_checkerboard)),
+    ));
+
+    commands.spawn((Camera2d, PanCam::default()));
+}
+
+/// A black and white checkerboard with `tiles` squares along each side
+fn checkerboard(tiles: u32, tile_size: u32) -> Image {
+    let size = tiles * tile_size;
+    let mut data = Vec::with_capacity((size * size * 4) as usize);
+    for y in 0..size {
+        for x in 0..size {
+            let value = if (x / tile_size + y / tile_size).is_multiple_of(2) {
+                255
+            } else {
+                40
+            };
+            data.extend_from_slice(&[value, value, value, 255]);
+        }
+    }
+
+    let mut image = Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.sampler = ImageSampler::nearest();
+    image
+}
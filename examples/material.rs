@@ -0,0 +1,102 @@
+use bevy::{
+    asset::RenderAssetUsages,
+    prelude::*,
+    render::render_resource::{AsBindGroup, Extent3d, TextureDimension, TextureFormat},
+    shader::ShaderRef,
+};
+use bevy_pancam::*;
+use bevy_smud::prelude::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::srgb(0.1, 0.1, 0.1)))
+        .add_plugins((
+            DefaultPlugins,
+            SmudPlugin,
+            SmudMaterialPlugin::<FireFill>::default(),
+            PanCamPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+/// A fill with typed parameters, instead of packing everything into `fill_params`
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+struct FireFill {
+    #[uniform(0)]
+    speed: f32,
+    #[texture(1)]
+    #[sampler(2)]
+    palette: Handle<Image>,
+}
+
+impl SmudMaterial for FireFill {
+    fn fill_shader() -> ShaderRef {
+        "fills/fire_material.wgsl".into()
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<FireFill>>,
+) {
+    let palette = images.add(palette(&[
+        Color::BLACK,
+        Color::srgb(0.8, 0.1, 0.0),
+        Color::srgb(1.0, 0.5, 0.0),
+        Color::srgb(1.0, 0.9, 0.5),
+        Color::WHITE,
+    ]));
+
+    let slow = materials.add(FireFill {
+        speed: 0.5,
+        palette: palette.clone(),
+    });
+    let fast = materials.add(FireFill {
+        speed: 3.0,
+        palette,
+    });
+
+    let circle = shaders.add_sdf_expr("smud::sd_circle(input.pos, 100.)");
+
+    // Shapes sharing a material are drawn in the same batch
+    for (i, material) in [&slow, &fast, &slow, &fast].into_iter().enumerate() {
+        commands.spawn((
+            Transform::from_translation(Vec3::new(i as f32 * 250. - 375., 0., 0.)),
+            SmudShape {
+                sdf: circle.clone(),
+                bounds: Rectangle::from_length(250.),
+                ..default()
+            },
+            ShapeMaterial(material.clone()),
+        ));
+    }
+
+    commands.spawn((Camera2d, PanCam::default()));
+}
+
+/// A horizontal gradient through the given colors
+fn palette(colors: &[Color]) -> Image {
+    let width = 256;
+    let mut data = Vec::with_capacity(width * 4);
+    for x in 0..width {
+        let t = x as f32 / (width - 1) as f32 * (colors.len() - 1) as f32;
+        let i = (t as usize).min(colors.len() - 2);
+        let color = colors[i].mix(&colors[i + 1], t - i as f32);
+        data.extend_from_slice(&color.to_srgba().to_u8_array());
+    }
+
+    Image::new(
+        Extent3d {
+            width: width as u32,
+            height: 1,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
@@ -0,0 +1,56 @@
+//! Many overlapping opaque shapes
+//!
+//! Opaque shapes write depth, so fragments hidden behind shapes drawn earlier are rejected by
+//! the depth test instead of being shaded and blended. Each batch is drawn front to back.
+
+use bevy::{
+    diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    prelude::*,
+};
+use bevy_pancam::*;
+use bevy_smud::prelude::*;
+use rand::prelude::*;
+
+fn main() {
+    App::new()
+        .insert_resource(ClearColor(Color::BLACK))
+        .add_plugins((
+            DefaultPlugins,
+            LogDiagnosticsPlugin::default(),
+            FrameTimeDiagnosticsPlugin::default(),
+            SmudPlugin,
+            PanCamPlugin,
+        ))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
+    let mut rng = rand::rng();
+
+    let tile = shaders.add_sdf_expr("smud::sd_rounded_box(input.pos, input.bounds, vec4(8.))");
+    let spacing = 40.0;
+    let size = 100;
+
+    for i in 0..size {
+        for j in 0..size {
+            // Tiles overlap their neighbours, and are stacked in random order
+            commands.spawn((
+                Transform::from_xyz(
+                    (i - size / 2) as f32 * spacing,
+                    (j - size / 2) as f32 * spacing,
+                    rng.random_range(0.0..100.0),
+                ),
+                SmudShape {
+                    color: Color::hsl(rng.random_range(0.0..360.0), 0.6, 0.5),
+                    sdf: tile.clone(),
+                    bounds: Rectangle::from_length(spacing * 1.8),
+                    blend_mode: BlendMode::Opaque,
+                    ..default()
+                },
+            ));
+        }
+    }
+
+    commands.spawn((Camera2d, PanCam::default()));
+}
@@ -26,7 +26,7 @@ fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
         Pickable::default(),
         SmudPickingShape::new(|p| {
             // Circle SDF using the sdf module
-            sdf::circle(p, 100.0)
+            sdf::sd_circle(p, 100.0)
         }),
     ));
 
@@ -54,7 +54,7 @@ fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
         Pickable::default(),
         SmudPickingShape::new(|p| {
             // Heart SDF using the sdf module, scaled and offset
-            sdf::heart((p / 160.0) - Vec2::new(0.0, -0.5)) * 160.0
+            sdf::sd_heart((p / 160.0) - Vec2::new(0.0, -0.5)) * 160.0
         }),
     ));
 
@@ -0,0 +1,47 @@
+//! Rendering shapes into an offscreen image
+//!
+//! Shapes on layer 1 are rendered into an image, which is then shown with a sprite. The image
+//! could just as well be sampled by a compute shader or another render pass.
+
+use bevy::{camera::visibility::RenderLayers, prelude::*, render::render_resource::TextureFormat};
+use bevy_smud::{SmudRenderTarget, prelude::*};
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, SmudPlugin))
+        .add_systems(Startup, setup)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let offscreen = RenderLayers::layer(1);
+
+    let image = SmudRenderTarget::new(UVec2::new(256, 256), TextureFormat::Rgba16Float)
+        .with_layers(offscreen.clone())
+        .with_clear_color(Color::srgb(0.1, 0.1, 0.2))
+        .spawn(&mut commands, &mut images);
+
+    commands.spawn((
+        SmudShape {
+            color: Color::srgb(0.9, 0.6, 0.2),
+            sdf: shaders.add_sdf_expr("smud::sd_star_5_(input.pos, 100., 2.)"),
+            bounds: Rectangle::from_length(240.),
+            ..default()
+        },
+        offscreen,
+    ));
+
+    // The image is drawn twice by the main camera
+    for x in [-150., 150.] {
+        commands.spawn((
+            Sprite::from_image(image.clone()),
+            Transform::from_xyz(x, 0., 0.),
+        ));
+    }
+
+    commands.spawn(Camera2d);
+}
@@ -0,0 +1,92 @@
+//! Shapes in a 3d scene
+//!
+//! Shapes seen by a 3d camera are depth tested against meshes. Billboards always face the
+//! camera, other shapes lie in the xy plane of their transform.
+
+use bevy::{color::palettes::css, prelude::*};
+use bevy_smud::prelude::*;
+
+fn main() {
+    App::new()
+        .add_plugins((DefaultPlugins, SmudPlugin))
+        .add_systems(Startup, setup)
+        .add_systems(Update, orbit_camera)
+        .run();
+}
+
+fn setup(
+    mut commands: Commands,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let circle = shaders.add_sdf_expr("smud::sd_circle(input.pos, 40.)");
+    let star = shaders.add_sdf_expr("smud::sd_star_5_(input.pos, 40., 2.)");
+
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(10., 10.))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.5, 0.3))),
+    ));
+
+    let cube = meshes.add(Cuboid::new(1., 1., 1.));
+    let cube_material = materials.add(Color::srgb(0.8, 0.7, 0.6));
+
+    for (i, color) in [css::TOMATO, css::GOLD, css::DEEP_SKY_BLUE]
+        .into_iter()
+        .enumerate()
+    {
+        let x = (i as f32 - 1.) * 2.5;
+
+        commands.spawn((
+            Mesh3d(cube.clone()),
+            MeshMaterial3d(cube_material.clone()),
+            Transform::from_xyz(x, 0.5, 0.),
+        ));
+
+        // Floats above the cube, always facing the camera
+        commands.spawn((
+            Transform::from_xyz(x, 1.6, 0.).with_scale(Vec3::splat(0.01)),
+            SmudShape {
+                color: color.into(),
+                sdf: circle.clone(),
+                bounds: Rectangle::from_length(100.),
+                ..default()
+            },
+            SmudBillboard,
+        ));
+    }
+
+    // Lies flat on the ground, and is hidden by the cubes in front of it
+    commands.spawn((
+        Transform::from_xyz(0., 0.01, -2.)
+            .with_rotation(Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2))
+            .with_scale(Vec3::splat(0.03)),
+        SmudShape {
+            color: css::WHITE.into(),
+            sdf: star,
+            bounds: Rectangle::from_length(100.),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        DirectionalLight {
+            shadow_maps_enabled: true,
+            ..default()
+        },
+        Transform::from_xyz(4., 8., 4.).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+
+    commands.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(0., 3., 8.).looking_at(Vec3::ZERO, Vec3::Y),
+    ));
+}
+
+fn orbit_camera(time: Res<Time>, mut cameras: Query<&mut Transform, With<Camera3d>>) {
+    for mut transform in &mut cameras {
+        let angle = time.elapsed_secs() * 0.3;
+        *transform = Transform::from_xyz(8. * angle.sin(), 3., 8. * angle.cos())
+            .looking_at(Vec3::ZERO, Vec3::Y);
+    }
+}
@@ -1,4 +1,4 @@
-//! This example just shows that transforms work
+//! This example just shows that transforms work, including non-uniform scale and skew
 
 use bevy::prelude::*;
 use bevy_pancam::{PanCam, PanCamPlugin};
@@ -37,6 +37,22 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
             });
     });
 
+    // Non-uniform scale (squash and stretch)
+    commands.spawn((
+        Transform::from_xyz(-700., 0., 0.).with_scale(Vec3::new(1.5, 0.6, 1.)),
+        shape.clone(),
+    ));
+
+    // Skew, by rotating a child of a non-uniformly scaled parent
+    commands
+        .spawn((
+            Transform::from_xyz(700., 0., 0.).with_scale(Vec3::new(1.5, 0.6, 1.)),
+            Visibility::default(),
+        ))
+        .with_children(|parent| {
+            parent.spawn((Transform::from_rotation(Quat::from_rotation_z(0.5)), shape));
+        });
+
     // bevy_smud comes with anti-aliasing built into the standard fills
     // which is more efficient than MSAA, and also works on Linux, wayland
     commands.spawn((Camera2d, PanCam::default(), Msaa::Off));
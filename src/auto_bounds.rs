@@ -0,0 +1,212 @@
+//! Automatic computation of [`SmudShape::bounds`]
+
+use bevy::prelude::*;
+
+use crate::SmudShape;
+
+#[cfg(feature = "bevy_picking")]
+use crate::picking_backend::{SdfInput, SmudPickingShape};
+
+/// Opt-in component that keeps [`SmudShape::bounds`] tight around the shape
+///
+/// Shapes created from bevy primitives are already sized by their bounds, so they keep them,
+/// except that circles get square bounds. Bounds are only actually computed for other shapes, by
+/// sampling the distance function of their [`SmudPickingShape`](crate::prelude::SmudPickingShape)
+/// on the cpu. Shapes with neither are left alone.
+///
+/// Bounds are recomputed whenever the sdf, params, picking shape or this component changes, for
+/// instance when `params` are animated. While sampling, the distance function gets the search
+/// area as its bounds, so shapes sized by their own bounds can't feed back into the result.
+/// Padding for anti-aliasing still comes from [`SmudShape::extra_bounds`].
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component, Default, Debug, Clone)]
+#[require(AutoBoundsInput)]
+pub struct AutoBounds {
+    /// Half size of the area searched for the shape when sampling, in local space
+    pub search_extent: f32,
+    /// Number of samples along each axis, for each sampling pass
+    pub resolution: u32,
+}
+
+impl Default for AutoBounds {
+    fn default() -> Self {
+        Self {
+            search_extent: 1024.,
+            resolution: 64,
+        }
+    }
+}
+
+impl AutoBounds {
+    /// Find bounds, centered on the origin, containing the shape of a signed distance function
+    ///
+    /// A coarse pass over the search area finds the shape, and a second pass over a smaller
+    /// area refines it. Every sample inside the shape means the shape extends at least its
+    /// distance beyond that sample, so exact distance functions give bounds that are tight even
+    /// between samples. Shapes thinner than the sample spacing of the first pass may be missed.
+    ///
+    /// Returns `None` if no samples were inside the shape.
+    pub fn sample_bounds(&self, distance: impl Fn(Vec2) -> f32) -> Option<Rectangle> {
+        let resolution = self.resolution.max(1);
+        let mut extent = Vec2::splat(self.search_extent);
+        let mut found = None;
+
+        for _ in 0..2 {
+            let step = 2. * extent / resolution as f32;
+            let mut half_size: Option<Vec2> = None;
+
+            for i in 0..=resolution {
+                for j in 0..=resolution {
+                    let p = -extent + step * Vec2::new(i as f32, j as f32);
+                    let d = distance(p);
+                    if d <= 0. {
+                        // Everything closer to p than -d is inside the shape
+                        let reach = p.abs() - d;
+                        half_size = Some(half_size.map_or(reach, |h| h.max(reach)));
+                    }
+                }
+            }
+
+            let Some(half_size) = half_size else {
+                break;
+            };
+            found = Some(half_size);
+            // Leave room for parts of the shape that were between samples
+            extent = half_size + step;
+        }
+
+        found.map(|half_size| Rectangle { half_size })
+    }
+}
+
+/// What the bounds of a shape with [`AutoBounds`] were last computed from
+#[derive(Component, Default, PartialEq)]
+pub(crate) struct AutoBoundsInput(Option<(AssetId<Shader>, Vec4, AutoBounds)>);
+
+/// Update the bounds of shapes with [`AutoBounds`]
+#[cfg_attr(not(feature = "bevy_picking"), allow(unused_variables))]
+pub(crate) fn update_auto_bounds(
+    mut shapes: Query<(Entity, &mut SmudShape, &AutoBounds, &mut AutoBoundsInput)>,
+    #[cfg(feature = "bevy_picking")] picking_shapes: Query<Ref<SmudPickingShape>>,
+) {
+    for (entity, mut shape, auto_bounds, mut last_input) in &mut shapes {
+        // Other changes, such as the color, don't affect the bounds
+        let input = AutoBoundsInput(Some((shape.sdf.id(), shape.params, *auto_bounds)));
+        #[cfg(feature = "bevy_picking")]
+        let picking_changed = picking_shapes
+            .get(entity)
+            .is_ok_and(|picking_shape| picking_shape.is_changed());
+        #[cfg(not(feature = "bevy_picking"))]
+        let picking_changed = false;
+        if *last_input == input && !picking_changed {
+            continue;
+        }
+        *last_input = input;
+
+        #[cfg(feature = "bevy_primitives")]
+        let bounds = crate::bevy_primitives::primitive_bounds(&shape);
+        #[cfg(not(feature = "bevy_primitives"))]
+        let bounds: Option<Rectangle> = None;
+
+        #[cfg(feature = "bevy_picking")]
+        let bounds = bounds.or_else(|| {
+            let picking_shape = picking_shapes.get(entity).ok()?;
+            let input_bounds = Vec2::splat(auto_bounds.search_extent);
+            let params = shape.params;
+            auto_bounds.sample_bounds(|pos| {
+                (picking_shape.distance_fn)(SdfInput {
+                    pos,
+                    bounds: input_bounds,
+                    params,
+                })
+            })
+        });
+
+        // Only write on changes, so change detection isn't triggered every frame
+        if let Some(bounds) = bounds
+            && shape.bounds != bounds
+        {
+            shape.bounds = bounds;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sdf;
+
+    #[test]
+    fn test_sample_circle_bounds() {
+        let bounds = AutoBounds::default()
+            .sample_bounds(|p| sdf::sd_circle(p, 50.))
+            .expect("circle should be found");
+
+        assert!((bounds.half_size - Vec2::splat(50.)).abs().max_element() < 0.5);
+    }
+
+    #[test]
+    fn test_sample_box_bounds() {
+        let bounds = AutoBounds::default()
+            .sample_bounds(|p| sdf::sd_box(p, Vec2::new(50., 10.)))
+            .expect("box should be found");
+
+        assert!((bounds.half_size - Vec2::new(50., 10.)).abs().max_element() < 0.5);
+    }
+
+    #[test]
+    fn test_sample_offset_shape_bounds() {
+        // Bounds are centered on the origin, so they cover the shape on both sides
+        let bounds = AutoBounds::default()
+            .sample_bounds(|p| sdf::sd_circle(p - Vec2::new(100., 0.), 20.))
+            .expect("circle should be found");
+
+        assert!(
+            (bounds.half_size - Vec2::new(120., 20.))
+                .abs()
+                .max_element()
+                < 0.5
+        );
+    }
+
+    #[test]
+    fn test_sample_empty_shape() {
+        assert!(AutoBounds::default().sample_bounds(|_| 1.).is_none());
+    }
+
+    #[cfg(feature = "bevy_picking")]
+    #[test]
+    fn test_changed_params_update_bounds() {
+        let mut app = App::new();
+        app.add_systems(Update, update_auto_bounds);
+        let entity = app
+            .world_mut()
+            .spawn((
+                SmudShape {
+                    params: Vec4::new(50., 0., 0., 0.),
+                    ..default()
+                },
+                AutoBounds::default(),
+                SmudPickingShape::with_input(|input| sdf::sd_circle(input.pos, input.params.x)),
+            ))
+            .id();
+        let half_size = |app: &App| {
+            app.world()
+                .get::<SmudShape>(entity)
+                .unwrap()
+                .bounds
+                .half_size
+        };
+
+        app.update();
+        assert!((half_size(&app) - Vec2::splat(50.)).abs().max_element() < 0.5);
+
+        app.world_mut()
+            .get_mut::<SmudShape>(entity)
+            .unwrap()
+            .params
+            .x = 20.;
+        app.update();
+        assert!((half_size(&app) - Vec2::splat(20.)).abs().max_element() < 0.5);
+    }
+}
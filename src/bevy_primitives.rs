@@ -35,19 +35,48 @@
 //! ));
 //! ```
 //!
+//! # Polygons and Polylines
+//!
+//! The vertices of [`Polygon`], [`ConvexPolygon`] and [`Polyline2d`] don't fit in
+//! [`SmudShape::params`], so they are stored in [`SmudParams`] instead, one vertex per
+//! parameter. Spawn both:
+//!
+//! ```no_run
+//! # use bevy::prelude::*;
+//! # use bevy_smud::prelude::*;
+//! # let mut commands: Commands = panic!();
+//! let arrow = Polygon::new([
+//!     Vec2::new(-40., -10.),
+//!     Vec2::new(10., -10.),
+//!     Vec2::new(10., -30.),
+//!     Vec2::new(40., 0.),
+//!     Vec2::new(10., 30.),
+//!     Vec2::new(10., 10.),
+//!     Vec2::new(-40., 10.),
+//! ]);
+//! commands.spawn((SmudShape::from(arrow.clone()), SmudParams::from(arrow)));
+//! ```
+//!
+//! Lines made from a [`Polyline2d`] are two units thick, `params.x` of the shape is half the
+//! thickness. Use [`SmudShape::from_polyline`] for other thicknesses, so the bounds grow with
+//! the line.
+//!
 //! # Picking Support
 //!
 //! When the `bevy_picking` feature is enabled, `SmudPickingShape` is automatically
-//! added to entities with primitive-based shapes for precise hit-testing.
+//! added to entities with primitive-based shapes for precise hit-testing. For polygons and
+//! polylines, the picking shape is rebuilt from the vertices in [`SmudParams`] whenever they
+//! change.
 
 use bevy::asset::{load_internal_asset, uuid_handle};
 use bevy::math::bounding::Bounded2d;
 use bevy::math::primitives::{
-    Annulus, Capsule2d, Circle, CircularSector, Ellipse, Rectangle, RegularPolygon, Rhombus,
+    Annulus, Capsule2d, Circle, CircularSector, ConvexPolygon, Ellipse, Polygon, Polyline2d,
+    Rectangle, RegularPolygon, Rhombus,
 };
 use bevy::prelude::*;
 
-use crate::SmudShape;
+use crate::{SmudParams, SmudShape};
 
 #[cfg(feature = "bevy_picking")]
 use crate::{picking_backend::SdfInput, sdf};
@@ -121,6 +150,13 @@ pub const CIRCULAR_SECTOR_SDF_HANDLE: Handle<Shader> =
 pub const REGULAR_POLYGON_SDF_HANDLE: Handle<Shader> =
     uuid_handle!("38dc4249-e998-4a6f-ace5-c619ae875929");
 
+/// Polygon shape SDF, with the vertices in [`SmudParams`]
+pub const POLYGON_SDF_HANDLE: Handle<Shader> = uuid_handle!("24721ec2-4001-43aa-8fe7-143201d42434");
+
+/// Polyline shape SDF, with the vertices in [`SmudParams`]
+pub const POLYLINE_SDF_HANDLE: Handle<Shader> =
+    uuid_handle!("c66c2895-f52e-4397-a1a5-0603ef3961be");
+
 /// Plugin that adds support for Bevy primitive shapes.
 ///
 /// This plugin:
@@ -182,10 +218,25 @@ impl Plugin for BevyPrimitivesPlugin {
             "../assets/shapes/regular_polygon.wgsl",
             Shader::from_wgsl
         );
+        load_internal_asset!(
+            app,
+            POLYGON_SDF_HANDLE,
+            "../assets/shapes/polygon.wgsl",
+            Shader::from_wgsl
+        );
+        load_internal_asset!(
+            app,
+            POLYLINE_SDF_HANDLE,
+            "../assets/shapes/polyline.wgsl",
+            Shader::from_wgsl
+        );
 
         // Register observers for auto-adding picking shapes
         #[cfg(feature = "bevy_picking")]
-        app.add_observer(auto_add_picking_shape);
+        app.add_observer(auto_add_picking_shape).add_systems(
+            PostUpdate,
+            update_vertex_picking_shapes.before(crate::auto_bounds::update_auto_bounds),
+        );
     }
 }
 
@@ -229,7 +280,7 @@ impl SmudPrimitive for Circle {
         // Circle uses min(bounds.x, bounds.y) for radius in shader
         Box::new(move |input| {
             let radius = input.bounds.x.min(input.bounds.y);
-            sdf::circle(input.pos, radius)
+            sdf::sd_circle(input.pos, radius)
         })
     }
 }
@@ -257,9 +308,9 @@ impl SmudPrimitive for Ellipse {
             let b = input.bounds.y;
             const EPSILON: f32 = 1e-6;
             if (a - b).abs() < EPSILON {
-                sdf::circle(input.pos, a)
+                sdf::sd_circle(input.pos, a)
             } else {
-                sdf::ellipse(input.pos, a, b)
+                sdf::sd_ellipse(input.pos, a, b)
             }
         })
     }
@@ -290,7 +341,7 @@ impl SmudPrimitive for Annulus {
         Box::new(move |input| {
             let outer_radius = input.bounds.x.min(input.bounds.y);
             let inner_radius = input.params.x;
-            sdf::annulus(input.pos, outer_radius, inner_radius)
+            sdf::sd_annulus(input.pos, outer_radius, inner_radius)
         })
     }
 }
@@ -318,7 +369,7 @@ impl SmudPrimitive for Capsule2d {
         Box::new(move |input| {
             let radius = input.bounds.x.min(input.bounds.y);
             let half_length = input.bounds.y - radius;
-            sdf::capsule(input.pos, radius, half_length)
+            sdf::sd_capsule(input.pos, radius, half_length)
         })
     }
 }
@@ -341,7 +392,7 @@ impl SmudPrimitive for Rhombus {
     #[cfg(feature = "bevy_picking")]
     fn picking_fn(&self) -> Box<dyn Fn(SdfInput) -> f32 + Send + Sync> {
         // Rhombus uses bounds directly for half-diagonals
-        Box::new(move |input| sdf::rhombus(input.pos, input.bounds))
+        Box::new(move |input| sdf::sd_rhombus(input.pos, input.bounds))
     }
 }
 
@@ -379,7 +430,7 @@ impl SmudPrimitive for CircularSector {
         Box::new(move |input| {
             let radius = input.bounds.x.min(input.bounds.y);
             let c = Vec2::new(input.params.x, input.params.y); // sin, cos
-            sdf::pie(input.pos, c, radius)
+            sdf::sd_pie(input.pos, c, radius)
         })
     }
 }
@@ -417,11 +468,89 @@ impl SmudPrimitive for RegularPolygon {
         Box::new(move |input| {
             let radius = input.bounds.x.min(input.bounds.y);
             let sides = input.params.x as i32;
-            sdf::regular_polygon(input.pos, radius, sides)
+            sdf::sd_regular_polygon(input.pos, radius, sides)
         })
     }
 }
 
+/// Half the thickness of lines made from a [`Polyline2d`]
+const POLYLINE_HALF_THICKNESS: f32 = 1.0;
+
+/// Extra params with one vertex per param, in xy
+fn vertex_params(vertices: &[Vec2]) -> SmudParams {
+    SmudParams(vertices.iter().map(|v| v.extend(0.0).extend(0.0)).collect())
+}
+
+/// Bounds centered on the origin that contain all the vertices
+fn vertex_bounds(vertices: &[Vec2]) -> Rectangle {
+    let half_size = vertices
+        .iter()
+        .fold(Vec2::ZERO, |half_size, v| half_size.max(v.abs()));
+    Rectangle { half_size }
+}
+
+impl From<Polygon> for SmudShape {
+    fn from(polygon: Polygon) -> Self {
+        Self {
+            sdf: POLYGON_SDF_HANDLE,
+            bounds: vertex_bounds(&polygon.vertices),
+            ..default()
+        }
+    }
+}
+
+impl From<Polygon> for SmudParams {
+    fn from(polygon: Polygon) -> Self {
+        vertex_params(&polygon.vertices)
+    }
+}
+
+impl From<ConvexPolygon> for SmudShape {
+    fn from(polygon: ConvexPolygon) -> Self {
+        Self {
+            sdf: POLYGON_SDF_HANDLE,
+            bounds: vertex_bounds(polygon.vertices()),
+            ..default()
+        }
+    }
+}
+
+impl From<ConvexPolygon> for SmudParams {
+    fn from(polygon: ConvexPolygon) -> Self {
+        vertex_params(polygon.vertices())
+    }
+}
+
+impl SmudShape {
+    /// Create a line along a [`Polyline2d`], `half_thickness` units thick on each side
+    ///
+    /// The bounds contain the thickness of the line. Spawn it with the [`SmudParams`] of the
+    /// polyline.
+    pub fn from_polyline(polyline: &Polyline2d, half_thickness: f32) -> Self {
+        let bounds = vertex_bounds(&polyline.vertices);
+        Self {
+            sdf: POLYLINE_SDF_HANDLE,
+            bounds: Rectangle {
+                half_size: bounds.half_size + half_thickness,
+            },
+            params: Vec4::new(half_thickness, 0.0, 0.0, 0.0),
+            ..default()
+        }
+    }
+}
+
+impl From<Polyline2d> for SmudShape {
+    fn from(polyline: Polyline2d) -> Self {
+        Self::from_polyline(&polyline, POLYLINE_HALF_THICKNESS)
+    }
+}
+
+impl From<Polyline2d> for SmudParams {
+    fn from(polyline: Polyline2d) -> Self {
+        vertex_params(&polyline.vertices)
+    }
+}
+
 impl<T: SmudPrimitive> From<T> for SmudShape {
     fn from(primitive: T) -> Self {
         Self {
@@ -440,6 +569,74 @@ impl<T: SmudPrimitive> From<T> for crate::picking_backend::SmudPickingShape {
     }
 }
 
+#[cfg(feature = "bevy_picking")]
+fn polygon_picking_shape(vertices: Vec<Vec2>) -> crate::picking_backend::SmudPickingShape {
+    crate::picking_backend::SmudPickingShape::new(move |p| sdf::sd_polygon(p, &vertices))
+}
+
+#[cfg(feature = "bevy_picking")]
+fn polyline_picking_shape(vertices: Vec<Vec2>) -> crate::picking_backend::SmudPickingShape {
+    // Half the thickness is in params.x, like in the shader
+    crate::picking_backend::SmudPickingShape::with_input(move |input| {
+        sdf::sd_polyline(input.pos, &vertices) - input.params.x
+    })
+}
+
+#[cfg(feature = "bevy_picking")]
+impl From<Polygon> for crate::picking_backend::SmudPickingShape {
+    fn from(polygon: Polygon) -> Self {
+        polygon_picking_shape(polygon.vertices)
+    }
+}
+
+#[cfg(feature = "bevy_picking")]
+impl From<ConvexPolygon> for crate::picking_backend::SmudPickingShape {
+    fn from(polygon: ConvexPolygon) -> Self {
+        polygon_picking_shape(polygon.vertices().to_vec())
+    }
+}
+
+#[cfg(feature = "bevy_picking")]
+impl From<Polyline2d> for crate::picking_backend::SmudPickingShape {
+    fn from(polyline: Polyline2d) -> Self {
+        polyline_picking_shape(polyline.vertices)
+    }
+}
+
+/// Try to create a picking shape for a polygon or polyline, from the vertices in its params
+#[cfg(feature = "bevy_picking")]
+fn vertex_picking_from_shape(
+    shape: &SmudShape,
+    params: &SmudParams,
+) -> Option<crate::picking_backend::SmudPickingShape> {
+    let vertices = params
+        .iter()
+        .map(|param| Vec2::new(param.x, param.y))
+        .collect();
+    if shape.sdf.id() == POLYGON_SDF_HANDLE.id() {
+        Some(polygon_picking_shape(vertices))
+    } else if shape.sdf.id() == POLYLINE_SDF_HANDLE.id() {
+        Some(polyline_picking_shape(vertices))
+    } else {
+        None
+    }
+}
+
+/// Bounds of the primitive a shape was created from, if any
+///
+/// Used by [`AutoBounds`](crate::AutoBounds).
+pub(crate) fn primitive_bounds(shape: &SmudShape) -> Option<Rectangle> {
+    Rectangle::try_from_shape(shape)
+        .map(|p| p.bounds())
+        .or_else(|| Circle::try_from_shape(shape).map(|p| p.bounds()))
+        .or_else(|| Ellipse::try_from_shape(shape).map(|p| p.bounds()))
+        .or_else(|| Annulus::try_from_shape(shape).map(|p| p.bounds()))
+        .or_else(|| Capsule2d::try_from_shape(shape).map(|p| p.bounds()))
+        .or_else(|| Rhombus::try_from_shape(shape).map(|p| p.bounds()))
+        .or_else(|| CircularSector::try_from_shape(shape).map(|p| p.bounds()))
+        .or_else(|| RegularPolygon::try_from_shape(shape).map(|p| p.bounds()))
+}
+
 /// Observer that automatically adds SmudPickingShape for shapes created from primitives
 #[cfg(feature = "bevy_picking")]
 fn auto_add_picking_shape(
@@ -465,6 +662,21 @@ fn auto_add_picking_shape(
     }
 }
 
+/// Rebuild the picking shapes of polygons and polylines when their vertices change
+///
+/// This also covers [`SmudParams`] inserted after the shape.
+#[cfg(feature = "bevy_picking")]
+fn update_vertex_picking_shapes(
+    shapes: Query<(Entity, &SmudShape, &SmudParams), Changed<SmudParams>>,
+    mut commands: Commands,
+) {
+    for (entity, shape, params) in &shapes {
+        if let Some(picking_shape) = vertex_picking_from_shape(shape, params) {
+            commands.entity(entity).insert(picking_shape);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -628,4 +840,55 @@ mod tests {
             "RegularPolygon sides should match after round-trip conversion"
         );
     }
+
+    #[test]
+    fn test_polygon_vertices_in_params() {
+        let polygon = Polygon::new([
+            Vec2::new(-10.0, -5.0),
+            Vec2::new(20.0, 0.0),
+            Vec2::new(0.0, 15.0),
+        ]);
+        let shape = SmudShape::from(polygon.clone());
+        let params = SmudParams::from(polygon);
+
+        assert_eq!(shape.sdf.id(), POLYGON_SDF_HANDLE.id());
+        assert_eq!(
+            shape.bounds.half_size,
+            Vec2::new(20.0, 15.0),
+            "Polygon bounds should contain every vertex"
+        );
+        assert_eq!(
+            params.0,
+            vec![
+                Vec4::new(-10.0, -5.0, 0.0, 0.0),
+                Vec4::new(20.0, 0.0, 0.0, 0.0),
+                Vec4::new(0.0, 15.0, 0.0, 0.0),
+            ],
+            "Polygon vertices should be stored one per param"
+        );
+    }
+
+    #[test]
+    fn test_polyline_bounds_include_thickness() {
+        let polyline = Polyline2d::new([Vec2::new(-10.0, 0.0), Vec2::new(10.0, 5.0)]);
+        let shape = SmudShape::from(polyline.clone());
+
+        assert_eq!(shape.sdf.id(), POLYLINE_SDF_HANDLE.id());
+        assert_eq!(shape.params.x, POLYLINE_HALF_THICKNESS);
+        assert_eq!(
+            shape.bounds.half_size,
+            Vec2::new(10.0, 5.0) + POLYLINE_HALF_THICKNESS,
+            "Polyline bounds should contain the thickness of the line"
+        );
+        assert_eq!(SmudParams::from(polyline).len(), 2);
+    }
+
+    #[test]
+    fn test_thick_polyline_bounds_include_thickness() {
+        let polyline = Polyline2d::new([Vec2::new(-10.0, 0.0), Vec2::new(10.0, 5.0)]);
+        let shape = SmudShape::from_polyline(&polyline, 3.0);
+
+        assert_eq!(shape.params.x, 3.0);
+        assert_eq!(shape.bounds.half_size, Vec2::new(13.0, 8.0));
+    }
 }
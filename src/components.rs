@@ -2,7 +2,10 @@ use bevy::camera::visibility::{VisibilityClass, add_visibility_class};
 use bevy::color::palettes::css;
 use bevy::math::primitives::Rectangle;
 use bevy::prelude::*;
-use bevy::render::sync_world::SyncToRenderWorld;
+use bevy::render::{
+    render_resource::{BlendComponent, BlendFactor, BlendOperation, BlendState},
+    sync_world::SyncToRenderWorld,
+};
 
 use crate::DEFAULT_FILL_HANDLE;
 
@@ -14,13 +17,95 @@ pub enum BlendMode {
     Alpha,
     /// Additive blending (colors are added together)
     Additive,
+    /// Multiplies the colors behind the shape, useful for shadows and tinting
+    Multiply,
+    /// Inverse of multiply, brightens the colors behind the shape
+    Screen,
+    /// Subtracts the color of the shape from the colors behind it
+    Subtract,
+    /// Alpha blending for fills that output colors already multiplied by alpha
+    PremultipliedAlpha,
+    /// No blending, the shape replaces the colors behind it
+    ///
+    /// Fragments with alpha below 0.5 are discarded, so edges are not anti-aliased.
+    /// Opaque shapes write depth, and the shapes of each batch are drawn front to back, which
+    /// saves a lot of overdraw for scenes with many overlapping shapes.
+    Opaque,
+}
+
+impl BlendMode {
+    pub(crate) fn blend_state(self) -> Option<BlendState> {
+        match self {
+            BlendMode::Alpha => Some(BlendState::ALPHA_BLENDING),
+            BlendMode::Additive => Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            }),
+            // The shader outputs mix(1, color, alpha), see `shader_def`
+            BlendMode::Multiply => Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::Zero,
+                    operation: BlendOperation::Add,
+                },
+                alpha: KEEP_DST_ALPHA,
+            }),
+            // The shader outputs premultiplied colors, see `shader_def`
+            BlendMode::Screen => Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::OneMinusDst,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            }),
+            BlendMode::Subtract => Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::ReverseSubtract,
+                },
+                alpha: KEEP_DST_ALPHA,
+            }),
+            BlendMode::PremultipliedAlpha => Some(BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+            BlendMode::Opaque => None,
+        }
+    }
+
+    /// Shader def for adjusting the output of the fill to the blend state
+    pub(crate) fn shader_def(self) -> Option<&'static str> {
+        match self {
+            BlendMode::Multiply => Some("BLEND_MULTIPLY"),
+            BlendMode::Screen => Some("BLEND_SCREEN"),
+            BlendMode::Opaque => Some("BLEND_OPAQUE"),
+            _ => None,
+        }
+    }
 }
 
+const KEEP_DST_ALPHA: BlendComponent = BlendComponent {
+    src_factor: BlendFactor::Zero,
+    dst_factor: BlendFactor::One,
+    operation: BlendOperation::Add,
+};
+
 #[derive(Component, Reflect, Debug, Clone)]
-#[require(Transform, Visibility, SyncToRenderWorld, VisibilityClass)] // TODO: anchor?
+#[require(Transform, Visibility, SyncToRenderWorld, VisibilityClass, SmudAnchor)]
 #[reflect(Component, Default, Debug, Clone)]
 #[component(on_add = add_visibility_class::<SmudShape>)]
 /// Main component used for describing an sdf shape
+///
+/// Shapes are frustum culled using an [`Aabb`](bevy::camera::primitives::Aabb) computed from
+/// `bounds` and `extra_bounds`. Add [`NoFrustumCulling`](bevy::camera::visibility::NoFrustumCulling)
+/// to opt out.
 pub struct SmudShape {
     /// The color used by the fill shader
     pub color: Color,
@@ -33,11 +118,32 @@ pub struct SmudShape {
     /// The shader needs to have the signature `fn fill(input: smud::FillInput) -> vec4<f32>`.
     pub fill: Handle<Shader>, // todo: wrap in newtypes?
     /// The bounds for rendering this shape, should be larger than the actual SDF shape to avoid clipping
+    ///
+    /// Add [`AutoBounds`](crate::AutoBounds) to have them computed from the shape instead.
     pub bounds: Rectangle,
     /// Parameters to pass to shapes, for things such as width of a box
     // perhaps it would be a better idea to have this as a separate component?
     // keeping it here for now...
     pub params: Vec4,
+    /// Parameters to pass to the fill, for things such as outline width or gradient angle
+    ///
+    /// Fills read them as `input.fill_params`, while `input.params` are the shape [`params`](Self::params).
+    pub fill_params: Vec4,
+    /// Image that can be sampled by the fill shader
+    ///
+    /// Fills read it using `smud::fill_texture`, shapes without an image sample white:
+    ///
+    /// ```wgsl
+    /// #import smud::fill_texture
+    ///
+    /// fn fill(input: smud::FillInput) -> vec4<f32> {
+    ///     let color = fill_texture::sample(fill_texture::uv(input)) * input.color;
+    ///     // ...
+    /// }
+    /// ```
+    ///
+    /// Sampler settings (filtering, address mode) are taken from [`Image::sampler`].
+    pub image: Option<Handle<Image>>,
     /// Blend mode for the shape
     pub blend_mode: BlendMode,
     /// Extra padding to add to the bounds when rendering the shape
@@ -51,6 +157,8 @@ impl Default for SmudShape {
             sdf: default(),
             bounds: default(),
             params: default(),
+            fill_params: default(),
+            image: None,
             fill: DEFAULT_FILL_HANDLE,
             blend_mode: BlendMode::default(),
             extra_bounds: 5.0,
@@ -71,9 +179,84 @@ impl SmudShape {
         self
     }
 
+    /// Set the fill parameters for this shape (builder pattern)
+    pub fn with_fill_params(mut self, fill_params: Vec4) -> Self {
+        self.fill_params = fill_params;
+        self
+    }
+
+    /// Set the image sampled by the fill shader (builder pattern)
+    pub fn with_image(mut self, image: Handle<Image>) -> Self {
+        self.image = Some(image);
+        self
+    }
+
     /// Set the blend mode for this shape (builder pattern)
     pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
         self.blend_mode = blend_mode;
         self
     }
 }
+
+/// Where the origin of a [`SmudShape`] is, relative to its bounds
+///
+/// Works like bevy's sprite `Anchor`: `(-0.5, -0.5)` is the bottom left corner of the bounds,
+/// and `(0.5, 0.5)` the top right. The shape is offset so the anchor point ends up at the
+/// translation of the entity, which is also the point it's scaled and rotated around.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Default, Deref, DerefMut)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct SmudAnchor(pub Vec2);
+
+impl SmudAnchor {
+    #[allow(missing_docs)]
+    pub const CENTER: Self = Self(Vec2::ZERO);
+    #[allow(missing_docs)]
+    pub const BOTTOM_LEFT: Self = Self(Vec2::new(-0.5, -0.5));
+    #[allow(missing_docs)]
+    pub const BOTTOM_CENTER: Self = Self(Vec2::new(0.0, -0.5));
+    #[allow(missing_docs)]
+    pub const BOTTOM_RIGHT: Self = Self(Vec2::new(0.5, -0.5));
+    #[allow(missing_docs)]
+    pub const CENTER_LEFT: Self = Self(Vec2::new(-0.5, 0.0));
+    #[allow(missing_docs)]
+    pub const CENTER_RIGHT: Self = Self(Vec2::new(0.5, 0.0));
+    #[allow(missing_docs)]
+    pub const TOP_LEFT: Self = Self(Vec2::new(-0.5, 0.5));
+    #[allow(missing_docs)]
+    pub const TOP_CENTER: Self = Self(Vec2::new(0.0, 0.5));
+    #[allow(missing_docs)]
+    pub const TOP_RIGHT: Self = Self(Vec2::new(0.5, 0.5));
+
+    /// Offset from the translation of the entity to the center of the shape, in local space
+    pub fn offset(&self, bounds: Rectangle) -> Vec2 {
+        -self.0 * bounds.size()
+    }
+}
+
+/// Extra per-shape parameters, for shapes that need more than the four floats in
+/// [`SmudShape::params`]
+///
+/// The parameters are uploaded to a storage buffer (or a fixed size uniform buffer on WebGL2)
+/// and can be read in sdf and fill shaders using the `extra_params` field of the input:
+///
+/// ```wgsl
+/// #import smud::shape_params
+///
+/// fn sdf(input: smud::SdfInput) -> f32 {
+///     let n = shape_params::count(input.extra_params);
+///     let first = shape_params::get(input.extra_params, 0u);
+///     // ...
+/// }
+/// ```
+#[derive(Component, Reflect, Debug, Clone, Default, Deref, DerefMut)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct SmudParams(pub Vec<Vec4>);
+
+/// Makes a [`SmudShape`] face the camera in 3d views
+///
+/// The shape is laid out in the plane of the camera, around the translation of the entity.
+/// Rotation and scale in the xy plane still apply, so the shape can be spun or stretched on
+/// screen. Has no effect in 2d views.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct SmudBillboard;
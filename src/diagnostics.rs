@@ -0,0 +1,218 @@
+//! Diagnostics for shape rendering
+
+use std::{
+    any::TypeId,
+    sync::{Arc, Mutex},
+};
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic},
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    render::{Render, RenderApp, RenderSystems, render_resource::SpecializedRenderPipeline},
+};
+
+use crate::{
+    ExtractedShapes, GeneratedShaders, ShapeBatches, ShapeMeta, ShapeVertex, SmudPipeline,
+    SmudShape, prepare_shapes,
+    shape_pipelines::{ShaderKey, ShapePipelines},
+};
+
+/// Adds diagnostics for shape rendering
+///
+/// Values measured in the render world lag one frame behind, since rendering is pipelined.
+/// Use together with [`LogDiagnosticsPlugin`](bevy::diagnostic::LogDiagnosticsPlugin) to print
+/// them:
+///
+/// ```no_run
+/// # use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*};
+/// # use bevy_smud::{SmudDiagnosticsPlugin, prelude::*};
+/// App::new().add_plugins((
+///     DefaultPlugins,
+///     SmudPlugin,
+///     SmudDiagnosticsPlugin,
+///     LogDiagnosticsPlugin::default(),
+/// ));
+/// ```
+#[derive(Default)]
+pub struct SmudDiagnosticsPlugin;
+
+impl SmudDiagnosticsPlugin {
+    /// Number of world shapes extracted to the render world this frame
+    ///
+    /// Shapes that didn't change since the last frame aren't extracted again.
+    pub const SHAPES_EXTRACTED: DiagnosticPath = DiagnosticPath::const_new("smud/shapes_extracted");
+    /// Number of world shapes not visible in any view, either hidden or frustum culled
+    pub const SHAPES_CULLED: DiagnosticPath = DiagnosticPath::const_new("smud/shapes_culled");
+    /// Average number of batches drawn per view by the world pipeline
+    pub const BATCHES_PER_VIEW: DiagnosticPath = DiagnosticPath::const_new("smud/batches_per_view");
+    /// Size of the world instance buffer in bytes
+    pub const INSTANCE_BUFFER_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("smud/instance_buffer_bytes");
+    /// Number of unique sdf and fill combinations with a generated shader, shared by world and
+    /// UI shapes
+    pub const SHADER_COMBINATIONS: DiagnosticPath =
+        DiagnosticPath::const_new("smud/shader_combinations");
+    /// Number of pipelines specialized for the generated shaders, by the world, UI and material
+    /// pipelines
+    pub const SPECIALIZED_PIPELINES: DiagnosticPath =
+        DiagnosticPath::const_new("smud/specialized_pipelines");
+    /// Number of UI shapes extracted to the render world
+    #[cfg(feature = "bevy_ui")]
+    pub const UI_SHAPES_EXTRACTED: DiagnosticPath =
+        DiagnosticPath::const_new("smud/ui_shapes_extracted");
+    /// Number of UI shapes drawn per view
+    ///
+    /// UI shapes aren't batched, so this is one per shape.
+    #[cfg(feature = "bevy_ui")]
+    pub const UI_BATCHES_PER_VIEW: DiagnosticPath =
+        DiagnosticPath::const_new("smud/ui_batches_per_view");
+    /// Size of the UI instance buffer in bytes
+    #[cfg(feature = "bevy_ui")]
+    pub const UI_INSTANCE_BUFFER_BYTES: DiagnosticPath =
+        DiagnosticPath::const_new("smud/ui_instance_buffer_bytes");
+}
+
+impl Plugin for SmudDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        let stats = SharedRenderStats::default();
+
+        app.insert_resource(stats.clone())
+            .register_diagnostic(Diagnostic::new(Self::SHAPES_EXTRACTED))
+            .register_diagnostic(Diagnostic::new(Self::SHAPES_CULLED))
+            .register_diagnostic(Diagnostic::new(Self::BATCHES_PER_VIEW))
+            .register_diagnostic(Diagnostic::new(Self::INSTANCE_BUFFER_BYTES).with_suffix("B"))
+            .register_diagnostic(Diagnostic::new(Self::SHADER_COMBINATIONS))
+            .register_diagnostic(Diagnostic::new(Self::SPECIALIZED_PIPELINES))
+            .add_systems(Update, measure_shapes);
+
+        #[cfg(feature = "bevy_ui")]
+        app.register_diagnostic(Diagnostic::new(Self::UI_SHAPES_EXTRACTED))
+            .register_diagnostic(Diagnostic::new(Self::UI_BATCHES_PER_VIEW))
+            .register_diagnostic(Diagnostic::new(Self::UI_INSTANCE_BUFFER_BYTES).with_suffix("B"));
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app.insert_resource(stats).add_systems(
+            Render,
+            (
+                collect_render_stats.after(prepare_shapes),
+                collect_pipeline_stats::<SmudPipeline>,
+            )
+                .in_set(RenderSystems::PrepareBindGroups),
+        );
+
+        #[cfg(feature = "bevy_ui")]
+        render_app.add_systems(
+            Render,
+            (
+                crate::ui::collect_ui_render_stats.after(crate::ui::prepare_ui_shapes),
+                collect_pipeline_stats::<crate::ui::UiShapePipeline>,
+            )
+                .in_set(RenderSystems::PrepareBindGroups),
+        );
+    }
+}
+
+/// Statistics measured in the render world
+#[derive(Default)]
+pub(crate) struct RenderStats {
+    pub shapes_extracted: usize,
+    pub batches_per_view: f64,
+    pub instance_buffer_bytes: usize,
+    pub shader_combinations: usize,
+    /// Specialized pipelines of each pipeline type
+    pub specialized_pipelines: HashMap<TypeId, usize>,
+    #[cfg(feature = "bevy_ui")]
+    pub ui_shapes_extracted: usize,
+    #[cfg(feature = "bevy_ui")]
+    pub ui_batches_per_view: f64,
+    #[cfg(feature = "bevy_ui")]
+    pub ui_instance_buffer_bytes: usize,
+}
+
+/// Render stats, shared between the main and render world
+#[derive(Resource, Clone, Default)]
+pub(crate) struct SharedRenderStats(pub Arc<Mutex<RenderStats>>);
+
+fn collect_render_stats(
+    stats: Res<SharedRenderStats>,
+    extracted_shapes: Res<ExtractedShapes>,
+    batches: Res<ShapeBatches>,
+    shape_meta: Res<ShapeMeta>,
+    generated_shaders: Res<GeneratedShaders>,
+) {
+    let views = batches
+        .0
+        .keys()
+        .map(|(view, _)| *view)
+        .collect::<HashSet<_>>();
+
+    let mut stats = stats.0.lock().unwrap();
+    stats.shapes_extracted = extracted_shapes.extracted;
+    stats.batches_per_view = if views.is_empty() {
+        0.
+    } else {
+        batches.0.len() as f64 / views.len() as f64
+    };
+    stats.instance_buffer_bytes = shape_meta.vertices.len() * size_of::<ShapeVertex>();
+    stats.shader_combinations = generated_shaders.len();
+}
+
+/// Count the specialized pipelines of `P`
+///
+/// Material plugins add this for their pipeline, so it does nothing without
+/// [`SmudDiagnosticsPlugin`].
+pub(crate) fn collect_pipeline_stats<P: SpecializedRenderPipeline<Key: ShaderKey> + 'static>(
+    stats: Option<Res<SharedRenderStats>>,
+    pipelines: Res<ShapePipelines<P>>,
+) {
+    if let Some(stats) = stats {
+        let mut stats = stats.0.lock().unwrap();
+        stats
+            .specialized_pipelines
+            .insert(TypeId::of::<P>(), pipelines.len());
+    }
+}
+
+fn measure_shapes(
+    mut diagnostics: Diagnostics,
+    stats: Res<SharedRenderStats>,
+    shapes: Query<&ViewVisibility, With<SmudShape>>,
+) {
+    diagnostics.add_measurement(&SmudDiagnosticsPlugin::SHAPES_CULLED, || {
+        shapes.iter().filter(|visibility| !visibility.get()).count() as f64
+    });
+
+    let stats = stats.0.lock().unwrap();
+    diagnostics.add_measurement(&SmudDiagnosticsPlugin::SHAPES_EXTRACTED, || {
+        stats.shapes_extracted as f64
+    });
+    diagnostics.add_measurement(&SmudDiagnosticsPlugin::BATCHES_PER_VIEW, || {
+        stats.batches_per_view
+    });
+    diagnostics.add_measurement(&SmudDiagnosticsPlugin::INSTANCE_BUFFER_BYTES, || {
+        stats.instance_buffer_bytes as f64
+    });
+    diagnostics.add_measurement(&SmudDiagnosticsPlugin::SHADER_COMBINATIONS, || {
+        stats.shader_combinations as f64
+    });
+    diagnostics.add_measurement(&SmudDiagnosticsPlugin::SPECIALIZED_PIPELINES, || {
+        stats.specialized_pipelines.values().sum::<usize>() as f64
+    });
+
+    #[cfg(feature = "bevy_ui")]
+    {
+        diagnostics.add_measurement(&SmudDiagnosticsPlugin::UI_SHAPES_EXTRACTED, || {
+            stats.ui_shapes_extracted as f64
+        });
+        diagnostics.add_measurement(&SmudDiagnosticsPlugin::UI_BATCHES_PER_VIEW, || {
+            stats.ui_batches_per_view
+        });
+        diagnostics.add_measurement(&SmudDiagnosticsPlugin::UI_INSTANCE_BUFFER_BYTES, || {
+            stats.ui_instance_buffer_bytes as f64
+        });
+    }
+}
@@ -0,0 +1,96 @@
+//! Render world support for sampling images in fills, see [`SmudShape::image`](crate::SmudShape::image)
+
+use bevy::{
+    platform::collections::HashMap,
+    prelude::*,
+    render::{
+        Extract,
+        render_asset::RenderAssets,
+        render_resource::{
+            BindGroup, BindGroupEntries, BindGroupLayoutDescriptor, BindGroupLayoutEntries,
+            PipelineCache, SamplerBindingType, ShaderStages, TextureSampleType,
+            binding_types::{sampler, texture_2d},
+        },
+        renderer::RenderDevice,
+        texture::{FallbackImage, GpuImage},
+    },
+};
+
+/// Layout for the bind group containing the image of a shape
+///
+/// Shared between the world and ui pipelines, so the same bind groups can be used for both.
+pub(crate) fn fill_texture_layout() -> BindGroupLayoutDescriptor {
+    BindGroupLayoutDescriptor::new(
+        "smud_fill_texture_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::FRAGMENT,
+            (
+                texture_2d(TextureSampleType::Float { filterable: true }),
+                sampler(SamplerBindingType::Filtering),
+            ),
+        ),
+    )
+}
+
+/// Bind groups for the images used by shapes
+///
+/// `None` is used for shapes without an image, and binds the (white) fallback image.
+#[derive(Resource, Default)]
+pub(crate) struct FillTextureBindGroups {
+    values: HashMap<Option<AssetId<Image>>, BindGroup>,
+}
+
+impl FillTextureBindGroups {
+    pub fn get(&self, image: Option<AssetId<Image>>) -> Option<&BindGroup> {
+        self.values.get(&image)
+    }
+
+    /// Create the bind group for the image if it doesn't exist yet
+    ///
+    /// Returns `false` if the image isn't loaded yet.
+    pub fn prepare(
+        &mut self,
+        image: Option<AssetId<Image>>,
+        render_device: &RenderDevice,
+        pipeline_cache: &PipelineCache,
+        gpu_images: &RenderAssets<GpuImage>,
+        fallback_image: &FallbackImage,
+    ) -> bool {
+        if self.values.contains_key(&image) {
+            return true;
+        }
+
+        let gpu_image = match image {
+            Some(image) => match gpu_images.get(image) {
+                Some(gpu_image) => gpu_image,
+                None => return false,
+            },
+            None => &fallback_image.d2,
+        };
+
+        let bind_group = render_device.create_bind_group(
+            "smud_fill_texture_bind_group",
+            &pipeline_cache.get_bind_group_layout(&fill_texture_layout()),
+            &BindGroupEntries::sequential((&gpu_image.texture_view, &gpu_image.sampler)),
+        );
+        self.values.insert(image, bind_group);
+        true
+    }
+}
+
+/// Drop bind groups for images that were modified or removed
+pub(crate) fn extract_image_events(
+    mut bind_groups: ResMut<FillTextureBindGroups>,
+    mut image_events: Extract<MessageReader<AssetEvent<Image>>>,
+) {
+    for event in image_events.read() {
+        match event {
+            AssetEvent::Modified { id }
+            | AssetEvent::Removed { id }
+            | AssetEvent::Unused { id } => {
+                bind_groups.values.remove(&Some(*id));
+            }
+            AssetEvent::Added { .. } | AssetEvent::LoadedWithDependencies { .. } => {}
+        }
+    }
+}
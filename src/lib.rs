@@ -2,12 +2,18 @@
 #![doc = include_str!("../README.md")]
 #![allow(clippy::too_many_arguments)]
 
-use std::ops::Range;
+use std::{marker::PhantomData, ops::Range};
 
 use bevy::{
-    camera::CompositingSpace,
+    asset::UntypedAssetId,
+    camera::{
+        CompositingSpace,
+        primitives::Aabb,
+        visibility::{NoFrustumCulling, VisibilitySystems},
+    },
     core_pipeline::{
-        core_2d::{CORE_2D_DEPTH_FORMAT, Transparent2d},
+        core_2d::{AlphaMask2d, CORE_2D_DEPTH_FORMAT, Transparent2d},
+        core_3d::{CORE_3D_DEPTH_FORMAT, Transparent3d},
         tonemapping::{
             DebandDither, Tonemapping, TonemappingLuts, get_lut_bind_group_layout_entries,
             get_lut_bindings,
@@ -20,26 +26,27 @@ use bevy::{
             lifetimeless::{Read, SRes},
         },
     },
-    math::{FloatOrd, Vec3Swizzles},
+    math::FloatOrd,
     mesh::VertexBufferLayout,
-    platform::collections::HashMap,
+    platform::collections::{HashMap, HashSet},
     prelude::*,
     render::{
         Extract, MainWorld, Render, RenderApp, RenderSystems,
         camera::ExtractedCamera,
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
         globals::{GlobalsBuffer, GlobalsUniform},
         render_asset::RenderAssets,
         render_phase::{
-            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
-            RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+            AddRenderCommand, CachedRenderPipelinePhaseItem, DrawFunctions, PhaseItem,
+            PhaseItemExtraIndex, RenderCommand, RenderCommandResult, SetItemPipeline,
+            SortedPhaseItem, SortedRenderPhase, TrackedRenderPass, ViewSortedRenderPhases,
         },
         render_resource::{
             BindGroup, BindGroupEntries, BindGroupLayoutDescriptor, BindGroupLayoutEntries,
-            BlendComponent, BlendFactor, BlendOperation, BlendState, BufferUsages,
-            CachedRenderPipelineId, ColorTargetState, ColorWrites, CompareFunction, DepthBiasState,
-            DepthStencilState, Face, FragmentState, FrontFace, MultisampleState, PipelineCache,
-            PolygonMode, PrimitiveState, PrimitiveTopology, RawBufferVec, RenderPipelineDescriptor,
-            ShaderStages, SpecializedRenderPipeline, SpecializedRenderPipelines, StencilFaceState,
+            BufferUsages, CachedRenderPipelineId, ColorTargetState, ColorWrites, CompareFunction,
+            DepthBiasState, DepthStencilState, Face, FragmentState, FrontFace, MultisampleState,
+            PipelineCache, PolygonMode, PrimitiveState, PrimitiveTopology, RawBufferVec,
+            RenderPipelineDescriptor, ShaderStages, SpecializedRenderPipeline, StencilFaceState,
             StencilState, TextureFormat, VertexAttribute, VertexFormat, VertexState,
             VertexStepMode, binding_types::uniform_buffer,
         },
@@ -57,26 +64,54 @@ use bevy::{
 use bytemuck::{Pod, Zeroable};
 use fixedbitset::FixedBitSet;
 use shader_loading::*;
+use uuid::Uuid;
 
+pub use auto_bounds::AutoBounds;
 pub use components::*;
+pub use diagnostics::SmudDiagnosticsPlugin;
+pub use material::{ShapeMaterial, SmudMaterial, SmudMaterialPlugin};
+pub use render_target::SmudRenderTarget;
+pub use sdf_expr::SdfExpr;
+pub use shader_errors::{SmudFallbackShape, SmudShaderError};
 pub use shader_loading::{DEFAULT_FILL_HANDLE, SIMPLE_FILL_HANDLE};
 
 #[cfg(feature = "bevy_ui")]
 use ui::UiShapePlugin;
 
-use crate::util::generate_shader_id;
+use crate::{
+    auto_bounds::update_auto_bounds,
+    fill_texture::{FillTextureBindGroups, extract_image_events, fill_texture_layout},
+    opaque::{OpaqueShape, OpaqueShapes, queue_opaque_shapes},
+    shader_errors::{ShaderErrors, extract_removed_shader_errors, extract_shader_errors},
+    shape_params::{PARAMS_UNIFORM_SHADER_DEF, ParamsBuffer, params_layout, params_use_uniform},
+    shape_pipelines::{ShaderKey, ShapePipelines, remove_shape_pipelines},
+    util::{generate_shader_id, shape_matrix2, shape_plane_matrix2},
+    view_3d::queue_shapes_3d,
+};
 
+mod auto_bounds;
 #[cfg(feature = "bevy_primitives")]
 pub mod bevy_primitives;
 mod components;
+mod diagnostics;
+mod fill_texture;
+mod material;
+mod opaque;
 #[cfg(feature = "bevy_picking")]
 mod picking_backend;
+pub mod rasterizer;
+mod render_target;
 pub mod sdf;
 mod sdf_assets;
+mod sdf_expr;
+mod shader_errors;
 mod shader_loading;
+mod shape_params;
+mod shape_pipelines;
 #[cfg(feature = "bevy_ui")]
 mod ui;
 mod util;
+mod view_3d;
 
 /// Re-export of the essentials needed for rendering shapes
 ///
@@ -86,8 +121,9 @@ mod util;
 /// ```
 pub mod prelude {
     pub use crate::{
-        BlendMode, DEFAULT_FILL_HANDLE, SIMPLE_FILL_HANDLE, SmudPlugin, SmudShape,
-        sdf_assets::SdfAssets,
+        AutoBounds, BlendMode, DEFAULT_FILL_HANDLE, SIMPLE_FILL_HANDLE, SdfExpr, ShapeMaterial,
+        SmudAnchor, SmudBillboard, SmudMaterial, SmudMaterialPlugin, SmudParams, SmudPlugin,
+        SmudShape, sdf_assets::SdfAssets,
     };
 
     #[cfg(feature = "bevy_primitives")]
@@ -108,6 +144,99 @@ pub mod prelude {
 /// Main plugin for enabling rendering of Sdf shapes
 pub struct SmudPlugin;
 
+/// Opt-in sorting of shapes that reduces the number of draw calls
+///
+/// Shapes are drawn in z order, and consecutive shapes can only be drawn in one batch if they
+/// share a shader, image and material. When shapes with different shaders are mixed at the same
+/// z, this often means one draw call per shape.
+///
+/// With this resource inserted, shapes are grouped by shader, image and material before being
+/// sorted by z. Shapes in the same interval of [`z_tolerance`](Self::z_tolerance) along z are
+/// sorted as if they were at the start of it, so they stay grouped and can be drawn in one batch.
+/// Shapes in different intervals are still drawn back to front.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_smud::{SmudBatchSorting, prelude::*};
+/// App::new()
+///     .add_plugins((DefaultPlugins, SmudPlugin))
+///     .insert_resource(SmudBatchSorting::default());
+/// ```
+#[derive(Resource, ExtractResource, Debug, Clone, Copy, Default)]
+pub struct SmudBatchSorting {
+    /// Size of the intervals along z that shapes are grouped in
+    ///
+    /// Shapes within the same interval are drawn in the order of their groups rather than their
+    /// z, so a shape may end up in front of a shape in the same interval with a higher z. They're
+    /// also sorted at the start of the interval relative to other items, like sprites. Zero only
+    /// groups shapes with exactly the same z.
+    pub z_tolerance: f32,
+}
+
+impl SmudBatchSorting {
+    /// Start of the interval along z that a shape is grouped in
+    fn z_interval(&self, z: f32) -> FloatOrd {
+        if self.z_tolerance > 0. {
+            FloatOrd((z / self.z_tolerance).floor() * self.z_tolerance)
+        } else {
+            FloatOrd(z)
+        }
+    }
+
+    /// Key the transparent phase is sorted by
+    ///
+    /// Sorting the phase is stable, so shapes with the same key are drawn in the order they were
+    /// queued in by [`queue_order`].
+    fn sort_key(sorting: Option<&Self>, z: f32) -> FloatOrd {
+        match sorting {
+            Some(sorting) => sorting.z_interval(z),
+            None => FloatOrd(z),
+        }
+    }
+}
+
+/// Shapes with the same key can be drawn in one batch
+type BatchKey = (
+    AssetId<Shader>,
+    Option<AssetId<Image>>,
+    Option<UntypedAssetId>,
+);
+
+/// Order to queue shapes in, as indices into [`ExtractedShapes::shapes`]
+///
+/// With [`SmudBatchSorting`], shapes in the same z interval that can be batched are queued next
+/// to each other. Groups and the shapes in them are ordered by z and then entity, rather than by
+/// their index in the store, which changes when other shapes are removed.
+fn queue_order(
+    extracted_shapes: &ExtractedShapes,
+    sorting: Option<&SmudBatchSorting>,
+    order: &mut Vec<usize>,
+) {
+    order.clear();
+    let Some(sorting) = sorting else {
+        order.extend(0..extracted_shapes.shapes.len());
+        return;
+    };
+
+    let mut groups: HashMap<(FloatOrd, BatchKey), Vec<usize>> = HashMap::default();
+    for (index, shape) in extracted_shapes.shapes.iter().enumerate() {
+        let z_interval = sorting.z_interval(shape.transform.translation().z);
+        let key = (shape.shader.id(), shape.image, shape.material);
+        groups.entry((z_interval, key)).or_default().push(index);
+    }
+
+    let order_key = |&index: &usize| {
+        let shape = &extracted_shapes.shapes[index];
+        (FloatOrd(shape.transform.translation().z), shape.main_entity)
+    };
+    let mut groups = groups.into_values().collect::<Vec<_>>();
+    for group in &mut groups {
+        group.sort_unstable_by_key(order_key);
+    }
+    groups.sort_unstable_by_key(|group| order_key(&group[0]));
+    order.extend(groups.into_iter().flatten());
+}
+
 /// System set for shape rendering.
 #[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
 pub enum ShapeRenderSystems {
@@ -126,23 +255,48 @@ impl Plugin for SmudPlugin {
         #[cfg(feature = "bevy_ui")]
         app.add_plugins(UiShapePlugin);
 
-        app.register_type::<SmudShape>();
-        // TODO: calculate bounds?
+        app.register_type::<SmudShape>()
+            .register_type::<SmudParams>()
+            .register_type::<SmudBillboard>()
+            .register_type::<AutoBounds>()
+            .add_message::<SmudShaderError>()
+            .init_resource::<SmudFallbackShape>()
+            .init_resource::<SmudShaderCache>()
+            .add_plugins(ExtractResourcePlugin::<SmudBatchSorting>::default())
+            .add_systems(
+                PostUpdate,
+                (
+                    update_auto_bounds,
+                    calculate_shape_bounds.in_set(VisibilitySystems::CalculateBounds),
+                )
+                    .chain(),
+            );
 
         // TODO: picking
         if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
             render_app
-                .init_resource::<SpecializedRenderPipelines<SmudPipeline>>()
-                .init_resource::<ShapeMeta>()
+                .init_resource::<ShapePipelines<SmudPipeline>>()
                 .init_resource::<ExtractedShapes>()
+                .init_resource::<FillTextureBindGroups>()
+                .init_resource::<OpaqueShapes>()
+                .init_resource::<ShaderErrors>()
                 .add_render_command::<Transparent2d, DrawSmudShape>()
+                .add_render_command::<AlphaMask2d, DrawSmudShape>()
+                .add_render_command::<Transparent3d, DrawSmudShape>()
                 .add_systems(
                     ExtractSchedule,
                     (
+                        extract_shader_events.before(evict_generated_shaders),
+                        evict_generated_shaders.before(generate_shaders),
                         generate_shaders,
                         extract_shapes
                             .in_set(ShapeRenderSystems::ExtractShapes)
                             .after(generate_shaders),
+                        extract_image_events,
+                        extract_shader_errors.after(generate_shaders),
+                        remove_shape_pipelines::<SmudPipeline>
+                            .after(ShapeRenderSystems::ExtractShapes),
+                        extract_removed_shader_errors,
                     ),
                 );
         }
@@ -152,12 +306,17 @@ impl Plugin for SmudPlugin {
         let render_app = app.sub_app_mut(RenderApp);
         render_app
             .init_resource::<ShapeBatches>()
+            .init_resource::<ShapeMeta>()
             .init_resource::<SmudPipeline>()
             .init_resource::<GeneratedShaders>()
             .add_systems(
                 Render,
                 (
                     queue_shapes.in_set(RenderSystems::Queue),
+                    queue_opaque_shapes
+                        .in_set(RenderSystems::Queue)
+                        .after(queue_shapes),
+                    queue_shapes_3d.in_set(RenderSystems::Queue),
                     prepare_shape_view_bind_groups.in_set(RenderSystems::PrepareBindGroups),
                     prepare_shapes.in_set(RenderSystems::PrepareBindGroups),
                 ),
@@ -165,7 +324,56 @@ impl Plugin for SmudPlugin {
     }
 }
 
-type DrawSmudShape = (SetItemPipeline, SetShapeViewBindGroup<0>, DrawShapeBatch);
+/// Adds or updates the [`Aabb`] of shapes, so they can be frustum culled
+///
+/// The aabb is in local space, so it doesn't need updating when the transform changes.
+/// Billboards can face any direction, so they get a cube containing every orientation.
+#[allow(clippy::type_complexity)]
+fn calculate_shape_bounds(
+    mut commands: Commands,
+    shapes: Query<
+        (Entity, &SmudShape, &SmudAnchor, Has<SmudBillboard>),
+        (
+            Or<(
+                Changed<SmudShape>,
+                Changed<SmudAnchor>,
+                Changed<SmudBillboard>,
+                Without<Aabb>,
+            )>,
+            Without<NoFrustumCulling>,
+        ),
+    >,
+    mut removed_billboards: RemovedComponents<SmudBillboard>,
+) {
+    for entity in removed_billboards.read() {
+        if let Ok(mut entity) = commands.get_entity(entity) {
+            entity.remove::<Aabb>();
+        }
+    }
+
+    for (entity, shape, anchor, billboard) in &shapes {
+        let half_size = shape.bounds.half_size + Vec2::splat(shape.extra_bounds);
+        let center = anchor.offset(shape.bounds);
+        let aabb = if billboard {
+            let radius = (center.abs() + half_size).length();
+            Aabb::from_min_max(Vec3::splat(-radius), Vec3::splat(radius))
+        } else {
+            Aabb::from_min_max(
+                (center - half_size).extend(0.),
+                (center + half_size).extend(0.),
+            )
+        };
+        commands.entity(entity).try_insert(aabb);
+    }
+}
+
+type DrawSmudShape = (
+    SetItemPipeline,
+    SetShapeViewBindGroup<0>,
+    SetShapeParamsBindGroup<1>,
+    SetShapeTextureBindGroup<2>,
+    DrawShapeBatch,
+);
 
 struct SetShapeViewBindGroup<const I: usize>;
 impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetShapeViewBindGroup<I> {
@@ -185,6 +393,51 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetShapeViewBindGroup<I>
     }
 }
 
+struct SetShapeParamsBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetShapeParamsBindGroup<I> {
+    type Param = SRes<ShapeMeta>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        _entity: Option<()>,
+        shape_meta: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(params_bind_group) = shape_meta.into_inner().params_bind_group.as_ref() else {
+            return RenderCommandResult::Failure("params_bind_group not available");
+        };
+        pass.set_bind_group(I, params_bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+struct SetShapeTextureBindGroup<const I: usize>;
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetShapeTextureBindGroup<I> {
+    type Param = (SRes<ShapeBatches>, SRes<FillTextureBindGroups>);
+    type ViewQuery = Read<ExtractedView>;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        view: ROQueryItem<'w, '_, Self::ViewQuery>,
+        _entity: Option<()>,
+        (batches, texture_bind_groups): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(batch) = batches.get(&(view.retained_view_entity, item.main_entity())) else {
+            return RenderCommandResult::Skip;
+        };
+        let Some(texture_bind_group) = texture_bind_groups.into_inner().get(batch.image) else {
+            return RenderCommandResult::Failure("texture bind group not available");
+        };
+        pass.set_bind_group(I, texture_bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
 struct DrawShapeBatch;
 impl<P: PhaseItem> RenderCommand<P> for DrawShapeBatch {
     type Param = (SRes<ShapeMeta>, SRes<ShapeBatches>);
@@ -211,10 +464,14 @@ impl<P: PhaseItem> RenderCommand<P> for DrawShapeBatch {
 #[derive(Resource)]
 struct SmudPipeline {
     view_layout: BindGroupLayoutDescriptor,
+    params_layout: BindGroupLayoutDescriptor,
+    /// Extra params are in a uniform buffer instead of a storage buffer (WebGL2)
+    params_uniform: bool,
+    texture_layout: BindGroupLayoutDescriptor,
 }
 
-impl Default for SmudPipeline {
-    fn default() -> Self {
+impl FromWorld for SmudPipeline {
+    fn from_world(world: &mut World) -> Self {
         let tonemapping_lut_entries = get_lut_bind_group_layout_entries();
         let entries = BindGroupLayoutEntries::with_indices(
             ShaderStages::VERTEX_FRAGMENT,
@@ -237,7 +494,15 @@ impl Default for SmudPipeline {
 
         let view_layout = BindGroupLayoutDescriptor::new("shape_view_layout", &entries);
 
-        Self { view_layout }
+        let params_uniform = params_use_uniform(world.resource::<RenderDevice>());
+        let params_layout = params_layout("shape_params_layout", params_uniform);
+
+        Self {
+            view_layout,
+            params_layout,
+            params_uniform,
+            texture_layout: fill_texture_layout(),
+        }
     }
 }
 
@@ -248,6 +513,12 @@ struct SmudPipelineKey {
     shader: Handle<Shader>,
 }
 
+impl ShaderKey for SmudPipelineKey {
+    fn shader(&self) -> AssetId<Shader> {
+        self.shader.id()
+    }
+}
+
 impl SpecializedRenderPipeline for SmudPipeline {
     type Key = SmudPipelineKey;
 
@@ -311,12 +582,30 @@ impl SpecializedRenderPipeline for SmudPipeline {
         if key.mesh.contains(PipelineKey::OKLAB_COMPOSITING) {
             shader_defs.push("OKLAB_OUTPUT".into());
         }
+        if self.params_uniform {
+            shader_defs.push(PARAMS_UNIFORM_SHADER_DEF.into());
+        }
+        if let Some(blend_def) = key.mesh.blend_mode().shader_def() {
+            shader_defs.push(blend_def.into());
+        }
+        if key.mesh.contains(PipelineKey::MAY_DISCARD) {
+            shader_defs.push("MAY_DISCARD".into());
+        }
+
+        let mut vertex_shader_defs = Vec::new();
+        let view_3d = key.mesh.contains(PipelineKey::VIEW_3D);
+        if view_3d {
+            vertex_shader_defs.push("VIEW_3D".into());
+        }
+        if key.mesh.contains(PipelineKey::BILLBOARD) {
+            vertex_shader_defs.push("BILLBOARD".into());
+        }
 
         debug!("shader_defs: {shader_defs:?}");
 
         // Customize how to store the meshes' vertex attributes in the vertex buffer
         // Our meshes only have position, color and params
-        let vertex_attributes = vec![
+        let mut vertex_attributes = vec![
             // (GOTCHA! attributes are sorted alphabetically, and offsets need to reflect this)
             // Color
             VertexAttribute {
@@ -343,27 +632,55 @@ impl SpecializedRenderPipeline for SmudPipeline {
                 offset: (4 + 4 + 4) * 4,
                 shader_location: 0,
             },
-            // Rotation
+            // Transform (2x2 matrix, column major)
             VertexAttribute {
-                format: VertexFormat::Float32x2,
+                format: VertexFormat::Float32x4,
                 offset: (4 + 4 + 4 + 3) * 4,
                 shader_location: 3,
             },
-            // Scale
+            // Extra params (offset and count)
             VertexAttribute {
-                format: VertexFormat::Float32,
-                offset: (4 + 4 + 4 + 3 + 2) * 4,
-                shader_location: 4,
+                format: VertexFormat::Uint32x2,
+                offset: (4 + 4 + 4 + 3 + 4) * 4,
+                shader_location: 6,
+            },
+            // Fill params
+            VertexAttribute {
+                format: VertexFormat::Float32x4,
+                offset: (4 + 4 + 4 + 3 + 4 + 2) * 4,
+                shader_location: 7,
             },
         ];
-        // This is the sum of the size of the attributes above
-        let vertex_array_stride = (4 + 4 + 4 + 3 + 2 + 1) * 4;
+        if view_3d {
+            vertex_attributes.extend([
+                // Transform z (z of the x and y axes)
+                VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: (4 + 4 + 4 + 3 + 4 + 2 + 4) * 4,
+                    shader_location: 8,
+                },
+                // Anchor offset
+                VertexAttribute {
+                    format: VertexFormat::Float32x2,
+                    offset: (4 + 4 + 4 + 3 + 4 + 2 + 4 + 2) * 4,
+                    shader_location: 9,
+                },
+                // Plane transform (2x2 matrix, column major)
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: (4 + 4 + 4 + 3 + 4 + 2 + 4 + 2 + 2) * 4,
+                    shader_location: 10,
+                },
+            ]);
+        }
+        // This is the sum of the size of all attributes, including the 3d ones
+        let vertex_array_stride = (4 + 4 + 4 + 3 + 4 + 2 + 4 + 2 + 2 + 4) * 4;
 
         RenderPipelineDescriptor {
             vertex: VertexState {
                 shader: VERTEX_SHADER_HANDLE,
                 entry_point: Some("vertex".into()),
-                shader_defs: Vec::new(),
+                shader_defs: vertex_shader_defs,
                 buffers: vec![VertexBufferLayout {
                     array_stride: vertex_array_stride,
                     step_mode: VertexStepMode::Instance,
@@ -376,32 +693,22 @@ impl SpecializedRenderPipeline for SmudPipeline {
                 shader_defs,
                 targets: vec![Some(ColorTargetState {
                     format: key.mesh.target_format(),
-                    blend: Some(if key.mesh.contains(PipelineKey::BLEND_ADDITIVE) {
-                        BlendState {
-                            color: BlendComponent {
-                                src_factor: BlendFactor::SrcAlpha,
-                                dst_factor: BlendFactor::One,
-                                operation: BlendOperation::Add,
-                            },
-                            alpha: BlendComponent {
-                                src_factor: BlendFactor::One,
-                                dst_factor: BlendFactor::One,
-                                operation: BlendOperation::Add,
-                            },
-                        }
-                    } else {
-                        BlendState::ALPHA_BLENDING
-                    }),
+                    blend: key.mesh.blend_mode().blend_state(),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
             layout: vec![
                 // Bind group 0 is the view uniform
                 self.view_layout.clone(),
+                // Bind group 1 is the extra params of all shapes
+                self.params_layout.clone(),
+                // Bind group 2 is the image of the shapes in the batch
+                self.texture_layout.clone(),
             ],
             primitive: PrimitiveState {
                 front_face: FrontFace::Ccw,
-                cull_mode: Some(Face::Back),
+                // Shapes in 3d can be seen from behind
+                cull_mode: (!view_3d).then_some(Face::Back),
                 unclipped_depth: false, // What is this?
                 polygon_mode: PolygonMode::Fill,
                 conservative: false, // What is this?
@@ -409,8 +716,13 @@ impl SpecializedRenderPipeline for SmudPipeline {
                 strip_index_format: None, // TODO: what does this do?
             },
             depth_stencil: Some(DepthStencilState {
-                format: CORE_2D_DEPTH_FORMAT,
-                depth_write_enabled: Some(false),
+                format: if view_3d {
+                    CORE_3D_DEPTH_FORMAT
+                } else {
+                    CORE_2D_DEPTH_FORMAT
+                },
+                // Opaque shapes hide what's drawn behind them
+                depth_write_enabled: Some(key.mesh.blend_mode() == BlendMode::Opaque),
                 depth_compare: Some(CompareFunction::GreaterEqual),
                 stencil: StencilState {
                     front: StencilFaceState::IGNORE,
@@ -436,14 +748,165 @@ impl SpecializedRenderPipeline for SmudPipeline {
     }
 }
 
+/// Settings and size of the cache of shaders generated for each sdf and fill combination
+#[derive(Resource, Debug, Clone)]
+pub struct SmudShaderCache {
+    /// Number of frames a combination can go unused by all shapes before its generated shader
+    /// is removed, along with the pipelines specialized for it
+    pub evict_after_frames: u32,
+    len: usize,
+}
+
+impl Default for SmudShaderCache {
+    fn default() -> Self {
+        Self {
+            evict_after_frames: 300,
+            len: 0,
+        }
+    }
+}
+
+impl SmudShaderCache {
+    /// Number of generated shaders currently in the cache
+    pub fn shader_count(&self) -> usize {
+        self.len
+    }
+}
+
+struct GeneratedShader {
+    handle: Handle<Shader>,
+    /// Copies of the sdf and fill that are imported instead of the originals, see
+    /// [`GeneratedShaders::import_path`]
+    copies: Vec<Handle<Shader>>,
+    /// Frame the combination was last used by a shape
+    last_used: u32,
+}
+
 #[derive(Default, Resource)]
-pub(crate) struct GeneratedShaders(
-    pub(crate) HashMap<(AssetId<Shader>, AssetId<Shader>), Handle<Shader>>,
-);
+pub(crate) struct GeneratedShaders {
+    shaders: HashMap<(AssetId<Shader>, AssetId<Shader>), GeneratedShader>,
+    frame: u32,
+    /// Sdfs and fills that were modified or removed since the last frame
+    modified: HashSet<AssetId<Shader>>,
+    /// Generated shaders that were removed this frame, shapes using them need re-extracting
+    stale: HashSet<AssetId<Shader>>,
+    /// Generated shaders that were evicted or regenerated this frame, see [`ShapePipelines`]
+    removed: HashSet<AssetId<Shader>>,
+    /// Sdf and fill of [`SmudFallbackShape`], which is never evicted
+    fallback: Option<(AssetId<Shader>, AssetId<Shader>)>,
+}
 
 impl GeneratedShaders {
+    /// The shader generated for an sdf and fill combination, if it exists
+    pub(crate) fn get(
+        &self,
+        sdf: AssetId<Shader>,
+        fill: AssetId<Shader>,
+    ) -> Option<&Handle<Shader>> {
+        self.shaders
+            .get(&(sdf, fill))
+            .map(|generated| &generated.handle)
+    }
+
+    /// The sdf and fill a shader was generated from
+    pub(crate) fn source(
+        &self,
+        shader: AssetId<Shader>,
+    ) -> Option<(AssetId<Shader>, AssetId<Shader>)> {
+        self.shaders
+            .iter()
+            .find(|(_, generated)| generated.handle.id() == shader)
+            .map(|(&key, _)| key)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.shaders.len()
+    }
+
+    /// Whether a shader was removed this frame, because its sdf or fill was modified
+    fn is_stale(&self, shader: AssetId<Shader>) -> bool {
+        !self.stale.is_empty() && self.stale.contains(&shader)
+    }
+
+    /// Remove shaders generated from modified sdfs and fills, so they're generated again
+    fn remove_modified(&mut self, shaders: &mut Assets<Shader>) {
+        self.stale.clear();
+        if self.modified.is_empty() {
+            return;
+        }
+        let Self {
+            shaders: generated_shaders,
+            modified,
+            stale,
+            removed,
+            ..
+        } = self;
+        generated_shaders.retain(|(sdf, fill), generated| {
+            let keep = !modified.contains(sdf) && !modified.contains(fill);
+            if !keep {
+                debug!("Sdf or fill modified, regenerating shader");
+                stale.insert(generated.handle.id());
+                // The regenerated shader gets a new id, so its pipelines are specialized again
+                removed.insert(generated.handle.id());
+                generated.remove(shaders);
+            }
+            keep
+        });
+        modified.clear();
+    }
+
+    /// Remove shaders that haven't been used for more than
+    /// [`SmudShaderCache::evict_after_frames`] frames, and update the size of the cache
+    ///
+    /// [`ShapePipelines`] drop the pipelines specialized for the removed shaders.
+    fn evict_unused(&mut self, cache: &mut SmudShaderCache, shaders: &mut Assets<Shader>) {
+        self.frame = self.frame.wrapping_add(1);
+        let Self {
+            shaders: generated_shaders,
+            frame,
+            removed,
+            fallback,
+            ..
+        } = self;
+        generated_shaders.retain(|key, generated| {
+            let used = Some(*key) == *fallback
+                || frame.wrapping_sub(generated.last_used) <= cache.evict_after_frames;
+            if !used {
+                debug!("Evicting unused generated shader");
+                removed.insert(generated.handle.id());
+                generated.remove(shaders);
+            }
+            used
+        });
+        cache.len = generated_shaders.len();
+    }
+
+    /// Import path for an sdf or fill
+    ///
+    /// Shaders without a `#define_import_path` are imported through a copy with a generated
+    /// import path, so the user's asset is left untouched and can be hot reloaded.
+    fn import_path(
+        shader: &Shader,
+        shaders: &mut Assets<Shader>,
+        copies: &mut Vec<Handle<Shader>>,
+    ) -> String {
+        match &shader.import_path {
+            ShaderImport::Custom(p) => p.to_owned(),
+            _ => {
+                let id = generate_shader_id();
+                let path = format!("smud::generated::{id}");
+                let mut copy = shader.clone();
+                copy.import_path = ShaderImport::Custom(path.clone());
+                copies.push(add_uncounted(shaders, copy));
+                path
+            }
+        }
+    }
+
     /// Generate and add shader
-    fn try_generate(
+    ///
+    /// Also marks the combination as used this frame.
+    pub(crate) fn try_generate(
         &mut self,
         sdf: &Handle<Shader>,
         fill: &Handle<Shader>,
@@ -451,42 +914,24 @@ impl GeneratedShaders {
     ) -> Option<Handle<Shader>> {
         let shader_key = (sdf.id(), fill.id());
 
-        if let Some(handle) = self.0.get(&shader_key) {
-            return Some(handle.clone());
+        if let Some(generated) = self.shaders.get_mut(&shader_key) {
+            generated.last_used = self.frame;
+            return Some(generated.handle.clone());
         }
 
-        let sdf_import_path = match shaders.get_mut(sdf) {
-            Some(mut shader) => match &shader.import_path {
-                ShaderImport::Custom(p) => p.to_owned(),
-                _ => {
-                    let id = generate_shader_id();
-                    let path = format!("smud::generated::{id}");
-                    shader.import_path = ShaderImport::Custom(path.clone());
-                    path
-                }
-            },
-            None => {
-                debug!("Waiting for sdf to load");
-                return None;
-            }
+        let Some(sdf_shader) = shaders.get(sdf).cloned() else {
+            debug!("Waiting for sdf to load");
+            return None;
         };
-
-        let fill_import_path = match shaders.get_mut(fill) {
-            Some(mut shader) => match &shader.import_path {
-                ShaderImport::Custom(p) => p.to_owned(),
-                _ => {
-                    let id = generate_shader_id();
-                    let path = format!("smud::generated::{id}");
-                    shader.import_path = ShaderImport::Custom(path.clone());
-                    path
-                }
-            },
-            None => {
-                debug!("Waiting for fill to load");
-                return None;
-            }
+        let Some(fill_shader) = shaders.get(fill).cloned() else {
+            debug!("Waiting for fill to load");
+            return None;
         };
 
+        let mut copies = Vec::new();
+        let sdf_import_path = Self::import_path(&sdf_shader, shaders, &mut copies);
+        let fill_import_path = Self::import_path(&fill_shader, shaders, &mut copies);
+
         debug!("Generating shader");
         let generated_shader = Shader::from_wgsl(
             format!(
@@ -512,17 +957,26 @@ struct FragmentInput {{
     @location(1) pos: vec2<f32>,
     @location(2) params: vec4<f32>,
     @location(3) bounds: vec2<f32>,
+    @location(4) @interpolate(flat) transform: vec4<f32>,
+    @location(5) @interpolate(flat) extra_params: vec2<u32>,
+    @location(6) fill_params: vec4<f32>,
 }};
 
 @fragment
 fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {{
-    let sdf_input = smud::SdfInput(in.pos, in.params, in.bounds);
-    let d = sdf::sdf(sdf_input);
+    let sdf_input = smud::SdfInput(in.pos, in.params, in.bounds, in.extra_params);
+    let sdf_distance = sdf::sdf(sdf_input);
+    let d = smud::correct_distance(sdf_distance, in.pos, in.transform);
+    let gradient = smud::distance_gradient(sdf_distance, in.pos);
     let fill_input = smud::FillInput(
         in.pos,
         in.params,
+        in.fill_params,
         d,
         in.color,
+        in.extra_params,
+        in.bounds,
+        gradient,
     );
     var color = fill::fill(fill_input);
 
@@ -538,6 +992,21 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {{
     color = vec4(linear_rgb_to_oklab(color.rgb), color.a);
 #endif
 
+#ifdef BLEND_MULTIPLY
+    color = vec4(mix(vec3<f32>(1.), color.rgb, color.a), color.a);
+#endif
+#ifdef BLEND_SCREEN
+    color = vec4(color.rgb * color.a, color.a);
+#endif
+#ifdef MAY_DISCARD
+    if color.a < 0.5 {{
+        discard;
+    }}
+#endif
+#ifdef BLEND_OPAQUE
+    color.a = 1.;
+#endif
+
     return color;
 }}
 "#
@@ -545,17 +1014,76 @@ fn fragment(in: FragmentInput) -> @location(0) vec4<f32> {{
             format!("smud::generated::{shader_key:?}"),
         );
 
-        let generated_shader_handle = shaders.add(generated_shader);
+        let generated_shader_handle = add_uncounted(shaders, generated_shader);
 
-        self.0.insert(shader_key, generated_shader_handle.clone());
+        self.shaders.insert(
+            shader_key,
+            GeneratedShader {
+                handle: generated_shader_handle.clone(),
+                copies,
+                last_used: self.frame,
+            },
+        );
 
         Some(generated_shader_handle)
     }
 }
 
+impl GeneratedShader {
+    fn remove(&self, shaders: &mut Assets<Shader>) {
+        shaders.remove(self.handle.id());
+        for copy in &self.copies {
+            shaders.remove(copy.id());
+        }
+    }
+}
+
+/// Add a shader that isn't reference counted
+///
+/// Pipeline keys hold on to generated shader handles, so [`GeneratedShaders`] removes them
+/// explicitly when they're no longer used instead.
+fn add_uncounted(shaders: &mut Assets<Shader>, shader: Shader) -> Handle<Shader> {
+    let handle = Handle::<Shader>::Uuid(Uuid::new_v4(), PhantomData);
+    shaders
+        .insert(&handle, shader)
+        .expect("uuid asset ids are always valid");
+    handle
+}
+
+fn extract_shader_events(
+    mut generated_shaders: ResMut<GeneratedShaders>,
+    mut shader_events: Extract<MessageReader<AssetEvent<Shader>>>,
+) {
+    for event in shader_events.read() {
+        match event {
+            AssetEvent::Modified { id } | AssetEvent::Removed { id } => {
+                generated_shaders.modified.insert(*id);
+            }
+            AssetEvent::Added { .. }
+            | AssetEvent::LoadedWithDependencies { .. }
+            | AssetEvent::Unused { .. } => {}
+        }
+    }
+}
+
+fn evict_generated_shaders(
+    mut main_world: ResMut<MainWorld>,
+    mut generated_shaders: ResMut<GeneratedShaders>,
+) {
+    main_world.resource_scope(|world, mut shaders: Mut<Assets<Shader>>| {
+        generated_shaders.removed.clear();
+        generated_shaders.remove_modified(&mut shaders);
+
+        let Some(mut cache) = world.get_resource_mut::<SmudShaderCache>() else {
+            return;
+        };
+        generated_shaders.evict_unused(&mut cache, &mut shaders);
+    });
+}
+
 // TODO: do some of this work in the main world instead, so we don't need to take a mutable
 // reference to MainWorld.
-fn generate_shaders(
+pub(crate) fn generate_shaders(
     mut main_world: ResMut<MainWorld>,
     mut generated_shaders: ResMut<GeneratedShaders>,
 ) {
@@ -568,20 +1096,81 @@ fn generate_shaders(
 
 #[derive(Component, Clone, Debug)]
 struct ExtractedShape {
-    main_entity: Entity,
+    main_entity: MainEntity,
     render_entity: Entity,
-    color: Color,
-    params: Vec4,
-    bounds: Vec2,
-    extra_bounds: f32,
     shader: Handle<Shader>,
+    image: Option<AssetId<Image>>,
+    /// Set by [`SmudMaterialPlugin`] for shapes with a [`ShapeMaterial`]
+    material: Option<UntypedAssetId>,
     transform: GlobalTransform,
     blend_mode: BlendMode,
+    /// Faces the camera in 3d views, see [`SmudBillboard`]
+    billboard: bool,
+    extra_params: Vec<Vec4>,
+    /// Instance data, computed once when the shape is extracted
+    vertex: ShapeVertex,
+    /// [`ExtractedShapes::tick`] when `vertex` last changed
+    changed_tick: u32,
 }
 
+/// Shapes in the render world, retained between frames
+///
+/// Only shapes that changed since the last frame are re-extracted.
 #[derive(Resource, Default, Debug)]
 struct ExtractedShapes {
     shapes: Vec<ExtractedShape>,
+    indices: HashMap<MainEntity, usize>,
+    /// Extra params were added, changed or removed, so the params buffer needs rebuilding
+    params_dirty: bool,
+    /// Incremented every frame, shapes that changed this frame have it as `changed_tick`
+    tick: u32,
+    /// Number of shapes extracted this frame, unchanged shapes are kept from earlier frames
+    extracted: usize,
+}
+
+impl ExtractedShapes {
+    fn get(&self, main_entity: MainEntity) -> Option<&ExtractedShape> {
+        let index = *self.indices.get(&main_entity)?;
+        self.shapes.get(index)
+    }
+
+    fn get_mut(&mut self, main_entity: MainEntity) -> Option<&mut ExtractedShape> {
+        let index = *self.indices.get(&main_entity)?;
+        self.shapes.get_mut(index)
+    }
+
+    fn insert(&mut self, mut shape: ExtractedShape) {
+        shape.changed_tick = self.tick;
+        self.extracted += 1;
+        match self.indices.get(&shape.main_entity) {
+            Some(&index) => {
+                let old = &self.shapes[index];
+                if old.extra_params == shape.extra_params {
+                    // Still valid, the params buffer is only rebuilt when dirty
+                    shape.vertex.extra_params = old.vertex.extra_params;
+                } else {
+                    self.params_dirty = true;
+                }
+                self.shapes[index] = shape;
+            }
+            None => {
+                self.params_dirty |= !shape.extra_params.is_empty();
+                self.indices.insert(shape.main_entity, self.shapes.len());
+                self.shapes.push(shape);
+            }
+        }
+    }
+
+    fn remove(&mut self, main_entity: MainEntity) {
+        let Some(index) = self.indices.remove(&main_entity) else {
+            return;
+        };
+        let removed = self.shapes.swap_remove(index);
+        self.params_dirty |= !removed.extra_params.is_empty();
+        if let Some(moved) = self.shapes.get(index) {
+            self.indices.insert(moved.main_entity, index);
+        }
+    }
 }
 
 #[allow(clippy::type_complexity)]
@@ -592,39 +1181,89 @@ fn extract_shapes(
         Query<(
             Entity,
             RenderEntity,
-            &ViewVisibility,
-            &SmudShape,
-            &GlobalTransform,
+            Ref<ViewVisibility>,
+            Ref<SmudShape>,
+            Ref<SmudAnchor>,
+            Ref<GlobalTransform>,
+            Option<Ref<SmudParams>>,
+            Option<Ref<SmudBillboard>>,
         )>,
     >,
+    mut removed_shapes: Extract<RemovedComponents<SmudShape>>,
+    mut removed_params: Extract<RemovedComponents<SmudParams>>,
+    mut removed_billboards: Extract<RemovedComponents<SmudBillboard>>,
 ) {
-    extracted_shapes.shapes.clear();
+    // Shapes that lost their params or billboard are re-extracted, since they're no longer in
+    // the store
+    for main_entity in removed_shapes
+        .read()
+        .chain(removed_params.read())
+        .chain(removed_billboards.read())
+    {
+        extracted_shapes.remove(main_entity.into());
+    }
+
+    extracted_shapes.tick = extracted_shapes.tick.wrapping_add(1);
+    extracted_shapes.extracted = 0;
+
+    for (
+        main_entity,
+        render_entity,
+        view_visibility,
+        shape,
+        anchor,
+        transform,
+        params,
+        billboard,
+    ) in shape_query.iter()
+    {
+        let main_entity = MainEntity::from(main_entity);
 
-    for (main_entity, render_entity, view_visibility, shape, transform) in shape_query.iter() {
         if !view_visibility.get() {
+            extracted_shapes.remove(main_entity);
+            continue;
+        }
+
+        let changed = shape.is_changed()
+            || anchor.is_changed()
+            || transform.is_changed()
+            || view_visibility.is_changed()
+            || params.as_ref().is_some_and(|params| params.is_changed())
+            || billboard
+                .as_ref()
+                .is_some_and(|billboard| billboard.is_added());
+        // Shapes whose shader was regenerated need to pick up the new one
+        if !changed
+            && extracted_shapes
+                .get(main_entity)
+                .is_some_and(|extracted| !generated_shaders.is_stale(extracted.shader.id()))
+        {
             continue;
         }
 
         let Some(shader) = generated_shaders
-            .0
-            .get(&(shape.sdf.id(), shape.fill.id()))
+            .get(shape.sdf.id(), shape.fill.id())
             .cloned()
         else {
+            // Not in the store, so we'll try again next frame
+            extracted_shapes.remove(main_entity);
             continue;
         };
 
         // TODO: bevy_sprite has some slice stuff here? what is it for?
 
-        extracted_shapes.shapes.push(ExtractedShape {
+        extracted_shapes.insert(ExtractedShape {
             main_entity,
             render_entity,
-            color: shape.color,
-            params: shape.params,
-            transform: *transform,
             shader,
-            bounds: shape.bounds.half_size,
-            extra_bounds: shape.extra_bounds,
+            image: shape.image.as_ref().map(Handle::id),
+            material: None,
+            transform: *transform,
             blend_mode: shape.blend_mode,
+            billboard: billboard.is_some(),
+            extra_params: params.map(|params| params.0.clone()).unwrap_or_default(),
+            vertex: ShapeVertex::new(&shape, &anchor, &transform),
+            changed_tick: 0,
         });
     }
 }
@@ -643,8 +1282,17 @@ bitflags::bitflags! {
         const DEBAND_DITHER                     = 1 << 1;
         const SRGB_COMPOSITING                  = 1 << 2;
         const OKLAB_COMPOSITING                 = 1 << 3;
-        const BLEND_ADDITIVE                    = 1 << 4;
-        const MAY_DISCARD                       = 1 << 5;
+        const MAY_DISCARD                       = 1 << 4;
+        const VIEW_3D                           = 1 << 8;
+        const BILLBOARD                         = 1 << 9;
+        const BLEND_RESERVED_BITS               = Self::BLEND_MASK_BITS << Self::BLEND_SHIFT_BITS;
+        const BLEND_ALPHA                       = 0 << Self::BLEND_SHIFT_BITS;
+        const BLEND_ADDITIVE                    = 1 << Self::BLEND_SHIFT_BITS;
+        const BLEND_MULTIPLY                    = 2 << Self::BLEND_SHIFT_BITS;
+        const BLEND_SCREEN                      = 3 << Self::BLEND_SHIFT_BITS;
+        const BLEND_SUBTRACT                    = 4 << Self::BLEND_SHIFT_BITS;
+        const BLEND_PREMULTIPLIED_ALPHA         = 5 << Self::BLEND_SHIFT_BITS;
+        const BLEND_OPAQUE                      = 6 << Self::BLEND_SHIFT_BITS;
         const COLOR_TARGET_FORMAT_RESERVED_BITS = Self::COLOR_TARGET_FORMAT_MASK_BITS << Self::COLOR_TARGET_FORMAT_SHIFT_BITS;
         const MSAA_RESERVED_BITS                = Self::MSAA_MASK_BITS << Self::MSAA_SHIFT_BITS;
         const PRIMITIVE_TOPOLOGY_RESERVED_BITS  = Self::PRIMITIVE_TOPOLOGY_MASK_BITS << Self::PRIMITIVE_TOPOLOGY_SHIFT_BITS;
@@ -662,6 +1310,8 @@ bitflags::bitflags! {
 }
 
 impl PipelineKey {
+    const BLEND_MASK_BITS: u32 = 0b111;
+    const BLEND_SHIFT_BITS: u32 = 5;
     const MSAA_MASK_BITS: u32 = 0b111;
     const MSAA_SHIFT_BITS: u32 = 32 - Self::MSAA_MASK_BITS.count_ones();
     const PRIMITIVE_TOPOLOGY_MASK_BITS: u32 = 0b111;
@@ -722,32 +1372,137 @@ impl PipelineKey {
 
     pub fn from_blend_mode(blend_mode: BlendMode) -> Self {
         match blend_mode {
-            BlendMode::Alpha => Self::NONE,
+            BlendMode::Alpha => Self::BLEND_ALPHA,
             BlendMode::Additive => Self::BLEND_ADDITIVE,
+            BlendMode::Multiply => Self::BLEND_MULTIPLY,
+            BlendMode::Screen => Self::BLEND_SCREEN,
+            BlendMode::Subtract => Self::BLEND_SUBTRACT,
+            BlendMode::PremultipliedAlpha => Self::BLEND_PREMULTIPLIED_ALPHA,
+            BlendMode::Opaque => Self::BLEND_OPAQUE,
+        }
+    }
+
+    /// Key bits that depend on the shape rather than the view
+    fn from_shape(shape: &ExtractedShape) -> Self {
+        let key = Self::from_blend_mode(shape.blend_mode);
+        if shape.blend_mode == BlendMode::Opaque {
+            key | Self::MAY_DISCARD
+        } else {
+            key
+        }
+    }
+
+    pub fn blend_mode(&self) -> BlendMode {
+        match self.intersection(Self::BLEND_RESERVED_BITS) {
+            Self::BLEND_ADDITIVE => BlendMode::Additive,
+            Self::BLEND_MULTIPLY => BlendMode::Multiply,
+            Self::BLEND_SCREEN => BlendMode::Screen,
+            Self::BLEND_SUBTRACT => BlendMode::Subtract,
+            Self::BLEND_PREMULTIPLIED_ALPHA => BlendMode::PremultipliedAlpha,
+            Self::BLEND_OPAQUE => BlendMode::Opaque,
+            _ => BlendMode::Alpha,
         }
     }
 }
 
-#[allow(clippy::type_complexity)]
+/// Views that shapes are queued for
+type ShapeViews<'w, 's> = Query<
+    'w,
+    's,
+    (
+        &'static RenderVisibleEntities,
+        &'static ExtractedCamera,
+        &'static ExtractedView,
+        &'static Msaa,
+        Option<&'static Tonemapping>,
+        Option<&'static DebandDither>,
+    ),
+>;
+
+/// The parts of the pipeline key that are shared by all shapes in a view
+fn view_pipeline_key(
+    camera: &ExtractedCamera,
+    view: &ExtractedView,
+    msaa: &Msaa,
+    tonemapping: Option<&Tonemapping>,
+    dither: Option<&DebandDither>,
+) -> PipelineKey {
+    let mesh_key = PipelineKey::from_msaa_samples(msaa.samples())
+        | PipelineKey::from_primitive_topology(PrimitiveTopology::TriangleStrip);
+
+    let mut view_key = PipelineKey::from_target_format(view.target_format) | mesh_key;
+
+    if camera.compositing_space == Some(CompositingSpace::Srgb) {
+        view_key |= PipelineKey::SRGB_COMPOSITING;
+    }
+    if camera.compositing_space == Some(CompositingSpace::Oklab) {
+        view_key |= PipelineKey::OKLAB_COMPOSITING;
+    }
+
+    if !camera.hdr {
+        if let Some(tonemapping) = tonemapping {
+            view_key |= PipelineKey::TONEMAP_IN_SHADER;
+            view_key |= match tonemapping {
+                Tonemapping::None => PipelineKey::TONEMAP_METHOD_NONE,
+                Tonemapping::Reinhard => PipelineKey::TONEMAP_METHOD_REINHARD,
+                Tonemapping::ReinhardLuminance => PipelineKey::TONEMAP_METHOD_REINHARD_LUMINANCE,
+                Tonemapping::AcesFitted => PipelineKey::TONEMAP_METHOD_ACES_FITTED,
+                Tonemapping::AgX => PipelineKey::TONEMAP_METHOD_AGX,
+                Tonemapping::SomewhatBoringDisplayTransform => {
+                    PipelineKey::TONEMAP_METHOD_SOMEWHAT_BORING_DISPLAY_TRANSFORM
+                }
+                Tonemapping::TonyMcMapface => PipelineKey::TONEMAP_METHOD_TONY_MC_MAPFACE,
+                Tonemapping::BlenderFilmic => PipelineKey::TONEMAP_METHOD_BLENDER_FILMIC,
+                Tonemapping::KhronosPbrNeutral => PipelineKey::TONEMAP_METHOD_KHRONOS_PBR_NEUTRAL,
+            };
+        }
+        if let Some(DebandDither::Enabled) = dither {
+            view_key |= PipelineKey::DEBAND_DITHER;
+        }
+    }
+
+    view_key
+}
+
+/// Collect the indices of the shapes visible in a view
+fn collect_visible_shapes(
+    visible_entities: &RenderVisibleEntities,
+    view_entities: &mut FixedBitSet,
+) {
+    view_entities.clear();
+    if let Some(visible_entities) = visible_entities.get::<SmudShape>() {
+        view_entities.extend(
+            visible_entities
+                .iter_visible()
+                .map(|(_, e)| e.index_u32() as usize),
+        );
+    }
+}
+
 fn queue_shapes(
     mut view_entities: Local<FixedBitSet>,
+    mut order: Local<Vec<usize>>,
     draw_functions: Res<DrawFunctions<Transparent2d>>,
+    alpha_mask_draw_functions: Res<DrawFunctions<AlphaMask2d>>,
+    mut opaque_shapes: ResMut<OpaqueShapes>,
     smud_pipeline: Res<SmudPipeline>,
-    mut pipelines: ResMut<SpecializedRenderPipelines<SmudPipeline>>,
+    mut pipelines: ResMut<ShapePipelines<SmudPipeline>>,
     pipeline_cache: ResMut<PipelineCache>,
     extracted_shapes: ResMut<ExtractedShapes>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    mut shader_errors: ResMut<ShaderErrors>,
+    sorting: Option<Res<SmudBatchSorting>>,
     mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
-    mut views: Query<(
-        &RenderVisibleEntities,
-        &ExtractedCamera,
-        &ExtractedView,
-        &Msaa,
-        Option<&Tonemapping>,
-        Option<&DebandDither>,
-    )>,
-    // ?
+    mut views: ShapeViews,
 ) {
     let draw_smud_shape_function = draw_functions.read().get_id::<DrawSmudShape>().unwrap();
+    let draw_opaque_shape_function = alpha_mask_draw_functions
+        .read()
+        .get_id::<DrawSmudShape>()
+        .unwrap();
+    let sorting = sorting.as_deref();
+
+    queue_order(&extracted_shapes, sorting, &mut order);
 
     // Iterate over each view (a camera is a view)
     for (visible_entities, camera, view, msaa, tonemapping, dither) in &mut views {
@@ -756,84 +1511,79 @@ fn queue_shapes(
             continue;
         };
 
-        let mesh_key = PipelineKey::from_msaa_samples(msaa.samples())
-            | PipelineKey::from_primitive_topology(PrimitiveTopology::TriangleStrip);
+        let view_key = view_pipeline_key(camera, view, msaa, tonemapping, dither);
 
-        let mut view_key = PipelineKey::from_target_format(view.target_format) | mesh_key;
-
-        if camera.compositing_space == Some(CompositingSpace::Srgb) {
-            view_key |= PipelineKey::SRGB_COMPOSITING;
-        }
-        if camera.compositing_space == Some(CompositingSpace::Oklab) {
-            view_key |= PipelineKey::OKLAB_COMPOSITING;
-        }
-
-        if !camera.hdr {
-            if let Some(tonemapping) = tonemapping {
-                view_key |= PipelineKey::TONEMAP_IN_SHADER;
-                view_key |= match tonemapping {
-                    Tonemapping::None => PipelineKey::TONEMAP_METHOD_NONE,
-                    Tonemapping::Reinhard => PipelineKey::TONEMAP_METHOD_REINHARD,
-                    Tonemapping::ReinhardLuminance => {
-                        PipelineKey::TONEMAP_METHOD_REINHARD_LUMINANCE
-                    }
-                    Tonemapping::AcesFitted => PipelineKey::TONEMAP_METHOD_ACES_FITTED,
-                    Tonemapping::AgX => PipelineKey::TONEMAP_METHOD_AGX,
-                    Tonemapping::SomewhatBoringDisplayTransform => {
-                        PipelineKey::TONEMAP_METHOD_SOMEWHAT_BORING_DISPLAY_TRANSFORM
-                    }
-                    Tonemapping::TonyMcMapface => PipelineKey::TONEMAP_METHOD_TONY_MC_MAPFACE,
-                    Tonemapping::BlenderFilmic => PipelineKey::TONEMAP_METHOD_BLENDER_FILMIC,
-                    Tonemapping::KhronosPbrNeutral => {
-                        PipelineKey::TONEMAP_METHOD_KHRONOS_PBR_NEUTRAL
-                    }
-                };
-            }
-            if let Some(DebandDither::Enabled) = dither {
-                view_key |= PipelineKey::DEBAND_DITHER;
-            }
-        }
-
-        view_entities.clear();
-        if let Some(visible_entities) = visible_entities.get::<SmudShape>() {
-            view_entities.extend(
-                visible_entities
-                    .iter_visible()
-                    .map(|(_, e)| e.index_u32() as usize),
-            );
-        }
+        collect_visible_shapes(visible_entities, &mut view_entities);
 
         transparent_phase
             .items
             .reserve(extracted_shapes.shapes.len());
 
-        for (index, extracted_shape) in extracted_shapes.shapes.iter().enumerate() {
-            if !view_entities.contains(extracted_shape.main_entity.index_u32() as usize) {
+        for &index in order.iter() {
+            let extracted_shape = &extracted_shapes.shapes[index];
+            // Shapes with materials are queued by their material plugin
+            if extracted_shape.material.is_some() {
+                continue;
+            }
+            if !view_entities.contains(extracted_shape.main_entity.id().index_u32() as usize) {
                 continue;
             }
-            let shape_key = view_key | PipelineKey::from_blend_mode(extracted_shape.blend_mode);
+            if let Some(image) = extracted_shape.image
+                && gpu_images.get(image).is_none()
+            {
+                debug!("Image not loaded yet, skipping");
+                continue;
+            }
+            let mesh_key = view_key | PipelineKey::from_shape(extracted_shape);
             let specialize_key = SmudPipelineKey {
-                mesh: shape_key,
+                mesh: mesh_key,
                 shader: extracted_shape.shader.clone(),
             };
-            let pipeline = pipelines.specialize(&pipeline_cache, &smud_pipeline, specialize_key);
+            let mut pipeline =
+                pipelines.specialize(&pipeline_cache, &smud_pipeline, specialize_key);
+
+            if shader_errors.failed(
+                &pipeline_cache,
+                pipeline,
+                extracted_shape.main_entity,
+                &extracted_shape.shader,
+            ) {
+                let Some(fallback) = shader_errors.fallback.clone() else {
+                    continue;
+                };
+                let fallback_key = SmudPipelineKey {
+                    mesh: mesh_key,
+                    shader: fallback,
+                };
+                pipeline = pipelines.specialize(&pipeline_cache, &smud_pipeline, fallback_key);
+            }
 
             if pipeline == CachedRenderPipelineId::INVALID {
                 debug!("Shape not ready yet, skipping");
                 continue; // skip shapes that are not ready yet
             }
 
+            if extracted_shape.blend_mode == BlendMode::Opaque {
+                opaque_shapes.push(
+                    view.retained_view_entity,
+                    OpaqueShape {
+                        extracted_index: index,
+                        pipeline,
+                        draw_function: draw_opaque_shape_function,
+                    },
+                );
+                continue;
+            }
+
             // These items will be sorted by depth with other phase items
-            let sort_key = FloatOrd(extracted_shape.transform.translation().z);
+            let sort_key =
+                SmudBatchSorting::sort_key(sorting, extracted_shape.transform.translation().z);
 
             // Add the item to the render phase
             transparent_phase.add_transient(Transparent2d {
                 draw_function: draw_smud_shape_function,
                 pipeline,
-                entity: (
-                    extracted_shape.render_entity,
-                    extracted_shape.main_entity.into(),
-                ),
+                entity: (extracted_shape.render_entity, extracted_shape.main_entity),
                 sort_key,
                 // batch_range and dynamic_offset will be calculated in prepare_shapes
                 batch_range: 0..0,
@@ -889,100 +1639,183 @@ fn prepare_shape_view_bind_groups(
 fn prepare_shapes(
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
+    smud_pipeline: Res<SmudPipeline>,
+    pipeline_cache: Res<PipelineCache>,
     mut shape_meta: ResMut<ShapeMeta>,
-    extracted_shapes: Res<ExtractedShapes>,
+    mut extracted_shapes: ResMut<ExtractedShapes>,
     mut phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
+    mut phases_3d: ResMut<ViewSortedRenderPhases<Transparent3d>>,
+    opaque_shapes: Res<OpaqueShapes>,
     mut batches: ResMut<ShapeBatches>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    fallback_image: Res<FallbackImage>,
+    mut texture_bind_groups: ResMut<FillTextureBindGroups>,
 ) {
     batches.clear();
 
-    // Clear the vertex buffer
-    shape_meta.vertices.clear();
+    if extracted_shapes.params_dirty || shape_meta.params_bind_group.is_none() {
+        shape_meta.params.clear();
+        let tick = extracted_shapes.tick;
+        for extracted_shape in &mut extracted_shapes.shapes {
+            extracted_shape.vertex.extra_params =
+                shape_meta.params.push(&extracted_shape.extra_params);
+            extracted_shape.changed_tick = tick;
+        }
+        shape_meta
+            .params
+            .write_buffer(&render_device, &render_queue);
+        shape_meta.params_bind_group = shape_meta.params.binding().map(|binding| {
+            render_device.create_bind_group(
+                "shape_params_bind_group",
+                &pipeline_cache.get_bind_group_layout(&smud_pipeline.params_layout),
+                &BindGroupEntries::single(binding),
+            )
+        });
+        extracted_shapes.params_dirty = false;
+    }
 
-    // Vertex buffer index
-    let mut index = 0;
+    let extracted_shapes = &*extracted_shapes;
+    let mut prepare_texture = |image| {
+        texture_bind_groups.prepare(
+            image,
+            &render_device,
+            &pipeline_cache,
+            &gpu_images,
+            &fallback_image,
+        );
+    };
 
     for (retained_view, transparent_phase) in phases.iter_mut() {
-        let mut current_batch = None;
-        let mut batch_item_index = 0;
-        // let mut batch_image_size = Vec2::ZERO;
-        // let mut batch_image_handle = AssetId::invalid();
-        let mut batch_shader_id = AssetId::invalid();
-
-        // Iterate through the phase items and detect when successive shapes that can be batched.
-        // Spawn an entity with a `ShapeBatch` component for each possible batch.
-        // Compatible items share the same entity.
-        for item_index in 0..transparent_phase.items.len() {
-            let item = &transparent_phase.items[item_index];
-
-            let Some(extracted_shape) = extracted_shapes
-                .shapes
-                .get(item.extracted_index)
-                .filter(|extracted_shape| extracted_shape.render_entity == item.entity())
-            else {
-                // If there is a phase item that is not a shape, then we must start a new
-                // batch to draw the other phase item(s) and to respect draw order. This can be
-                // done by invalidating the batch_shader_handle
-                batch_shader_id = AssetId::invalid();
-                continue;
-            };
-
-            let shader_id = extracted_shape.shader.id();
-
-            let batch_shader_changed = batch_shader_id != shader_id;
-
-            let lrgba: LinearRgba = extracted_shape.color.into();
-            let color = lrgba.to_f32_array();
-            let params = extracted_shape.params.to_array();
+        batch_sorted_phase(
+            *retained_view,
+            extracted_shapes.tick,
+            transparent_phase,
+            |item| {
+                extracted_shapes
+                    .shapes
+                    .get(item.extracted_index)
+                    .filter(|extracted_shape| extracted_shape.render_entity == item.entity())
+            },
+            &mut shape_meta,
+            &mut batches,
+            &mut prepare_texture,
+        );
+    }
 
-            let position = extracted_shape.transform.translation();
-            let position = position.into();
+    for (retained_view, transparent_phase) in phases_3d.iter_mut() {
+        batch_sorted_phase(
+            *retained_view,
+            extracted_shapes.tick,
+            transparent_phase,
+            |item| {
+                extracted_shapes
+                    .get(item.main_entity())
+                    .filter(|extracted_shape| extracted_shape.render_entity == item.entity())
+            },
+            &mut shape_meta,
+            &mut batches,
+            &mut prepare_texture,
+        );
+    }
 
-            let rotation_and_scale = extracted_shape
-                .transform
-                .affine()
-                .transform_vector3(Vec3::X)
-                .xy();
+    // Opaque shapes were already grouped into batches by queue_opaque_shapes
+    for (retained_view, view_batches) in &opaque_shapes.batches {
+        for indices in view_batches {
+            let first = &extracted_shapes.shapes[indices[0]];
+            let start = shape_meta.vertex_count();
+            for &extracted_index in indices {
+                shape_meta.push_vertex(
+                    &extracted_shapes.shapes[extracted_index],
+                    extracted_shapes.tick,
+                );
+            }
 
-            let scale = rotation_and_scale.length();
-            let rotation = (rotation_and_scale / scale).into();
+            prepare_texture(first.image);
 
-            let bounds = extracted_shape.bounds;
-            let extra_bounds = extracted_shape.extra_bounds;
+            batches.insert(
+                (*retained_view, first.main_entity),
+                ShapeBatch {
+                    shader: first.shader.id(),
+                    image: first.image,
+                    material: first.material,
+                    range: start..shape_meta.vertex_count(),
+                },
+            );
+        }
+    }
 
-            let vertex = ShapeVertex {
-                position,
-                color,
-                params,
-                rotation,
-                scale,
-                bounds: [bounds.x, bounds.y, extra_bounds, extra_bounds],
-            };
-            shape_meta.vertices.push(vertex);
+    shape_meta.write_dirty_ranges(&render_device, &render_queue);
+}
 
-            if batch_shader_changed {
-                batch_item_index = item_index;
+/// Detect successive shapes in a sorted phase that can be batched
+///
+/// Adds a [`ShapeBatch`] for each batch, and pushes the vertices of the shapes. `get_shape`
+/// returns the extracted shape of a phase item, or `None` if the item isn't a shape.
+fn batch_sorted_phase<'a, I: SortedPhaseItem + CachedRenderPipelinePhaseItem>(
+    retained_view: RetainedViewEntity,
+    tick: u32,
+    phase: &mut SortedRenderPhase<I>,
+    get_shape: impl Fn(&I) -> Option<&'a ExtractedShape>,
+    shape_meta: &mut ShapeMeta,
+    batches: &mut ShapeBatches,
+    mut prepare_texture: impl FnMut(Option<AssetId<Image>>),
+) {
+    let mut current_batch = None;
+    let mut batch_item_index = 0;
+    let mut batch_pipeline = CachedRenderPipelineId::INVALID;
+    let mut batch_shader_id = AssetId::invalid();
+    let mut batch_image_id = None;
+    let mut batch_material_id = None;
+
+    // Iterate through the phase items and detect when successive shapes that can be batched.
+    // Add a `ShapeBatch` for each possible batch, keyed by the first item in it.
+    for item_index in 0..phase.items.len() {
+        let item = &phase.items[item_index];
+
+        let Some(extracted_shape) = get_shape(item) else {
+            // If there is a phase item that is not a shape, then we must start a new
+            // batch to draw the other phase item(s) and to respect draw order. This can be
+            // done by invalidating the batch_shader_handle
+            batch_shader_id = AssetId::invalid();
+            continue;
+        };
 
-                current_batch = Some(batches.entry((*retained_view, item.main_entity())).insert(
-                    ShapeBatch {
-                        shader: shader_id,
-                        range: index..index,
-                    },
-                ));
-            }
+        let pipeline = item.cached_pipeline();
+        let shader_id = extracted_shape.shader.id();
+        let image_id = extracted_shape.image;
+        let material_id = extracted_shape.material;
+
+        // Shapes with the same shader can still differ in blend mode, and thus pipeline
+        let batch_changed = batch_pipeline != pipeline
+            || batch_shader_id != shader_id
+            || batch_image_id != image_id
+            || batch_material_id != material_id;
+
+        let index = shape_meta.push_vertex(extracted_shape, tick);
+
+        if batch_changed {
+            batch_item_index = item_index;
+            batch_pipeline = pipeline;
+            batch_shader_id = shader_id;
+            batch_image_id = image_id;
+            batch_material_id = material_id;
+
+            prepare_texture(image_id);
+
+            current_batch = Some(batches.entry((retained_view, item.main_entity())).insert(
+                ShapeBatch {
+                    shader: shader_id,
+                    image: image_id,
+                    material: material_id,
+                    range: index..index,
+                },
+            ));
+        }
 
-            transparent_phase.items[batch_item_index]
-                .batch_range_mut()
-                .end += 1;
+        phase.items[batch_item_index].batch_range_mut().end += 1;
 
-            current_batch.as_mut().unwrap().get_mut().range.end += 1;
-            index += 1;
-        }
+        current_batch.as_mut().unwrap().get_mut().range.end += 1;
     }
-
-    shape_meta
-        .vertices
-        .write_buffer(&render_device, &render_queue);
 }
 
 #[repr(C)]
@@ -992,19 +1825,137 @@ struct ShapeVertex {
     pub bounds: [f32; 4],
     pub params: [f32; 4], // for now all shapes have 4 f32 parameters
     pub position: [f32; 3],
-    pub rotation: [f32; 2],
-    pub scale: f32,
+    pub transform: [f32; 4],
+    /// Offset and count of the extra params in the params buffer
+    pub extra_params: [u32; 2],
+    pub fill_params: [f32; 4],
+    /// z of the x and y axes of the transform, only used in 3d
+    pub transform_z: [f32; 2],
+    /// Offset of the quad center from the origin before transforming, only used in 3d
+    pub anchor_offset: [f32; 2],
+    /// Transform within the plane of the shape, for correcting distances in 3d
+    pub plane_transform: [f32; 4],
+}
+
+impl ShapeVertex {
+    fn new(shape: &SmudShape, anchor: &SmudAnchor, transform: &GlobalTransform) -> Self {
+        let lrgba: LinearRgba = shape.color.into();
+        let bounds = shape.bounds.half_size;
+        let extra_bounds = shape.extra_bounds;
+        let matrix = shape_matrix2(transform);
+        let matrix3 = transform.affine().matrix3;
+        let anchor_offset = anchor.offset(shape.bounds);
+        // Move the center of the quad, so the anchor point ends up at the translation
+        let position = transform.translation() + (matrix * anchor_offset).extend(0.);
+        Self {
+            color: lrgba.to_f32_array(),
+            bounds: [bounds.x, bounds.y, extra_bounds, extra_bounds],
+            params: shape.params.to_array(),
+            position: position.into(),
+            // The full 2x2 linear part, so non-uniform scale and skew survive
+            transform: matrix.to_cols_array(),
+            // Filled in when the params buffer is built
+            extra_params: [0, 0],
+            fill_params: shape.fill_params.to_array(),
+            transform_z: [matrix3.x_axis.z, matrix3.y_axis.z],
+            anchor_offset: anchor_offset.into(),
+            plane_transform: shape_plane_matrix2(transform).to_cols_array(),
+        }
+    }
 }
 
 #[derive(Resource)]
 pub(crate) struct ShapeMeta {
+    /// Vertices in draw order, kept between frames so only the changed ones are written
     vertices: RawBufferVec<ShapeVertex>,
+    /// Shape of each vertex in `vertices`
+    vertex_entities: Vec<MainEntity>,
+    /// Number of vertices pushed this frame
+    vertex_count: usize,
+    /// Ranges of `vertices` that were written this frame and need uploading
+    dirty_ranges: Vec<Range<usize>>,
+    params: ParamsBuffer,
+    params_bind_group: Option<BindGroup>,
 }
 
-impl Default for ShapeMeta {
-    fn default() -> Self {
+impl FromWorld for ShapeMeta {
+    fn from_world(world: &mut World) -> Self {
+        let params_uniform = params_use_uniform(world.resource::<RenderDevice>());
         Self {
             vertices: RawBufferVec::new(BufferUsages::VERTEX),
+            vertex_entities: Vec::new(),
+            vertex_count: 0,
+            dirty_ranges: Vec::new(),
+            params: ParamsBuffer::new(params_uniform),
+            params_bind_group: None,
+        }
+    }
+}
+
+impl ShapeMeta {
+    /// Number of vertices pushed this frame
+    fn vertex_count(&self) -> u32 {
+        self.vertex_count as u32
+    }
+
+    /// Add the vertex of a shape after the ones pushed this frame, and return its index
+    ///
+    /// The vertex is only written if a different shape was at this index last frame, or if the
+    /// shape changed since then.
+    fn push_vertex(&mut self, shape: &ExtractedShape, tick: u32) -> u32 {
+        let index = self.vertex_count;
+        self.vertex_count += 1;
+
+        if index < self.vertices.len() {
+            if self.vertex_entities[index] == shape.main_entity && shape.changed_tick != tick {
+                return index as u32;
+            }
+            self.vertices.values_mut()[index] = shape.vertex;
+            self.vertex_entities[index] = shape.main_entity;
+        } else {
+            self.vertices.push(shape.vertex);
+            self.vertex_entities.push(shape.main_entity);
+        }
+
+        match self.dirty_ranges.last_mut() {
+            Some(range) if range.end == index => range.end += 1,
+            _ => self.dirty_ranges.push(index..index + 1),
+        }
+        index as u32
+    }
+
+    /// Upload the vertices written this frame, and start the next one
+    fn write_dirty_ranges(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        let len = self.vertex_count;
+        self.vertex_count = 0;
+        let dirty_ranges = std::mem::take(&mut self.dirty_ranges);
+
+        self.vertices.truncate(len);
+        self.vertex_entities.truncate(len);
+        if len == 0 {
+            return;
+        }
+
+        let old_size = self.vertices.buffer().map(|buffer| buffer.size());
+        self.vertices.reserve(len, render_device);
+        let Some(buffer) = self.vertices.buffer() else {
+            return;
+        };
+        let values = self.vertices.values();
+        let stride = size_of::<ShapeVertex>();
+        let write = |range: Range<usize>| {
+            render_queue.write_buffer(
+                buffer,
+                (range.start * stride) as u64,
+                bytemuck::cast_slice(&values[range]),
+            );
+        };
+
+        // A new buffer has no contents, so everything is dirty
+        if old_size != Some(buffer.size()) {
+            write(0..len);
+        } else {
+            dirty_ranges.into_iter().for_each(write);
         }
     }
 }
@@ -1020,5 +1971,113 @@ struct ShapeBatches(HashMap<(RetainedViewEntity, MainEntity), ShapeBatch>);
 #[derive(Component, Eq, PartialEq, Clone)]
 struct ShapeBatch {
     shader: AssetId<Shader>,
+    image: Option<AssetId<Image>>,
+    material: Option<UntypedAssetId>,
     range: Range<u32>,
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bevy::asset::uuid_handle;
+
+    const SHADER_A: Handle<Shader> = uuid_handle!("6b1e3c0e-4d0a-4f55-9d43-3d0f5b0b8a11");
+    const SHADER_B: Handle<Shader> = uuid_handle!("0f7c1a52-9e6d-4b8e-a7c2-5d9e0c3b4f22");
+
+    pub(crate) fn shape(z: f32, shader: Handle<Shader>) -> ExtractedShape {
+        ExtractedShape {
+            main_entity: Entity::PLACEHOLDER.into(),
+            render_entity: Entity::PLACEHOLDER,
+            shader,
+            image: None,
+            material: None,
+            transform: GlobalTransform::from_xyz(0., 0., z),
+            blend_mode: BlendMode::Alpha,
+            billboard: false,
+            extra_params: Vec::new(),
+            vertex: ShapeVertex::zeroed(),
+            changed_tick: 0,
+        }
+    }
+
+    fn entity(i: usize) -> MainEntity {
+        Entity::from_raw_u32(i as u32 + 1).unwrap().into()
+    }
+
+    /// Queue and sort the shapes like the transparent phase, and count the batches
+    fn batch_count(
+        extracted_shapes: &ExtractedShapes,
+        sorting: Option<&SmudBatchSorting>,
+    ) -> usize {
+        let mut order = Vec::new();
+        queue_order(extracted_shapes, sorting, &mut order);
+        order.sort_by_key(|&index| {
+            let z = extracted_shapes.shapes[index].transform.translation().z;
+            SmudBatchSorting::sort_key(sorting, z)
+        });
+        let shaders = order
+            .iter()
+            .map(|&index| extracted_shapes.shapes[index].shader.id())
+            .collect::<Vec<_>>();
+        1 + shaders.windows(2).filter(|pair| pair[0] != pair[1]).count()
+    }
+
+    #[test]
+    fn test_z_tolerance_groups_shapes() {
+        let mut extracted_shapes = ExtractedShapes::default();
+        for (i, z) in [0.0, 0.001, 0.002].repeat(2).into_iter().enumerate() {
+            let shader = if i % 2 == 0 { SHADER_A } else { SHADER_B };
+            extracted_shapes.insert(ExtractedShape {
+                main_entity: entity(i),
+                ..shape(z, shader)
+            });
+        }
+
+        // Sorted by z, the shaders alternate
+        assert_eq!(batch_count(&extracted_shapes, None), 4);
+        assert_eq!(
+            batch_count(&extracted_shapes, Some(&SmudBatchSorting::default())),
+            4
+        );
+        // Within one interval, shapes are grouped by shader
+        let sorting = SmudBatchSorting { z_tolerance: 0.01 };
+        assert_eq!(batch_count(&extracted_shapes, Some(&sorting)), 2);
+    }
+
+    #[test]
+    fn test_queue_order_is_kept_when_shapes_are_removed() {
+        let shader_c: Handle<Shader> = uuid_handle!("3a9d6e41-2c7b-4f08-b1e5-7c4a2d9f6e33");
+        let mut extracted_shapes = ExtractedShapes::default();
+        for (i, (z, shader)) in [(0.002, SHADER_A), (0.001, SHADER_B), (0.0, shader_c)]
+            .into_iter()
+            .enumerate()
+        {
+            extracted_shapes.insert(ExtractedShape {
+                main_entity: entity(i),
+                ..shape(z, shader)
+            });
+        }
+        let sorting = SmudBatchSorting { z_tolerance: 0.01 };
+        let queued_entities = |extracted_shapes: &ExtractedShapes| {
+            let mut order = Vec::new();
+            queue_order(extracted_shapes, Some(&sorting), &mut order);
+            order
+                .into_iter()
+                .map(|index| extracted_shapes.shapes[index].main_entity)
+                .collect::<Vec<_>>()
+        };
+
+        let before = queued_entities(&extracted_shapes);
+        // Moves the last shape into the place of the first one in the store, in front of the
+        // group it was queued after
+        let removed = extracted_shapes.shapes[0].main_entity;
+        extracted_shapes.remove(removed);
+        let after = queued_entities(&extracted_shapes);
+
+        let expected = before
+            .into_iter()
+            .filter(|&entity| entity != removed)
+            .collect::<Vec<_>>();
+        assert_eq!(after, expected);
+    }
+}
@@ -0,0 +1,517 @@
+//! Custom fill materials with typed uniforms and textures
+
+use std::{any::TypeId, hash::Hash, marker::PhantomData};
+
+use bevy::{
+    core_pipeline::core_2d::{AlphaMask2d, Transparent2d},
+    ecs::{
+        query::ROQueryItem,
+        system::{
+            SystemParamItem,
+            lifetimeless::{Read, SRes},
+        },
+    },
+    prelude::*,
+    render::{
+        Extract, MainWorld, Render, RenderApp, RenderSystems,
+        render_asset::{PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets},
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
+            RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
+        },
+        render_resource::{
+            AsBindGroup, AsBindGroupError, BindGroup, BindGroupLayoutDescriptor,
+            CachedRenderPipelineId, PipelineCache, RenderPipelineDescriptor,
+            SpecializedRenderPipeline,
+        },
+        renderer::RenderDevice,
+        sync_world::MainEntity,
+        texture::GpuImage,
+        view::ExtractedView,
+    },
+    shader::ShaderRef,
+};
+use fixedbitset::FixedBitSet;
+
+use crate::{
+    BlendMode, DEFAULT_FILL_HANDLE, DrawShapeBatch, ExtractedShapes, GeneratedShaders, PipelineKey,
+    SetShapeParamsBindGroup, SetShapeTextureBindGroup, SetShapeViewBindGroup, ShapeBatches,
+    ShapeRenderSystems, ShapeViews, SmudBatchSorting, SmudPipeline, SmudPipelineKey, SmudShape,
+    collect_visible_shapes,
+    diagnostics::collect_pipeline_stats,
+    generate_shaders,
+    opaque::{OpaqueShape, OpaqueShapes, queue_opaque_shapes},
+    queue_order,
+    shader_errors::ShaderErrors,
+    shape_pipelines::{ShaderKey, ShapePipelines, remove_shape_pipelines},
+    view_pipeline_key,
+};
+
+/// A fill with typed uniforms and textures, similar to bevy's `Material2d`
+///
+/// The bindings generated by [`AsBindGroup`] are available to the fill shader in bind group 3:
+///
+/// ```
+/// # use bevy::{prelude::*, render::render_resource::AsBindGroup, shader::ShaderRef};
+/// # use bevy_smud::prelude::*;
+/// #[derive(Asset, TypePath, AsBindGroup, Clone)]
+/// struct FireFill {
+///     #[uniform(0)]
+///     speed: f32,
+///     #[texture(1)]
+///     #[sampler(2)]
+///     palette: Handle<Image>,
+/// }
+///
+/// impl SmudMaterial for FireFill {
+///     fn fill_shader() -> ShaderRef {
+///         "fills/fire_material.wgsl".into()
+///     }
+/// }
+/// ```
+///
+/// ```wgsl
+/// @group(3) @binding(0) var<uniform> speed: f32;
+/// @group(3) @binding(1) var palette: texture_2d<f32>;
+/// @group(3) @binding(2) var palette_sampler: sampler;
+///
+/// fn fill(input: smud::FillInput) -> vec4<f32> {
+///     // ...
+/// }
+/// ```
+///
+/// Add a [`SmudMaterialPlugin`] for the type, and a [`ShapeMaterial`] to the shapes using it.
+pub trait SmudMaterial:
+    AsBindGroup<Data: PartialEq + Eq + Hash + Clone> + Asset + Clone + Sized
+{
+    /// Shader containing a wgsl function for the fill of the shape
+    ///
+    /// The shader needs to have the signature `fn fill(input: smud::FillInput) -> vec4<f32>`.
+    fn fill_shader() -> ShaderRef {
+        ShaderRef::Default
+    }
+}
+
+/// Component selecting the [`SmudMaterial`] of a shape
+///
+/// Shapes with the default [`SmudShape::fill`] are drawn with [`SmudMaterial::fill_shader`], without
+/// changing the fill of the shape. Shapes with another fill keep it, and the material bindings are
+/// available to that fill instead.
+/// Shapes sharing a material instance can be batched together.
+#[derive(Component, Clone, Debug, Deref, DerefMut, PartialEq, Eq)]
+#[require(SmudShape)]
+pub struct ShapeMaterial<M: SmudMaterial>(pub Handle<M>);
+
+impl<M: SmudMaterial> From<Handle<M>> for ShapeMaterial<M> {
+    fn from(handle: Handle<M>) -> Self {
+        Self(handle)
+    }
+}
+
+/// Adds support for shapes with a [`SmudMaterial`] of type `M`
+///
+/// Requires [`SmudPlugin`](crate::SmudPlugin).
+pub struct SmudMaterialPlugin<M: SmudMaterial>(PhantomData<M>);
+
+impl<M: SmudMaterial> Default for SmudMaterialPlugin<M> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<M: SmudMaterial> Plugin for SmudMaterialPlugin<M> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<M>()
+            .init_resource::<MaterialFill<M>>()
+            .add_plugins(RenderAssetPlugin::<PreparedSmudMaterial<M>>::default());
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .init_resource::<ShapePipelines<SmudMaterialPipeline<M>>>()
+                .add_render_command::<Transparent2d, DrawSmudMaterialShape<M>>()
+                .add_render_command::<AlphaMask2d, DrawSmudMaterialShape<M>>()
+                .add_systems(
+                    ExtractSchedule,
+                    (
+                        generate_material_shaders::<M>.after(generate_shaders),
+                        extract_removed_shape_materials::<M>
+                            .before(ShapeRenderSystems::ExtractShapes),
+                        (
+                            extract_shape_materials::<M>.after(generate_material_shaders::<M>),
+                            remove_shape_pipelines::<SmudMaterialPipeline<M>>,
+                        )
+                            .after(ShapeRenderSystems::ExtractShapes),
+                    ),
+                )
+                .add_systems(
+                    Render,
+                    queue_material_shapes::<M>
+                        .in_set(RenderSystems::Queue)
+                        .before(queue_opaque_shapes),
+                )
+                .add_systems(
+                    Render,
+                    collect_pipeline_stats::<SmudMaterialPipeline<M>>
+                        .in_set(RenderSystems::PrepareBindGroups),
+                );
+        }
+    }
+
+    fn finish(&self, app: &mut App) {
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app.init_resource::<SmudMaterialPipeline<M>>();
+        }
+    }
+}
+
+/// The fill shader of the material type, loaded once
+#[derive(Resource)]
+struct MaterialFill<M: SmudMaterial> {
+    shader: Handle<Shader>,
+    marker: PhantomData<M>,
+}
+
+impl<M: SmudMaterial> FromWorld for MaterialFill<M> {
+    fn from_world(world: &mut World) -> Self {
+        let shader = match M::fill_shader() {
+            ShaderRef::Default => DEFAULT_FILL_HANDLE,
+            ShaderRef::Handle(handle) => handle,
+            ShaderRef::Path(path) => world.resource::<AssetServer>().load(path),
+        };
+        Self {
+            shader,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<M: SmudMaterial> MaterialFill<M> {
+    /// The fill a shape with the material is drawn with, if it's not the fill of the shape
+    fn replaced_fill(&self, shape: &SmudShape) -> Option<&Handle<Shader>> {
+        (shape.fill == DEFAULT_FILL_HANDLE && self.shader != DEFAULT_FILL_HANDLE)
+            .then_some(&self.shader)
+    }
+}
+
+/// Generate the shaders of shapes drawn with the fill of the material
+fn generate_material_shaders<M: SmudMaterial>(
+    mut main_world: ResMut<MainWorld>,
+    mut generated_shaders: ResMut<GeneratedShaders>,
+) {
+    main_world.resource_scope(|world, mut shaders: Mut<Assets<Shader>>| {
+        world.resource_scope(|world, material_fill: Mut<MaterialFill<M>>| {
+            let mut shapes = world.query_filtered::<&SmudShape, With<ShapeMaterial<M>>>();
+            for shape in shapes.iter(world) {
+                if let Some(fill) = material_fill.replaced_fill(shape) {
+                    generated_shaders.try_generate(&shape.sdf, fill, &mut shaders);
+                }
+            }
+        });
+    });
+}
+
+/// The bind group of a material, prepared in the render world
+pub(crate) struct PreparedSmudMaterial<M: SmudMaterial> {
+    bind_group: BindGroup,
+    key: M::Data,
+}
+
+impl<M: SmudMaterial> RenderAsset for PreparedSmudMaterial<M> {
+    type SourceAsset = M;
+
+    type Param = (
+        SRes<RenderDevice>,
+        SRes<PipelineCache>,
+        SRes<SmudMaterialPipeline<M>>,
+        M::Param,
+    );
+
+    fn prepare_asset(
+        material: Self::SourceAsset,
+        _asset_id: AssetId<Self::SourceAsset>,
+        (render_device, pipeline_cache, pipeline, material_param): &mut SystemParamItem<
+            Self::Param,
+        >,
+        _previous_asset: Option<&Self>,
+    ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        match material.as_bind_group(
+            &pipeline.material_layout,
+            render_device,
+            pipeline_cache,
+            material_param,
+        ) {
+            Ok(prepared) => Ok(PreparedSmudMaterial {
+                bind_group: prepared.bind_group,
+                key: material.bind_group_data(),
+            }),
+            Err(AsBindGroupError::RetryNextUpdate) => {
+                Err(PrepareAssetError::RetryNextUpdate(material))
+            }
+            Err(other) => Err(PrepareAssetError::AsBindGroupError(other)),
+        }
+    }
+}
+
+/// [`SmudPipeline`] with an extra bind group for the material
+#[derive(Resource)]
+pub(crate) struct SmudMaterialPipeline<M: SmudMaterial> {
+    smud_pipeline: SmudPipeline,
+    material_layout: BindGroupLayoutDescriptor,
+    marker: PhantomData<M>,
+}
+
+impl<M: SmudMaterial> FromWorld for SmudMaterialPipeline<M> {
+    fn from_world(world: &mut World) -> Self {
+        let material_layout = M::bind_group_layout_descriptor(world.resource::<RenderDevice>());
+        Self {
+            smud_pipeline: SmudPipeline::from_world(world),
+            material_layout,
+            marker: PhantomData,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct SmudMaterialPipelineKey<D> {
+    shape: SmudPipelineKey,
+    /// [`AsBindGroup::Data`] of the material
+    material: D,
+}
+
+impl<D: Send + Sync + 'static> ShaderKey for SmudMaterialPipelineKey<D> {
+    fn shader(&self) -> AssetId<Shader> {
+        self.shape.shader()
+    }
+}
+
+impl<M: SmudMaterial> SpecializedRenderPipeline for SmudMaterialPipeline<M> {
+    type Key = SmudMaterialPipelineKey<M::Data>;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut descriptor = self.smud_pipeline.specialize(key.shape);
+        // Bind group 3 is the material
+        descriptor.layout.push(self.material_layout.clone());
+        descriptor.label = Some("bevy_smud_material_pipeline".into());
+        descriptor
+    }
+}
+
+/// Shapes that lost their material are extracted again, with their own fill and no material
+fn extract_removed_shape_materials<M: SmudMaterial>(
+    mut extracted_shapes: ResMut<ExtractedShapes>,
+    mut removed_materials: Extract<RemovedComponents<ShapeMaterial<M>>>,
+) {
+    for main_entity in removed_materials.read() {
+        extracted_shapes.remove(main_entity.into());
+    }
+}
+
+/// Tag the retained shapes with their material, and use the shader generated with the fill of the
+/// material
+///
+/// Only shapes that were extracted this frame, or whose material changed, are updated.
+#[allow(clippy::type_complexity)]
+fn extract_shape_materials<M: SmudMaterial>(
+    mut extracted_shapes: ResMut<ExtractedShapes>,
+    generated_shaders: Res<GeneratedShaders>,
+    material_fill: Extract<Res<MaterialFill<M>>>,
+    materials: Extract<Query<(Entity, &SmudShape, Ref<ShapeMaterial<M>>)>>,
+) {
+    let tick = extracted_shapes.tick;
+    for (main_entity, shape, material) in materials.iter() {
+        let main_entity = MainEntity::from(main_entity);
+        let Some(extracted) = extracted_shapes.get(main_entity) else {
+            continue;
+        };
+        if !material.is_changed() && extracted.changed_tick != tick {
+            continue;
+        }
+
+        let shader = match material_fill.replaced_fill(shape) {
+            Some(fill) => match generated_shaders.get(shape.sdf.id(), fill.id()) {
+                Some(shader) => Some(shader.clone()),
+                None => {
+                    // Not in the store, so we'll try again next frame
+                    extracted_shapes.remove(main_entity);
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        if let Some(extracted) = extracted_shapes.get_mut(main_entity) {
+            if let Some(shader) = shader {
+                extracted.shader = shader;
+            }
+            extracted.material = Some(material.id().untyped());
+            extracted.changed_tick = tick;
+        }
+    }
+}
+
+fn queue_material_shapes<M: SmudMaterial>(
+    mut view_entities: Local<FixedBitSet>,
+    mut order: Local<Vec<usize>>,
+    draw_functions: Res<DrawFunctions<Transparent2d>>,
+    alpha_mask_draw_functions: Res<DrawFunctions<AlphaMask2d>>,
+    mut opaque_shapes: ResMut<OpaqueShapes>,
+    material_pipeline: Res<SmudMaterialPipeline<M>>,
+    mut pipelines: ResMut<ShapePipelines<SmudMaterialPipeline<M>>>,
+    pipeline_cache: Res<PipelineCache>,
+    extracted_shapes: Res<ExtractedShapes>,
+    render_materials: Res<RenderAssets<PreparedSmudMaterial<M>>>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    mut shader_errors: ResMut<ShaderErrors>,
+    sorting: Option<Res<SmudBatchSorting>>,
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent2d>>,
+    mut views: ShapeViews,
+) {
+    let draw_function = draw_functions
+        .read()
+        .get_id::<DrawSmudMaterialShape<M>>()
+        .unwrap();
+    let opaque_draw_function = alpha_mask_draw_functions
+        .read()
+        .get_id::<DrawSmudMaterialShape<M>>()
+        .unwrap();
+    let sorting = sorting.as_deref();
+
+    queue_order(&extracted_shapes, sorting, &mut order);
+
+    for (visible_entities, camera, view, msaa, tonemapping, dither) in &mut views {
+        let Some(transparent_phase) = transparent_render_phases.get_mut(&view.retained_view_entity)
+        else {
+            continue;
+        };
+
+        let view_key = view_pipeline_key(camera, view, msaa, tonemapping, dither);
+
+        collect_visible_shapes(visible_entities, &mut view_entities);
+
+        for &index in order.iter() {
+            let extracted_shape = &extracted_shapes.shapes[index];
+            let Some(material_id) = extracted_shape.material else {
+                continue;
+            };
+            if material_id.type_id() != TypeId::of::<M>() {
+                continue;
+            }
+            if !view_entities.contains(extracted_shape.main_entity.id().index_u32() as usize) {
+                continue;
+            }
+            let Some(material) = render_materials.get(material_id.typed::<M>()) else {
+                debug!("Material not prepared yet, skipping");
+                continue;
+            };
+            if let Some(image) = extracted_shape.image
+                && gpu_images.get(image).is_none()
+            {
+                debug!("Image not loaded yet, skipping");
+                continue;
+            }
+
+            let mesh_key = view_key | PipelineKey::from_shape(extracted_shape);
+            let specialize_key = SmudMaterialPipelineKey {
+                shape: SmudPipelineKey {
+                    mesh: mesh_key,
+                    shader: extracted_shape.shader.clone(),
+                },
+                material: material.key.clone(),
+            };
+            let mut pipeline =
+                pipelines.specialize(&pipeline_cache, &material_pipeline, specialize_key);
+
+            if shader_errors.failed(
+                &pipeline_cache,
+                pipeline,
+                extracted_shape.main_entity,
+                &extracted_shape.shader,
+            ) {
+                let Some(fallback) = shader_errors.fallback.clone() else {
+                    continue;
+                };
+                let fallback_key = SmudMaterialPipelineKey {
+                    shape: SmudPipelineKey {
+                        mesh: mesh_key,
+                        shader: fallback,
+                    },
+                    material: material.key.clone(),
+                };
+                pipeline = pipelines.specialize(&pipeline_cache, &material_pipeline, fallback_key);
+            }
+
+            if pipeline == CachedRenderPipelineId::INVALID {
+                debug!("Shape not ready yet, skipping");
+                continue;
+            }
+
+            if extracted_shape.blend_mode == BlendMode::Opaque {
+                opaque_shapes.push(
+                    view.retained_view_entity,
+                    OpaqueShape {
+                        extracted_index: index,
+                        pipeline,
+                        draw_function: opaque_draw_function,
+                    },
+                );
+                continue;
+            }
+
+            transparent_phase.add_transient(Transparent2d {
+                draw_function,
+                pipeline,
+                entity: (extracted_shape.render_entity, extracted_shape.main_entity),
+                sort_key: SmudBatchSorting::sort_key(
+                    sorting,
+                    extracted_shape.transform.translation().z,
+                ),
+                // batch_range and dynamic_offset will be calculated in prepare_shapes
+                batch_range: 0..0,
+                extra_index: PhaseItemExtraIndex::None,
+                extracted_index: index,
+                indexed: true,
+            });
+        }
+    }
+}
+
+type DrawSmudMaterialShape<M> = (
+    SetItemPipeline,
+    SetShapeViewBindGroup<0>,
+    SetShapeParamsBindGroup<1>,
+    SetShapeTextureBindGroup<2>,
+    SetSmudMaterialBindGroup<M, 3>,
+    DrawShapeBatch,
+);
+
+struct SetSmudMaterialBindGroup<M: SmudMaterial, const I: usize>(PhantomData<M>);
+impl<P: PhaseItem, M: SmudMaterial, const I: usize> RenderCommand<P>
+    for SetSmudMaterialBindGroup<M, I>
+{
+    type Param = (
+        SRes<ShapeBatches>,
+        SRes<RenderAssets<PreparedSmudMaterial<M>>>,
+    );
+    type ViewQuery = Read<ExtractedView>;
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &P,
+        view: ROQueryItem<'w, '_, Self::ViewQuery>,
+        _entity: Option<()>,
+        (batches, render_materials): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(batch) = batches.get(&(view.retained_view_entity, item.main_entity())) else {
+            return RenderCommandResult::Skip;
+        };
+        let Some(material) = batch
+            .material
+            .and_then(|material| render_materials.into_inner().get(material.typed::<M>()))
+        else {
+            return RenderCommandResult::Skip;
+        };
+        pass.set_bind_group(I, &material.bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
@@ -0,0 +1,204 @@
+//! Render path for [`BlendMode::Opaque`](crate::BlendMode::Opaque) shapes
+//!
+//! Opaque shapes discard fragments outside the shape and write depth, so they go in the
+//! [`AlphaMask2d`] phase instead of [`Transparent2d`](bevy::core_pipeline::core_2d::Transparent2d).
+//! Every sdf shape needs to discard outside the shape, so none of them go in `Opaque2d`.
+
+use std::cmp::Reverse;
+
+use bevy::{
+    asset::UntypedAssetId,
+    core_pipeline::core_2d::{AlphaMask2d, AlphaMask2dBinKey, BatchSetKey2d},
+    math::FloatOrd,
+    platform::collections::{HashMap, HashSet},
+    prelude::*,
+    render::{
+        render_phase::{
+            BinnedRenderPhaseType, DrawFunctionId, InputUniformIndex, ViewBinnedRenderPhases,
+        },
+        render_resource::CachedRenderPipelineId,
+        sync_world::MainEntity,
+        view::RetainedViewEntity,
+    },
+};
+
+use crate::ExtractedShapes;
+
+/// An opaque shape, ready to be drawn
+pub(crate) struct OpaqueShape {
+    pub extracted_index: usize,
+    pub pipeline: CachedRenderPipelineId,
+    pub draw_function: DrawFunctionId,
+}
+
+/// Opaque shapes pushed by the queue systems, and the batches they were grouped into
+#[derive(Resource, Default)]
+pub(crate) struct OpaqueShapes {
+    queued: HashMap<RetainedViewEntity, Vec<OpaqueShape>>,
+    /// Extracted shape indices of each batch, sorted front to back within the batch
+    pub batches: HashMap<RetainedViewEntity, Vec<Vec<usize>>>,
+    /// Shapes representing a batch in the phase of each view, and the bin they were added to
+    ///
+    /// The phase is kept between frames, so they're removed once they no longer represent a
+    /// batch in the same bin.
+    added: HashMap<RetainedViewEntity, HashMap<MainEntity, AlphaMask2dBinKey>>,
+}
+
+impl OpaqueShapes {
+    pub fn push(&mut self, view: RetainedViewEntity, shape: OpaqueShape) {
+        self.queued.entry(view).or_default().push(shape);
+    }
+}
+
+/// Shapes that share all of these can be drawn in one batch
+#[derive(PartialEq, Eq, Hash)]
+struct OpaqueBatchKey {
+    pipeline: CachedRenderPipelineId,
+    draw_function: DrawFunctionId,
+    shader: AssetId<Shader>,
+    image: Option<AssetId<Image>>,
+    material: Option<UntypedAssetId>,
+}
+
+/// Group the queued opaque shapes into batches, and add one phase item per batch
+pub(crate) fn queue_opaque_shapes(
+    mut opaque_shapes: ResMut<OpaqueShapes>,
+    extracted_shapes: Res<ExtractedShapes>,
+    mut alpha_mask_phases: ResMut<ViewBinnedRenderPhases<AlphaMask2d>>,
+) {
+    let OpaqueShapes {
+        queued,
+        batches,
+        added,
+    } = &mut *opaque_shapes;
+    batches.clear();
+
+    // Views without opaque shapes this frame may still have items from earlier frames
+    let views = queued
+        .keys()
+        .chain(added.keys())
+        .copied()
+        .collect::<HashSet<_>>();
+
+    for view in views {
+        let shapes = queued.remove(&view).unwrap_or_default();
+        let previous = added.remove(&view).unwrap_or_default();
+        let Some(alpha_mask_phase) = alpha_mask_phases.get_mut(&view) else {
+            continue;
+        };
+
+        let mut groups: HashMap<OpaqueBatchKey, Vec<usize>> = HashMap::default();
+        for shape in shapes {
+            let extracted_shape = &extracted_shapes.shapes[shape.extracted_index];
+            let key = OpaqueBatchKey {
+                pipeline: shape.pipeline,
+                draw_function: shape.draw_function,
+                shader: extracted_shape.shader.id(),
+                image: extracted_shape.image,
+                material: extracted_shape.material,
+            };
+            groups.entry(key).or_default().push(shape.extracted_index);
+        }
+
+        let view_batches = batches.entry(view).or_default();
+        let mut representatives: HashMap<MainEntity, (Entity, AlphaMask2dBinKey)> =
+            HashMap::default();
+        for (key, mut indices) in groups {
+            // Front to back, so hidden fragments of the batch fail the depth test early. The
+            // phase orders batches by their bin key rather than by depth, so this doesn't hold
+            // across batches.
+            indices.sort_by_key(|&index| {
+                Reverse(FloatOrd(
+                    extracted_shapes.shapes[index].transform.translation().z,
+                ))
+            });
+
+            // The first shape represents the batch, see `prepare_shapes`
+            let first = &extracted_shapes.shapes[indices[0]];
+            let bin_key = AlphaMask2dBinKey {
+                pipeline: key.pipeline,
+                draw_function: key.draw_function,
+                asset_id: key.shader.untyped(),
+                material_bind_group_id: None,
+            };
+            representatives.insert(first.main_entity, (first.render_entity, bin_key));
+            view_batches.push(indices);
+        }
+
+        // Removed before adding, since the phase only removes shapes from the bin they were last
+        // added to
+        for (main_entity, bin_key) in &previous {
+            if representatives
+                .get(main_entity)
+                .is_none_or(|(_, key)| key != bin_key)
+            {
+                alpha_mask_phase.remove(*main_entity);
+            }
+        }
+
+        let view_added = added.entry(view).or_default();
+        for (main_entity, (render_entity, bin_key)) in representatives {
+            alpha_mask_phase.add(
+                BatchSetKey2d { indexed: false },
+                bin_key.clone(),
+                (render_entity, main_entity),
+                InputUniformIndex::default(),
+                BinnedRenderPhaseType::NonMesh,
+            );
+            view_added.insert(main_entity, bin_key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::system::RunSystemOnce, render::batching::gpu_preprocessing::GpuPreprocessingMode,
+    };
+
+    use super::*;
+    use crate::{BlendMode, ExtractedShape, tests::shape};
+
+    #[test]
+    fn test_shapes_no_longer_opaque_are_removed_from_the_phase() {
+        let view = RetainedViewEntity::new(Entity::PLACEHOLDER.into(), None, 0);
+        let main_entity = MainEntity::from(Entity::from_raw_u32(1).unwrap());
+
+        let mut world = World::new();
+        let mut phases = ViewBinnedRenderPhases::<AlphaMask2d>::default();
+        phases.prepare_for_new_frame(view, GpuPreprocessingMode::None);
+        world.insert_resource(phases);
+        world.init_resource::<OpaqueShapes>();
+        let mut extracted_shapes = ExtractedShapes::default();
+        extracted_shapes.insert(ExtractedShape {
+            main_entity,
+            blend_mode: BlendMode::Opaque,
+            ..shape(0., Handle::default())
+        });
+        world.insert_resource(extracted_shapes);
+
+        let queue = |world: &mut World, opaque: bool| {
+            if opaque {
+                world.resource_mut::<OpaqueShapes>().push(
+                    view,
+                    OpaqueShape {
+                        extracted_index: 0,
+                        pipeline: CachedRenderPipelineId::INVALID,
+                        draw_function: DrawFunctionId(0),
+                    },
+                );
+            }
+            world.run_system_once(queue_opaque_shapes).unwrap();
+            let phases = world.resource::<ViewBinnedRenderPhases<AlphaMask2d>>();
+            phases[&view]
+                .non_mesh_items
+                .values()
+                .any(|items| items.entities.contains_key(&main_entity))
+        };
+
+        assert!(queue(&mut world, true));
+        assert!(queue(&mut world, true));
+        // Switched to another blend mode, so it's queued in the transparent phase instead
+        assert!(!queue(&mut world, false));
+    }
+}
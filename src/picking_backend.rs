@@ -14,14 +14,15 @@
 //!
 //! - The backend considers a shape picked if the pointer is within the shape's bounding box
 //! - The `position` reported in `HitData` is in world space
-//! - The `normal` points away from the shape using the transform's back vector
+//! - With a [`SmudPickingShape`], the `normal` is the gradient of its distance function in world
+//!   space, otherwise it points away from the shape using the transform's back vector
 //! - Depth is calculated based on the shape's Z position in camera space
 
 use std::sync::Arc;
 
-use bevy::{picking::PickingSystems, picking::backend::prelude::*, prelude::*};
+use bevy::{math::Vec3Swizzles, picking::PickingSystems, picking::backend::prelude::*, prelude::*};
 
-use crate::SmudShape;
+use crate::{SmudAnchor, SmudShape, sdf::estimate_gradient, util::shape_matrix2};
 
 /// Input parameters for SDF picking functions, matching the shader SdfInput struct.
 #[derive(Debug, Clone, Copy)]
@@ -68,7 +69,7 @@ impl SmudPickingShape {
     /// # use bevy_smud::prelude::*;
     /// # use bevy_smud::sdf;
     /// # let _ =
-    /// SmudPickingShape::new(|p| sdf::circle(p, 50.0))
+    /// SmudPickingShape::new(|p| sdf::sd_circle(p, 50.0))
     /// # ;
     /// ```
     pub fn new<F>(distance_fn: F) -> Self
@@ -91,7 +92,7 @@ impl SmudPickingShape {
     /// # let _ =
     /// SmudPickingShape::with_input(|input| {
     ///     let radius = input.bounds.x.min(input.bounds.y);
-    ///     sdf::circle(input.pos, radius)
+    ///     sdf::sd_circle(input.pos, radius)
     /// })
     /// # ;
     /// ```
@@ -140,6 +141,7 @@ pub fn smud_picking(
     shapes: Query<(
         Entity,
         &SmudShape,
+        &SmudAnchor,
         &GlobalTransform,
         &ViewVisibility,
         Option<&Pickable>,
@@ -151,7 +153,7 @@ pub fn smud_picking(
     let mut sorted_shapes: Vec<_> = shapes
         .iter()
         .filter_map(
-            |(entity, shape, transform, visibility, pickable, sdf_shape)| {
+            |(entity, shape, anchor, transform, visibility, pickable, sdf_shape)| {
                 // Skip if visibility is off or transform is invalid
                 if !visibility.get() || transform.affine().is_nan() {
                     return None;
@@ -169,18 +171,20 @@ pub fn smud_picking(
                     return None;
                 }
 
-                Some((entity, shape, transform, pickable, sdf_shape))
+                Some((entity, shape, anchor, transform, pickable, sdf_shape))
             },
         )
         .collect();
 
     // Sort by Z coordinate (back to front)
-    sorted_shapes.sort_by(|(_, _, transform_a, _, _), (_, _, transform_b, _, _)| {
-        transform_b
-            .translation()
-            .z
-            .total_cmp(&transform_a.translation().z)
-    });
+    sorted_shapes.sort_by(
+        |(_, _, _, transform_a, _, _), (_, _, _, transform_b, _, _)| {
+            transform_b
+                .translation()
+                .z
+                .total_cmp(&transform_a.translation().z)
+        },
+    );
 
     for (&ray_id, &ray) in ray_map.map.iter() {
         let (camera_entity, pointer) = (ray_id.camera, ray_id.pointer);
@@ -199,7 +203,7 @@ pub fn smud_picking(
         let mut blocked = false;
 
         // Test intersection with each shape
-        for (entity, shape, shape_transform, pickable, sdf_picking) in &sorted_shapes {
+        for (entity, shape, anchor, shape_transform, pickable, sdf_picking) in &sorted_shapes {
             if blocked {
                 break;
             }
@@ -219,9 +223,15 @@ pub fn smud_picking(
             let t = (shape_z - ray_start.z) / ray_direction.z;
             let intersection_point = ray_start + *ray_direction * t;
 
-            // Transform the intersection point to shape local space
-            let world_to_shape = shape_transform.affine().inverse();
-            let local_point = world_to_shape.transform_point3(intersection_point);
+            // Transform the intersection point to shape local space, using the same
+            // 2x2 matrix as the renderer so non-uniform scale and skew match
+            let shape_matrix = shape_matrix2(shape_transform);
+            if shape_matrix.determinant() == 0.0 {
+                continue;
+            }
+            let local_point = shape_matrix.inverse()
+                * (intersection_point.xy() - shape_transform.translation().xy())
+                - anchor.offset(shape.bounds);
 
             // Check if the point is within the shape using SDF or bounding box
             let is_hit = if let Some(sdf_shape) = sdf_picking {
@@ -247,14 +257,28 @@ pub fn smud_picking(
                     .transform_point3(intersection_point);
                 let depth = -hit_pos_cam.z;
 
+                // The gradient of the distance function points away from the surface. Normals
+                // transform with the inverse transpose, so they stay perpendicular to the surface
+                // under non-uniform scale and skew
+                let normal = sdf_picking
+                    .and_then(|sdf_shape| {
+                        let gradient = estimate_gradient(
+                            |pos| {
+                                (sdf_shape.distance_fn)(SdfInput {
+                                    pos,
+                                    bounds: shape.bounds.half_size,
+                                    params: shape.params,
+                                })
+                            },
+                            local_point,
+                        );
+                        (shape_matrix.inverse().transpose() * gradient).try_normalize()
+                    })
+                    .map_or(*shape_transform.back(), |normal| normal.extend(0.0));
+
                 picks.push((
                     *entity,
-                    HitData::new(
-                        cam_entity,
-                        depth,
-                        Some(intersection_point),
-                        Some(*shape_transform.back()),
-                    ),
+                    HitData::new(cam_entity, depth, Some(intersection_point), Some(normal)),
                 ));
 
                 // Check if this shape should block shapes behind it
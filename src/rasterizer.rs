@@ -0,0 +1,420 @@
+//! CPU reference rasterizer for shapes
+//!
+//! Draws [`SmudShape`]s with a Rust sdf and fill into an RGBA buffer, the way the shape
+//! pipeline would draw them with a 2d camera. This makes it possible to test that shapes look
+//! right without a GPU, for instance by comparing against previously rendered images.
+//!
+//! The built-in fills have Rust equivalents in this module: [`simple_fill`],
+//! [`cubic_falloff_fill`] and [`outline_fill`]. Screen space derivatives (`fwidth`) are
+//! approximated with the distance at neighbouring pixels.
+//!
+//! Blending happens in linear space, the result is converted to sRGB by
+//! [`Rasterizer::to_rgba8`]. Tonemapping is not applied.
+
+use bevy::{
+    asset::RenderAssetUsages,
+    color::{ColorToComponents, ColorToPacked},
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
+
+use crate::{
+    BlendMode, SmudAnchor, SmudShape,
+    sdf::{estimate_gradient, sd_fill_alpha_fwidth},
+    util::shape_matrix2,
+};
+
+/// Input of a fill function, matching the shader `FillInput`
+#[derive(Debug, Clone, Copy)]
+pub struct FillInput {
+    /// Position in shape space
+    pub pos: Vec2,
+    /// [`SmudShape::params`]
+    pub params: Vec4,
+    /// [`SmudShape::fill_params`]
+    pub fill_params: Vec4,
+    /// Signed distance to the shape
+    pub distance: f32,
+    /// [`SmudShape::color`], in linear space
+    pub color: Vec4,
+    /// Half size of the shape, see [`SmudShape::bounds`]
+    pub bounds: Vec2,
+    /// Sum of the absolute change in distance to the neighbouring pixels, like `fwidth` in wgsl
+    pub fwidth: f32,
+    /// Direction away from the shape in shape space, see [`crate::sdf::distance_gradient`]
+    pub gradient: Vec2,
+}
+
+/// Equivalent of the simple fill, [`SIMPLE_FILL_HANDLE`](crate::SIMPLE_FILL_HANDLE)
+pub fn simple_fill(input: FillInput) -> Vec4 {
+    let a = sd_fill_alpha_fwidth(input.distance, input.fwidth);
+    input.color.truncate().extend(a * input.color.w)
+}
+
+/// Equivalent of the default fill, [`DEFAULT_FILL_HANDLE`](crate::DEFAULT_FILL_HANDLE)
+pub fn cubic_falloff_fill(input: FillInput) -> Vec4 {
+    let d2 = 1. - (input.distance * 0.13);
+    let alpha = (d2 * d2 * d2).clamp(0., 1.) * input.color.w;
+    let shadow_color = 0.2 * input.color.truncate();
+    let aaf = 0.7 / input.fwidth.max(f32::EPSILON);
+    let c = input
+        .color
+        .truncate()
+        .lerp(shadow_color, (input.distance * aaf).clamp(0., 1.));
+    c.extend(alpha)
+}
+
+/// Equivalent of the outline fill in `fills/outline.wgsl`
+pub fn outline_fill(input: FillInput) -> Vec4 {
+    let d_2 = (input.distance - 1.).abs() - 1.;
+    let a = sd_fill_alpha_fwidth(d_2, input.fwidth);
+    input.color.truncate().extend(a * input.color.w)
+}
+
+/// Renders shapes into a buffer on the cpu, see the [module docs](self)
+#[derive(Debug, Clone)]
+pub struct Rasterizer {
+    size: UVec2,
+    /// World position at the center of the image
+    pub center: Vec2,
+    /// World units per pixel, like the scale of an orthographic projection
+    pub scale: f32,
+    /// Linear colors, row by row from the top
+    pixels: Vec<Vec4>,
+}
+
+impl Rasterizer {
+    /// A rasterizer with the origin at the center of the image, cleared to transparent black
+    pub fn new(size: UVec2) -> Self {
+        Self {
+            size,
+            center: Vec2::ZERO,
+            scale: 1.,
+            pixels: vec![Vec4::ZERO; (size.x * size.y) as usize],
+        }
+    }
+
+    /// Size of the image in pixels
+    pub fn size(&self) -> UVec2 {
+        self.size
+    }
+
+    /// Set every pixel to a color
+    pub fn clear(&mut self, color: impl Into<Color>) {
+        let color = Vec4::from_array(color.into().to_linear().to_f32_array());
+        self.pixels.fill(color);
+    }
+
+    /// Linear color of a pixel, `(0, 0)` is the top left
+    pub fn pixel(&self, x: u32, y: u32) -> LinearRgba {
+        LinearRgba::from_f32_array(self.pixels[(y * self.size.x + x) as usize].to_array())
+    }
+
+    /// World position of the center of a pixel
+    pub fn pixel_position(&self, x: u32, y: u32) -> Vec2 {
+        let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - self.size.as_vec2() / 2.;
+        self.center + Vec2::new(offset.x, -offset.y) * self.scale
+    }
+
+    /// Draw a shape on top of what's already drawn
+    ///
+    /// `sdf` gets positions in shape space, like `input.pos` in a shader. Only the xy plane of
+    /// the transform is used, like with a 2d camera.
+    pub fn draw(
+        &mut self,
+        shape: &SmudShape,
+        anchor: &SmudAnchor,
+        transform: &GlobalTransform,
+        sdf: impl Fn(Vec2) -> f32,
+        fill: impl Fn(FillInput) -> Vec4,
+    ) {
+        let matrix = shape_matrix2(transform);
+        if matrix.determinant().abs() < 1e-12 {
+            return;
+        }
+        let inverse = matrix.inverse();
+        let center = transform.translation().xy() + matrix * anchor.offset(shape.bounds);
+        let quad = shape.bounds.half_size + Vec2::splat(shape.extra_bounds);
+        let color = Vec4::from_array(shape.color.to_linear().to_f32_array());
+
+        // Shape space offsets of one pixel right and one pixel down
+        let step_x = inverse * Vec2::new(self.scale, 0.);
+        let step_y = inverse * Vec2::new(0., -self.scale);
+        let distance_scale = distance_scale(matrix);
+
+        for y in 0..self.size.y {
+            for x in 0..self.size.x {
+                let pos = inverse * (self.pixel_position(x, y) - center);
+                if pos.x.abs() > quad.x || pos.y.abs() > quad.y {
+                    continue;
+                }
+
+                let gradient = estimate_gradient(&sdf, pos);
+                let scale = distance_scale(gradient);
+                let distance = sdf(pos) * scale;
+                let fwidth = (sdf(pos + step_x) * scale - distance).abs()
+                    + (sdf(pos + step_y) * scale - distance).abs();
+
+                let src = fill(FillInput {
+                    pos,
+                    params: shape.params,
+                    fill_params: shape.fill_params,
+                    distance,
+                    color,
+                    bounds: shape.bounds.half_size,
+                    fwidth,
+                    gradient,
+                });
+
+                let index = (y * self.size.x + x) as usize;
+                if let Some(blended) = blend(shape.blend_mode, src, self.pixels[index]) {
+                    self.pixels[index] = blended.clamp(Vec4::ZERO, Vec4::ONE);
+                }
+            }
+        }
+    }
+
+    /// The image as sRGB bytes, four per pixel, row by row from the top
+    pub fn to_rgba8(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|pixel| {
+                Srgba::from(LinearRgba::from_f32_array(pixel.to_array())).to_u8_array()
+            })
+            .collect()
+    }
+
+    /// The image as an [`Image`] in [`TextureFormat::Rgba8UnormSrgb`]
+    pub fn to_image(&self) -> Image {
+        Image::new(
+            Extent3d {
+                width: self.size.x,
+                height: self.size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            self.to_rgba8(),
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+}
+
+/// Factor that corrects distances for non-uniform scale, like `smud::correct_distance`
+fn distance_scale(matrix: Mat2) -> impl Fn(Vec2) -> f32 {
+    let det = matrix.determinant();
+    let inverse_transpose = matrix.inverse().transpose();
+    move |gradient| {
+        if gradient.length_squared() < 1e-12 {
+            return 1.;
+        }
+        let w = inverse_transpose * gradient.normalize();
+        1. / (det.abs().sqrt() * w.length())
+    }
+}
+
+/// Blend a fill color with the color behind it, `None` if the fragment is discarded
+fn blend(blend_mode: BlendMode, src: Vec4, dst: Vec4) -> Option<Vec4> {
+    let (src_rgb, a) = (src.truncate(), src.w);
+    let dst_rgb = dst.truncate();
+    let over_alpha = a + dst.w * (1. - a);
+    Some(match blend_mode {
+        BlendMode::Alpha => (src_rgb * a + dst_rgb * (1. - a)).extend(over_alpha),
+        BlendMode::Additive => (src_rgb * a + dst_rgb).extend(a + dst.w),
+        BlendMode::Multiply => (Vec3::ONE.lerp(src_rgb, a) * dst_rgb).extend(dst.w),
+        BlendMode::Screen => {
+            let src_rgb = src_rgb * a;
+            (src_rgb * (Vec3::ONE - dst_rgb) + dst_rgb).extend(over_alpha)
+        }
+        BlendMode::Subtract => (dst_rgb - src_rgb * a).extend(dst.w),
+        BlendMode::PremultipliedAlpha => (src_rgb + dst_rgb * (1. - a)).extend(over_alpha),
+        BlendMode::Opaque => {
+            if a < 0.5 {
+                return None;
+            }
+            src_rgb.extend(1.)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::PathBuf};
+
+    use bevy::color::palettes::css;
+
+    use super::*;
+    use crate::sdf;
+
+    /// Largest difference per channel allowed when comparing with a golden image
+    const TOLERANCE: u8 = 2;
+
+    /// Compare the rasterized image with `tests/golden/{name}.pam`
+    ///
+    /// Run the tests with `SMUD_UPDATE_GOLDEN=1` to write the golden images instead, after
+    /// checking that the new results look right.
+    fn assert_golden(name: &str, rasterizer: &Rasterizer) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/golden")
+            .join(format!("{name}.pam"));
+        let size = rasterizer.size();
+        let actual = rasterizer.to_rgba8();
+
+        if std::env::var_os("SMUD_UPDATE_GOLDEN").is_some() {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, encode_pam(size, &actual)).unwrap();
+            return;
+        }
+
+        let golden = fs::read(&path).unwrap_or_else(|err| {
+            panic!(
+                "failed to read {}: {err}, run with SMUD_UPDATE_GOLDEN=1 to create it",
+                path.display()
+            )
+        });
+        let (golden_size, expected) = decode_pam(&golden);
+        assert_eq!(golden_size, size, "{name}: size differs from golden image");
+
+        let mismatches = actual
+            .chunks(4)
+            .zip(expected.chunks(4))
+            .enumerate()
+            .filter(|(_, (a, e))| a.iter().zip(*e).any(|(a, e)| a.abs_diff(*e) > TOLERANCE))
+            .map(|(i, (a, e))| (i as u32 % size.x, i as u32 / size.x, a, e))
+            .collect::<Vec<_>>();
+        assert!(
+            mismatches.is_empty(),
+            "{name}: {} pixels differ from golden image, first at {:?}",
+            mismatches.len(),
+            mismatches.first(),
+        );
+    }
+
+    /// Netpbm PAM, uncompressed RGBA that most image tools can open
+    fn encode_pam(size: UVec2, rgba: &[u8]) -> Vec<u8> {
+        let mut pam = format!(
+            "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+            size.x, size.y
+        )
+        .into_bytes();
+        pam.extend_from_slice(rgba);
+        pam
+    }
+
+    fn decode_pam(pam: &[u8]) -> (UVec2, &[u8]) {
+        const END: &[u8] = b"ENDHDR\n";
+        let header_len = pam
+            .windows(END.len())
+            .position(|window| window == END)
+            .expect("PAM header should end with ENDHDR")
+            + END.len();
+        let header = std::str::from_utf8(&pam[..header_len]).unwrap();
+        let field = |name: &str| -> u32 {
+            header
+                .lines()
+                .find_map(|line| line.strip_prefix(name)?.trim().parse().ok())
+                .unwrap_or_else(|| panic!("PAM header should have {name}"))
+        };
+        assert_eq!(field("DEPTH"), 4, "golden images should be RGBA");
+        (
+            UVec2::new(field("WIDTH"), field("HEIGHT")),
+            &pam[header_len..],
+        )
+    }
+
+    fn shape(color: Srgba, bounds: Rectangle) -> SmudShape {
+        SmudShape {
+            color: color.into(),
+            bounds,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn test_pixel_positions() {
+        let rasterizer = Rasterizer::new(UVec2::new(4, 2));
+        assert_eq!(rasterizer.pixel_position(0, 0), Vec2::new(-1.5, 0.5));
+        assert_eq!(rasterizer.pixel_position(3, 1), Vec2::new(1.5, -0.5));
+    }
+
+    #[test]
+    fn test_simple_fill_covers_shape() {
+        let mut rasterizer = Rasterizer::new(UVec2::splat(16));
+        rasterizer.draw(
+            &shape(css::RED, Rectangle::from_length(12.)),
+            &SmudAnchor::default(),
+            &GlobalTransform::IDENTITY,
+            |p| sdf::sd_circle(p, 5.),
+            simple_fill,
+        );
+
+        assert_eq!(rasterizer.pixel(8, 8), LinearRgba::RED);
+        assert_eq!(rasterizer.pixel(0, 0), LinearRgba::NONE);
+    }
+
+    #[test]
+    fn test_golden_simple_circle() {
+        let mut rasterizer = Rasterizer::new(UVec2::splat(64));
+        rasterizer.clear(css::BLACK);
+        rasterizer.draw(
+            &shape(css::TOMATO, Rectangle::from_length(50.)),
+            &SmudAnchor::default(),
+            &GlobalTransform::IDENTITY,
+            |p| sdf::sd_circle(p, 20.),
+            simple_fill,
+        );
+        assert_golden("simple_circle", &rasterizer);
+    }
+
+    #[test]
+    fn test_golden_cubic_falloff_box() {
+        let mut rasterizer = Rasterizer::new(UVec2::splat(64));
+        rasterizer.draw(
+            &shape(css::GOLD, Rectangle::new(40., 24.)),
+            &SmudAnchor::default(),
+            &Transform::from_rotation(Quat::from_rotation_z(0.3)).into(),
+            |p| sdf::sd_box(p, Vec2::new(16., 8.)),
+            cubic_falloff_fill,
+        );
+        assert_golden("cubic_falloff_box", &rasterizer);
+    }
+
+    #[test]
+    fn test_golden_outline_star() {
+        let mut rasterizer = Rasterizer::new(UVec2::splat(64));
+        rasterizer.clear(css::MIDNIGHT_BLUE);
+        // Non-uniform scale and an anchor exercise the distance correction and offsets
+        rasterizer.draw(
+            &shape(css::WHITE, Rectangle::from_length(40.)),
+            &SmudAnchor::BOTTOM_LEFT,
+            &Transform::from_xyz(-24., -20., 0.)
+                .with_scale(Vec3::new(1.2, 0.8, 1.))
+                .into(),
+            |p| sdf::sd_star_5(p, 10., 2.),
+            outline_fill,
+        );
+        assert_golden("outline_star", &rasterizer);
+    }
+
+    #[test]
+    fn test_golden_blend_modes() {
+        let mut rasterizer = Rasterizer::new(UVec2::splat(64));
+        rasterizer.clear(css::GRAY);
+        let circle = |p| sdf::sd_circle(p, 14.);
+        for (blend_mode, color, x, y) in [
+            (BlendMode::Alpha, css::RED.with_alpha(0.7), -10., 10.),
+            (BlendMode::Additive, css::LIME, 10., 10.),
+            (BlendMode::Multiply, css::BLUE, -10., -10.),
+            (BlendMode::Opaque, css::YELLOW, 10., -10.),
+        ] {
+            rasterizer.draw(
+                &shape(color, Rectangle::from_length(32.)).with_blend_mode(blend_mode),
+                &SmudAnchor::default(),
+                &Transform::from_xyz(x, y, 0.).into(),
+                circle,
+                simple_fill,
+            );
+        }
+        assert_golden("blend_modes", &rasterizer);
+    }
+}
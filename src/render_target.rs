@@ -0,0 +1,204 @@
+//! Rendering shapes into an offscreen [`Image`]
+
+use bevy::{
+    asset::RenderAssetUsages,
+    camera::{Hdr, RenderTarget, visibility::RenderLayers},
+    core_pipeline::tonemapping::{DebandDither, Tonemapping},
+    prelude::*,
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
+};
+
+/// Renders the shapes on some [`RenderLayers`] into an [`Image`], so the result can be used by
+/// other passes, for instance sampled in a compute shader
+///
+/// Spawns a [`Camera2d`] that only sees the given layers. Shapes drawn into the image should be
+/// on the same layers, and main cameras shouldn't see those layers.
+///
+/// Tonemapping and dithering are disabled, so the colors of the shapes end up in the image
+/// unchanged. Float formats are rendered with [`Hdr`] by default, so values outside `0..1` are
+/// kept.
+///
+/// ```no_run
+/// # use bevy::{camera::visibility::RenderLayers, prelude::*, render::render_resource::TextureFormat};
+/// # use bevy_smud::{SmudRenderTarget, prelude::*};
+/// #[derive(Resource)]
+/// struct DistanceField(Handle<Image>);
+///
+/// fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+///     let image = SmudRenderTarget::new(UVec2::new(512, 512), TextureFormat::R16Float)
+///         .with_layers(RenderLayers::layer(1))
+///         .spawn(&mut commands, &mut images);
+///     commands.insert_resource(DistanceField(image));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SmudRenderTarget {
+    /// Size of the image in pixels
+    pub size: UVec2,
+    /// Format of the image
+    pub format: TextureFormat,
+    /// Layers rendered into the image
+    pub layers: RenderLayers,
+    /// Color the image is cleared to before shapes are drawn
+    pub clear_color: Color,
+    /// Whether to render through a float texture, see [`Hdr`]
+    pub hdr: bool,
+    /// Order of the camera, see [`Camera::order`]
+    ///
+    /// Defaults to -1, so the image is ready before the main camera renders.
+    pub order: isize,
+}
+
+impl SmudRenderTarget {
+    /// Render into an image of the given size and format
+    pub fn new(size: UVec2, format: TextureFormat) -> Self {
+        Self {
+            size,
+            format,
+            layers: RenderLayers::default(),
+            clear_color: Color::NONE,
+            hdr: is_float_format(format),
+            order: -1,
+        }
+    }
+
+    /// Set the layers rendered into the image (builder pattern)
+    pub fn with_layers(mut self, layers: RenderLayers) -> Self {
+        self.layers = layers;
+        self
+    }
+
+    /// Set the color the image is cleared to (builder pattern)
+    pub fn with_clear_color(mut self, clear_color: impl Into<Color>) -> Self {
+        self.clear_color = clear_color.into();
+        self
+    }
+
+    /// Set whether to render through a float texture (builder pattern)
+    pub fn with_hdr(mut self, hdr: bool) -> Self {
+        self.hdr = hdr;
+        self
+    }
+
+    /// Set the order of the camera (builder pattern)
+    pub fn with_order(mut self, order: isize) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Create the image to render into
+    ///
+    /// The image can be rendered to, sampled, and copied from. Its data is cleared to zero.
+    pub fn image(&self) -> Image {
+        let size = Extent3d {
+            width: self.size.x,
+            height: self.size.y,
+            depth_or_array_layers: 1,
+        };
+        let pixel_size =
+            self.format
+                .block_copy_size(None)
+                .expect("Render target format should have a fixed pixel size") as usize;
+
+        let mut image = Image::new_fill(
+            size,
+            TextureDimension::D2,
+            &vec![0; pixel_size],
+            self.format,
+            RenderAssetUsages::default(),
+        );
+        image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_DST
+            | TextureUsages::COPY_SRC
+            | TextureUsages::RENDER_ATTACHMENT;
+        image
+    }
+
+    /// Components for a camera rendering into `image`
+    ///
+    /// Doesn't include [`Hdr`], insert it as well if [`Self::hdr`] is set.
+    pub fn camera(&self, image: Handle<Image>) -> impl Bundle {
+        (
+            Camera2d,
+            Camera {
+                order: self.order,
+                clear_color: ClearColorConfig::Custom(self.clear_color),
+                ..default()
+            },
+            RenderTarget::Image(image.into()),
+            self.layers.clone(),
+            // Shapes have their own anti-aliasing, and not all formats can be multisampled
+            Msaa::Off,
+            Tonemapping::None,
+            DebandDither::Disabled,
+        )
+    }
+
+    /// Add the image, and spawn a camera rendering into it
+    ///
+    /// Returns the handle of the image.
+    pub fn spawn(&self, commands: &mut Commands, images: &mut Assets<Image>) -> Handle<Image> {
+        let image = images.add(self.image());
+        let mut camera = commands.spawn(self.camera(image.clone()));
+        if self.hdr {
+            camera.insert(Hdr);
+        }
+        image
+    }
+}
+
+fn is_float_format(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::R16Float
+            | TextureFormat::Rg16Float
+            | TextureFormat::Rgba16Float
+            | TextureFormat::R32Float
+            | TextureFormat::Rg32Float
+            | TextureFormat::Rgba32Float
+            | TextureFormat::Rg11b10Ufloat
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_image_layout() {
+        let target = SmudRenderTarget::new(UVec2::new(64, 32), TextureFormat::R16Float);
+        let image = target.image();
+
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R16Float);
+        assert_eq!(image.texture_descriptor.dimension, TextureDimension::D2);
+        assert_eq!(
+            image.texture_descriptor.size,
+            Extent3d {
+                width: 64,
+                height: 32,
+                depth_or_array_layers: 1,
+            }
+        );
+        assert_eq!(image.texture_descriptor.mip_level_count, 1);
+        assert_eq!(image.texture_descriptor.sample_count, 1);
+        assert!(
+            image
+                .texture_descriptor
+                .usage
+                .contains(TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING)
+        );
+        // Two bytes per pixel, tightly packed
+        assert_eq!(image.data.as_ref().map(Vec::len), Some(64 * 32 * 2));
+        assert!(target.hdr);
+    }
+
+    #[test]
+    fn test_unorm_is_not_hdr() {
+        let target = SmudRenderTarget::new(UVec2::splat(16), TextureFormat::Rgba8Unorm);
+        assert!(!target.hdr);
+        assert_eq!(
+            target.image().data.as_ref().map(Vec::len),
+            Some(16 * 16 * 4)
+        );
+    }
+}
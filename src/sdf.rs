@@ -1,27 +1,39 @@
 //! Signed Distance Field (SDF) functions for 2D shapes.
 //!
-//! This module provides Rust implementations of common 2D SDF functions
-//! that correspond to the WGSL shaders used in bevy_smud for rendering.
-//! These functions can be used for CPU-side calculations like picking,
+//! This module provides Rust implementations of the functions in the `smud` WGSL module
+//! (`assets/smud.wgsl`). These functions can be used for CPU-side calculations like picking,
 //! collision detection, or other geometric computations.
-
-use bevy::math::{Vec2, Vec3, Vec4};
+//!
+//! Every function in `smud.wgsl` has a counterpart here with the same name and arguments, so
+//! `smud::sd_circle(p, 50.)` in a shader is `sdf::sd_circle(p, 50.)` in Rust. There are two
+//! exceptions:
+//!
+//! - WGSL names ending with a digit have a trailing underscore, which the Rust names don't have,
+//!   so `smud::sd_star_5_` is [`sd_star_5`] and `smud::dot2_` is [`dot2`].
+//! - Screen space derivatives (`dpdx`, `dpdy` and `fwidth`) aren't available on the CPU. The
+//!   functions using them take the derivatives as extra arguments at the end instead, see for
+//!   instance [`sd_fill_alpha_fwidth`].
+//!
+//! The Rust versions also return a distance instead of NaN for a few degenerate shapes, like
+//! segments with both ends at the same point.
+
+use bevy::{
+    asset::RenderAssetUsages,
+    image::Image,
+    math::{Rect, UVec2, Vec2, Vec3, Vec3Swizzles, Vec4},
+    render::render_resource::{Extent3d, TextureDimension, TextureFormat},
+};
 use std::f32::consts::PI;
 
 // Helpers, some of these have perfect implementations in rust std
 // but we keep these for clarity and to 1-to-1 match with the WGSL versions
 
-/// Helper function to calculate squared length of a 2D vector
-fn dot2(p: Vec2) -> f32 {
-    p.length_squared()
-}
-
 /// Helper function to clamp a value
 fn clamp(x: f32, min: f32, max: f32) -> f32 {
     x.clamp(min, max)
 }
 
-/// Helper function to get sign of a value
+/// Helper function to get sign of a value, 0 for 0 like in WGSL
 fn sign(x: f32) -> f32 {
     if x > 0.0 {
         1.0
@@ -32,40 +44,86 @@ fn sign(x: f32) -> f32 {
     }
 }
 
-/// Helper function for modulo operation
-fn modulo(x: f32, y: f32) -> f32 {
+/// Helper function to pack a distance and its gradient, like the `sdg_*` functions return
+fn dg(d: f32, g: Vec2) -> Vec3 {
+    Vec3::new(d, g.x, g.y)
+}
+
+/// Helper function for linear interpolation, like `mix` in WGSL
+fn mix(x: f32, y: f32, a: f32) -> f32 {
+    x * (1.0 - a) + y * a
+}
+
+/// Same as `smoothstep` in WGSL, including reversed edges
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge0 == edge1 {
+        // Not defined in WGSL, pick the limit
+        return if (x - edge0) * (edge1 - edge0).signum() >= 0.0 {
+            1.0
+        } else {
+            0.0
+        };
+    }
+    let t = clamp((x - edge0) / (edge1 - edge0), 0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Same as `round` in WGSL, which rounds half to even
+fn round(v: Vec2) -> Vec2 {
+    Vec2::new(v.x.round_ties_even(), v.y.round_ties_even())
+}
+
+/// Dot product with the y component negated
+pub fn ndot(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.x - a.y * b.y
+}
+
+/// Squared length of a 2D vector
+pub fn dot2(a: Vec2) -> f32 {
+    a.dot(a)
+}
+
+/// Modulo with the sign of `y`, like `mod` in GLSL
+pub fn modulo(x: f32, y: f32) -> f32 {
     x - y * (x / y).floor()
 }
 
 // Distance functions
 
 /// Signed distance to a circle
-pub fn circle(p: Vec2, radius: f32) -> f32 {
-    p.length() - radius
+pub fn sd_circle(p: Vec2, r: f32) -> f32 {
+    p.length() - r
 }
 
 /// Signed distance to an annulus (ring)
-pub fn annulus(p: Vec2, outer_radius: f32, inner_radius: f32) -> f32 {
+pub fn sd_annulus(p: Vec2, outer_radius: f32, inner_radius: f32) -> f32 {
     let middle_radius = (outer_radius + inner_radius) * 0.5;
     let thickness = (outer_radius - inner_radius) * 0.5;
-    circle(p, middle_radius).abs() - thickness
+    sd_circle(p, middle_radius).abs() - thickness
 }
 
-/// Signed distance to a capsule (pill shape)
-pub fn capsule(p: Vec2, radius: f32, half_length: f32) -> f32 {
-    // Special case: when half_length is 0, the capsule is just a circle
-    if half_length == 0.0 {
-        return circle(p, radius);
-    }
+/// Signed distance to a vertical capsule (pill shape)
+///
+/// When `half_length` is 0, the capsule is a circle.
+pub fn sd_capsule(p: Vec2, radius: f32, half_length: f32) -> f32 {
     let a = Vec2::new(0.0, -half_length);
     let b = Vec2::new(0.0, half_length);
-    segment(p, a, b) - radius
+    sd_segment(p, a, b) - radius
 }
 
-/// Signed distance to a rounded box
-pub fn rounded_box(p: Vec2, b: Vec2, r: f32) -> f32 {
-    let q = p.abs() - b;
-    q.max(Vec2::ZERO).length() + q.x.max(q.y).min(0.0) - r
+/// Signed distance to a box with rounded corners
+///
+/// `b` is the half size of the box, and `r` the radius of each corner: top right, bottom right,
+/// top left and bottom left.
+pub fn sd_rounded_box(p: Vec2, b: Vec2, r: Vec4) -> f32 {
+    let r_2 = if p.x > 0.0 {
+        Vec2::new(r.x, r.y)
+    } else {
+        Vec2::new(r.z, r.w)
+    };
+    let r_2 = if p.y > 0.0 { r_2.x } else { r_2.y };
+    let q = p.abs() - b + r_2;
+    q.x.max(q.y).min(0.0) + q.max(Vec2::ZERO).length() - r_2
 }
 
 /// Signed distance to a box
@@ -74,17 +132,20 @@ pub fn sd_box(p: Vec2, b: Vec2) -> f32 {
     d.max(Vec2::ZERO).length() + d.x.max(d.y).min(0.0)
 }
 
-/// Signed distance to an oriented box
-pub fn oriented_box(p: Vec2, a: Vec2, b: Vec2, th: f32) -> f32 {
+/// Signed distance to a box from `a` to `b`, `th` thick
+pub fn sd_oriented_box(p: Vec2, a: Vec2, b: Vec2, th: f32) -> f32 {
     let l = (b - a).length();
     let d = (b - a) / l;
     let q = p - (a + b) * 0.5;
-    let q_rot = Vec2::new(q.dot(d), -q.dot(d.perp()));
-    rounded_box(q_rot, Vec2::new(l * 0.5, th), 0.0)
+    let q = Vec2::new(d.x * q.x + d.y * q.y, -d.y * q.x + d.x * q.y);
+    let q = q.abs() - Vec2::new(l, th) * 0.5;
+    q.max(Vec2::ZERO).length() + q.x.max(q.y).min(0.0)
 }
 
-/// Signed distance to a line segment
-pub fn segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
+/// Distance to a line segment
+///
+/// When `a == b`, this is the distance to that point.
+pub fn sd_segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
     let pa = p - a;
     let ba = b - a;
     let ba_len_sq = ba.dot(ba);
@@ -99,185 +160,171 @@ pub fn segment(p: Vec2, a: Vec2, b: Vec2) -> f32 {
 }
 
 /// Signed distance to a rhombus
-pub fn rhombus(p: Vec2, b: Vec2) -> f32 {
-    let p_abs = p.abs();
-    let h = clamp((-2.0 * p_abs.dot(b) + b.dot(b)) / b.dot(b), -1.0, 1.0);
-    let d = (p_abs - 0.5 * b * Vec2::new(1.0 - h, 1.0 + h)).length();
-    d * sign(p_abs.x * b.y + p_abs.y * b.x - b.x * b.y)
+pub fn sd_rhombus(p: Vec2, b: Vec2) -> f32 {
+    let p_2 = p.abs();
+    let h = clamp(ndot(b - 2.0 * p_2, b) / b.dot(b), -1.0, 1.0);
+    let d = (p_2 - 0.5 * b * Vec2::new(1.0 - h, 1.0 + h)).length();
+    d * sign(p_2.x * b.y + p_2.y * b.x - b.x * b.y)
 }
 
 /// Signed distance to a trapezoid
-pub fn trapezoid(p: Vec2, r1: f32, r2: f32, he: f32) -> f32 {
+pub fn sd_trapezoid(p: Vec2, r1: f32, r2: f32, he: f32) -> f32 {
+    let mut p_2 = p;
     let k1 = Vec2::new(r2, he);
     let k2 = Vec2::new(r2 - r1, 2.0 * he);
-    let mut p = p;
-    p.x = p.x.abs();
-    let ca = Vec2::new(p.x - (if p.y < 0.0 { r1 } else { r2 }), p.y.abs() - he);
-    let cb = Vec2::new(p.x - r2, p.y - he);
-    let cb_clamp = cb - k1 * clamp(cb.dot(k1) / k1.dot(k1), 0.0, 1.0);
-    let s = if cb.x * k2.y - cb.y * k2.x < 0.0 {
-        -1.0
-    } else {
-        1.0
-    };
-    s * (dot2(ca).min(dot2(cb_clamp))).sqrt()
+    p_2.x = p_2.x.abs();
+    let r = if p_2.y < 0.0 { r1 } else { r2 };
+    let ca = Vec2::new(p_2.x - p_2.x.min(r), p_2.y.abs() - he);
+    let cb = p_2 - k1 + k2 * clamp((k1 - p_2).dot(k2) / dot2(k2), 0.0, 1.0);
+    let s = if cb.x < 0.0 && ca.y < 0.0 { -1.0 } else { 1.0 };
+    s * dot2(ca).min(dot2(cb)).sqrt()
 }
 
 /// Signed distance to a parallelogram
-pub fn parallelogram(p: Vec2, wi: f32, he: f32, sk: f32) -> f32 {
+pub fn sd_parallelogram(p: Vec2, wi: f32, he: f32, sk: f32) -> f32 {
     let e = Vec2::new(sk, he);
-    let mut p = p;
-    p = Vec2::new(if p.y < 0.0 { -p.x } else { p.x }, p.y.abs());
-    let w = p - e;
-    let w_e = w - e * clamp(w.dot(e) / e.dot(e), 0.0, 1.0);
-    let d = Vec2::new(w_e.x - wi * clamp(w_e.x / wi, 0.0, 1.0), w_e.y);
-    let s = if p.x * he - p.y * wi > 0.0 { -1.0 } else { 1.0 };
-    s * (d.max(Vec2::ZERO).length() + d.x.max(d.y).min(0.0))
+    let mut p_2 = if p.y < 0.0 { -p } else { p };
+    let mut w = p_2 - e;
+    w.x -= clamp(w.x, -wi, wi);
+    let mut d = Vec2::new(w.dot(w), -w.y);
+    let s = p_2.x * e.y - p_2.y * e.x;
+    p_2 = if s < 0.0 { -p_2 } else { p_2 };
+    let mut v = p_2 - Vec2::new(wi, 0.0);
+    v -= e * clamp(v.dot(e) / e.dot(e), -1.0, 1.0);
+    d = d.min(Vec2::new(v.dot(v), wi * he - s.abs()));
+    d.x.sqrt() * sign(-d.y)
 }
 
 /// Signed distance to an equilateral triangle
-pub fn equilateral_triangle(p: Vec2, r: f32) -> f32 {
+pub fn sd_equilateral_triangle(p: Vec2, r: f32) -> f32 {
+    let mut p_2 = p;
     let k = (3.0_f32).sqrt();
-    let mut p = p;
-    p.x = p.x.abs() - r;
-    p.y += r / k;
-    if p.x + k * p.y > 0.0 {
-        p = Vec2::new((p.x - k * p.y) * 0.5, (-k * p.x - p.y) * 0.5);
+    p_2.x = p_2.x.abs() - r;
+    p_2.y += r / k;
+    if p_2.x + k * p_2.y > 0.0 {
+        p_2 = Vec2::new(p_2.x - k * p_2.y, -k * p_2.x - p_2.y) / 2.0;
     }
-    p.x -= clamp(p.x, -2.0 * r, 0.0);
-    -p.length() * sign(p.y)
+    p_2.x -= clamp(p_2.x, -2.0 * r, 0.0);
+    -p_2.length() * sign(p_2.y)
 }
 
 /// Signed distance to an isosceles triangle
-pub fn triangle_isosceles(p: Vec2, q: Vec2) -> f32 {
-    let mut p = p;
-    p.x = p.x.abs();
-    let a = p - q;
-    let b = Vec2::new(p.x - q.x, p.y + q.y);
-    let k = clamp(a.dot(q) / q.dot(q), 0.0, 1.0);
-    if k < 0.0 {
-        dot2(a)
-    } else if k > 1.0 {
-        dot2(b)
-    } else {
-        (a - q * k).length_squared()
-    }
-    .sqrt()
-        * sign(a.x * q.y - a.y * q.x)
+pub fn sd_triangle_isosceles(p: Vec2, q: Vec2) -> f32 {
+    let mut p_2 = p;
+    p_2.x = p_2.x.abs();
+    let a = p_2 - q * clamp(p_2.dot(q) / q.dot(q), 0.0, 1.0);
+    let b = p_2 - q * Vec2::new(clamp(p_2.x / q.x, 0.0, 1.0), 1.0);
+    let s = -sign(q.y);
+    let d = Vec2::new(a.dot(a), s * (p_2.x * q.y - p_2.y * q.x))
+        .min(Vec2::new(b.dot(b), s * (p_2.y - q.y)));
+    -d.x.sqrt() * sign(d.y)
 }
 
 /// Signed distance to a triangle
-pub fn triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> f32 {
-    let e0 = b - a;
-    let e1 = c - b;
-    let e2 = a - c;
-    let v0 = p - a;
-    let v1 = p - b;
-    let v2 = p - c;
+pub fn sd_triangle(p: Vec2, p0: Vec2, p1: Vec2, p2: Vec2) -> f32 {
+    let e0 = p1 - p0;
+    let e1 = p2 - p1;
+    let e2 = p0 - p2;
+
+    let v0 = p - p0;
+    let v1 = p - p1;
+    let v2 = p - p2;
+
     let pq0 = v0 - e0 * clamp(v0.dot(e0) / e0.dot(e0), 0.0, 1.0);
     let pq1 = v1 - e1 * clamp(v1.dot(e1) / e1.dot(e1), 0.0, 1.0);
     let pq2 = v2 - e2 * clamp(v2.dot(e2) / e2.dot(e2), 0.0, 1.0);
+
     let s = sign(e0.x * e2.y - e0.y * e2.x);
-    let d = [
-        (dot2(pq0), s * (v0.x * e0.y - v0.y * e0.x)),
-        (dot2(pq1), s * (v1.x * e1.y - v1.y * e1.x)),
-        (dot2(pq2), s * (v2.x * e2.y - v2.y * e2.x)),
-    ];
-    -d.iter()
-        .map(|(dist, _)| *dist)
-        .fold(f32::INFINITY, f32::min)
-        .sqrt()
-        * if d.iter().all(|(_, cross)| *cross > 0.0) {
-            1.0
-        } else {
-            -1.0
-        }
+    let d = Vec2::new(pq0.dot(pq0), s * (v0.x * e0.y - v0.y * e0.x))
+        .min(Vec2::new(pq1.dot(pq1), s * (v1.x * e1.y - v1.y * e1.x)))
+        .min(Vec2::new(pq2.dot(pq2), s * (v2.x * e2.y - v2.y * e2.x)));
+    -d.x.sqrt() * sign(d.y)
 }
 
 /// Signed distance to an uneven capsule
-pub fn uneven_capsule(p: Vec2, r1: f32, r2: f32, h: f32) -> f32 {
-    let mut p = p;
-    p.x = p.x.abs();
+pub fn sd_uneven_capsule(p: Vec2, r1: f32, r2: f32, h: f32) -> f32 {
+    let mut p_2 = p;
+    p_2.x = p_2.x.abs();
     let b = (r1 - r2) / h;
     let a = (1.0 - b * b).sqrt();
-    let k = p.dot(Vec2::new(-b, a));
+    let k = p_2.dot(Vec2::new(-b, a));
     if k < 0.0 {
-        p.length() - r1
-    } else if k > a * h {
-        (p - Vec2::new(0.0, h)).length() - r2
-    } else {
-        p.dot(Vec2::new(a, b)) - r1
+        return p_2.length() - r1;
+    }
+    if k > a * h {
+        return (p_2 - Vec2::new(0.0, h)).length() - r2;
     }
+    p_2.dot(Vec2::new(a, b)) - r1
 }
 
 /// Signed distance to a pentagon
-pub fn pentagon(p: Vec2, r: f32) -> f32 {
+pub fn sd_pentagon(p: Vec2, r: f32) -> f32 {
     let k = Vec3::new(0.809_017, 0.587_785_24, 0.726_542_53);
-    let mut p = p;
-    p.x = p.x.abs();
-    p = p - 2.0 * f32::min(Vec2::new(-k.x, k.y).dot(p), 0.0) * Vec2::new(-k.x, k.y);
-    p = p - 2.0 * f32::min(Vec2::new(k.x, k.y).dot(p), 0.0) * Vec2::new(k.x, k.y);
-    p = Vec2::new(p.x - clamp(p.x, -r * k.z, r * k.z), p.y - r);
-    p.length() * sign(p.y)
+    let mut p_2 = p;
+    p_2.x = p_2.x.abs();
+    p_2 -= 2.0 * Vec2::new(-k.x, k.y).dot(p_2).min(0.0) * Vec2::new(-k.x, k.y);
+    p_2 -= 2.0 * Vec2::new(k.x, k.y).dot(p_2).min(0.0) * Vec2::new(k.x, k.y);
+    p_2 -= Vec2::new(clamp(p_2.x, -r * k.z, r * k.z), r);
+    p_2.length() * sign(p_2.y)
 }
 
 /// Signed distance to a hexagon
-pub fn hexagon(p: Vec2, r: f32) -> f32 {
+pub fn sd_hexagon(p_in: Vec2, r: f32) -> f32 {
     let k = Vec3::new(-0.866_025_4, 0.5, 0.577_350_26);
-    let mut p = p.abs();
-    p = p - 2.0 * f32::min(Vec2::new(k.x, k.y).dot(p), 0.0) * Vec2::new(k.x, k.y);
-    p = Vec2::new(p.x - clamp(p.x, -k.z * r, k.z * r), p.y - r);
+    let mut p = p_in.abs();
+    p -= 2.0 * k.truncate().dot(p).min(0.0) * k.truncate();
+    p -= Vec2::new(clamp(p.x, -k.z * r, k.z * r), r);
     p.length() * sign(p.y)
 }
 
 /// Signed distance to an octagon
-pub fn octagon(p: Vec2, r: f32) -> f32 {
+pub fn sd_octagon(p: Vec2, r: f32) -> f32 {
     let k = Vec3::new(-0.923_879_5, 0.382_683_43, 0.414_213_57);
-    let mut p = p.abs();
-    p = p - 2.0 * f32::min(Vec2::new(k.x, k.y).dot(p), 0.0) * Vec2::new(k.x, k.y);
-    p = p - 2.0 * f32::min(Vec2::new(-k.x, k.y).dot(p), 0.0) * Vec2::new(-k.x, k.y);
-    p = Vec2::new(p.x - clamp(p.x, -k.z * r, k.z * r), p.y - r);
-    p.length() * sign(p.y)
+    let mut p_2 = p.abs();
+    p_2 -= 2.0 * Vec2::new(k.x, k.y).dot(p_2).min(0.0) * Vec2::new(k.x, k.y);
+    p_2 -= 2.0 * Vec2::new(-k.x, k.y).dot(p_2).min(0.0) * Vec2::new(-k.x, k.y);
+    p_2 -= Vec2::new(clamp(p_2.x, -k.z * r, k.z * r), r);
+    p_2.length() * sign(p_2.y)
 }
 
 /// Signed distance to a hexagram (6-pointed star)
-pub fn hexagram(p: Vec2, r: f32) -> f32 {
+pub fn sd_hexagram(p_in: Vec2, r: f32) -> f32 {
     let k = Vec4::new(-0.5, 0.866_025_4, 0.577_350_26, 1.732_050_8);
-    let mut p = p.abs();
-    p = p - 2.0 * f32::min(Vec2::new(k.x, k.y).dot(p), 0.0) * Vec2::new(k.x, k.y);
-    p = p - 2.0 * f32::min(Vec2::new(k.y, k.x).dot(p), 0.0) * Vec2::new(k.y, k.x);
-    p = Vec2::new(p.x - clamp(p.x, r * k.z, r * k.w), p.y - r);
+    let mut p = p_in.abs();
+    p -= 2.0 * Vec2::new(k.x, k.y).dot(p).min(0.0) * Vec2::new(k.x, k.y);
+    p -= 2.0 * Vec2::new(k.y, k.x).dot(p).min(0.0) * Vec2::new(k.y, k.x);
+    p -= Vec2::new(clamp(p.x, r * k.z, r * k.w), r);
     p.length() * sign(p.y)
 }
 
-/// Signed distance to a 5-pointed star
-pub fn star_5(p: Vec2, r: f32, rf: f32) -> f32 {
+/// Signed distance to a 5-pointed star, `smud::sd_star_5_` in WGSL
+pub fn sd_star_5(p_in: Vec2, r: f32, rf: f32) -> f32 {
     let k1 = Vec2::new(0.809_017, -0.587_785_24);
     let k2 = Vec2::new(-k1.x, k1.y);
-    let mut p = p;
+    let mut p = p_in;
     p.x = p.x.abs();
-    p = p - 2.0 * f32::max(k1.dot(p), 0.0) * k1;
-    p = p - 2.0 * f32::max(k2.dot(p), 0.0) * k2;
+    p -= 2.0 * k1.dot(p).max(0.0) * k1;
+    p -= 2.0 * k2.dot(p).max(0.0) * k2;
     p.x = p.x.abs();
     p.y -= r;
-    let ba = Vec2::new(rf * (-k1.y), rf * k1.x - 1.0);
+    let ba = rf * Vec2::new(-k1.y, k1.x) - Vec2::new(0.0, 1.0);
     let h = clamp(p.dot(ba) / ba.dot(ba), 0.0, r);
     (p - ba * h).length() * sign(p.y * ba.x - p.x * ba.y)
 }
 
-/// Signed distance to a regular polygon
-pub fn regular_polygon(p: Vec2, radius: f32, sides: i32) -> f32 {
+/// Signed distance to a regular polygon with a vertex at the top
+pub fn sd_regular_polygon(p: Vec2, radius: f32, sides: i32) -> f32 {
     // Get polar angle
     let mut angle = p.y.atan2(p.x);
     // Add PI/2 to match Bevy's convention (vertex at top instead of right)
-    angle += std::f32::consts::FRAC_PI_2;
+    angle += PI / 2.0;
     // Make angle to range [0, 2*PI]
     if angle < 0.0 {
-        angle += std::f32::consts::TAU;
+        angle += PI * 2.0;
     }
 
     // Get each piece angle
-    let delta = std::f32::consts::TAU / sides as f32;
+    let delta = 2.0 * PI / sides as f32;
     // How many pieces?
     let area_number = (angle / delta).floor();
 
@@ -305,7 +352,7 @@ pub fn regular_polygon(p: Vec2, radius: f32, sides: i32) -> f32 {
     let vector2 = p - point_a_prime;
     let axis2 = point_a_prime;
     let a2 = (axis2.normalize().dot(vector2.normalize())).acos();
-    if (std::f32::consts::TAU - a2) < (delta / 2.0) {
+    if (PI * 2.0 - a2) < (delta / 2.0) {
         return vector2.length();
     }
 
@@ -314,33 +361,33 @@ pub fn regular_polygon(p: Vec2, radius: f32, sides: i32) -> f32 {
     p.length() * theta.cos() - point_d.length()
 }
 
-/// Signed distance to a star with n points
-pub fn star(p: Vec2, r: f32, n: i32, m: f32) -> f32 {
+/// Signed distance to a star with `n` points, `m` is between 2 and `n`
+pub fn sd_star(p_in: Vec2, r: f32, n: i32, m: f32) -> f32 {
     let an = PI / n as f32;
     let en = PI / m;
     let acs = Vec2::new(an.cos(), an.sin());
     let ecs = Vec2::new(en.cos(), en.sin());
 
-    let bn = modulo(p.y.atan2(p.x), 2.0 * an) - an;
-    let mut p_star = Vec2::new(p.length() * bn.cos(), p.length() * bn.sin().abs());
-    p_star -= r * acs;
-    p_star = p_star + ecs * clamp(-p_star.dot(ecs), 0.0, r * acs.y / ecs.y);
-    p_star.length() * sign(p_star.x)
+    let bn = modulo(p_in.x.atan2(p_in.y), 2.0 * an) - an;
+    let mut p = p_in.length() * Vec2::new(bn.cos(), bn.sin().abs());
+    p -= r * acs;
+    p += ecs * clamp(-p.dot(ecs), 0.0, r * acs.y / ecs.y);
+    p.length() * sign(p.x)
 }
 
-/// Signed distance to a pie slice
-pub fn pie(p: Vec2, c: Vec2, r: f32) -> f32 {
-    let mut p = p;
+/// Signed distance to a pie slice, `c` is the sin/cos of the aperture
+pub fn sd_pie(p_in: Vec2, c: Vec2, r: f32) -> f32 {
+    let mut p = p_in;
     p.x = p.x.abs();
     let l = p.length() - r;
     let m = (p - c * clamp(p.dot(c), 0.0, r)).length();
-    f32::max(l, m * sign(c.y * p.x - c.x * p.y))
+    l.max(m * sign(c.y * p.x - c.x * p.y))
 }
 
 /// Signed distance to a cut disk
-pub fn cut_disk(p: Vec2, r: f32, h: f32) -> f32 {
+pub fn sd_cut_disk(p_in: Vec2, r: f32, h: f32) -> f32 {
     let w = (r * r - h * h).sqrt();
-    let mut p = p;
+    let mut p = p_in;
     p.x = p.x.abs();
     let s = f32::max(
         (h - r) * p.x * p.x + w * w * (h + r - 2.0 * p.y),
@@ -355,9 +402,9 @@ pub fn cut_disk(p: Vec2, r: f32, h: f32) -> f32 {
     }
 }
 
-/// Signed distance to an arc
-pub fn arc(p: Vec2, sc: Vec2, ra: f32, rb: f32) -> f32 {
-    let mut p = p;
+/// Signed distance to an arc, `sc` is the sin/cos of the arc's aperture
+pub fn sd_arc(p_in: Vec2, sc: Vec2, ra: f32, rb: f32) -> f32 {
+    let mut p = p_in;
     p.x = p.x.abs();
     (if sc.y * p.x > sc.x * p.y {
         (p - sc * ra).length()
@@ -366,99 +413,157 @@ pub fn arc(p: Vec2, sc: Vec2, ra: f32, rb: f32) -> f32 {
     }) - rb
 }
 
+/// Signed distance to an arc rotated by the sin/cos `sc_orientation`
+pub fn sd_arc_oriented(
+    p_in: Vec2,
+    sc_orientation: Vec2,
+    sc_aperture: Vec2,
+    ra: f32,
+    rb: f32,
+) -> f32 {
+    let mut p = Vec2::new(
+        p_in.x * sc_orientation.x + p_in.y * sc_orientation.y,
+        -p_in.x * sc_orientation.y + p_in.y * sc_orientation.x,
+    );
+    p.x = p.x.abs();
+    let k = if sc_aperture.y * p.x > sc_aperture.x * p.y {
+        p.dot(sc_aperture)
+    } else {
+        p.length()
+    };
+    (p.dot(p) + ra * ra - 2.0 * ra * k).sqrt() - rb
+}
+
 /// Signed distance to a horseshoe
-pub fn horseshoe(p: Vec2, c: Vec2, r: f32, w: Vec2) -> f32 {
-    let mut p = p;
+pub fn sd_horseshoe(p_in: Vec2, c: Vec2, r: f32, w: Vec2) -> f32 {
+    let mut p = p_in;
     p.x = p.x.abs();
     let l = p.length();
-    let p_rot = Vec2::new((-c.x) * p.x + c.y * p.y, c.y * p.x + c.x * p.y);
-    let p_new = Vec2::new(
-        if p_rot.y > 0.0 || p_rot.x > 0.0 {
-            p_rot.x
+    p = Vec2::new(-c.x * p.x + c.y * p.y, c.y * p.x + c.x * p.y);
+    p = Vec2::new(
+        if p.y > 0.0 || p.x > 0.0 {
+            p.x
         } else {
             l * sign(-c.x)
         },
-        if p_rot.x > 0.0 { p_rot.y } else { l },
+        if p.x > 0.0 { p.y } else { l },
     );
-    let p_final = Vec2::new(p_new.x, (p_new.y - r).abs() - w.y);
-    let p_clamped = Vec2::new(p_final.x - w.x, p_final.y);
-    p_clamped.max(Vec2::ZERO).length() + f32::min(0.0, f32::max(p_clamped.x, p_clamped.y))
+    p = Vec2::new(p.x, (p.y - r).abs()) - w;
+    p.max(Vec2::ZERO).length() + f32::min(0.0, p.x.max(p.y))
 }
 
 /// Signed distance to a rounded cross
-pub fn rounded_cross(p: Vec2, h: f32) -> f32 {
+pub fn sd_rounded_cross(p_in: Vec2, h: f32) -> f32 {
     let k = 0.5 * (h + 1.0 / h);
-    let p_abs = p.abs();
-    if p_abs.x < 1.0 && p_abs.y < p_abs.x * (k - h) + h {
-        k - (dot2(p_abs - Vec2::new(1.0, k))).sqrt()
+    let p = p_in.abs();
+    if p.x < 1.0 && p.y < p.x * (k - h) + h {
+        k - dot2(p - Vec2::new(1.0, k)).sqrt()
     } else {
-        (f32::min(
-            dot2(p_abs - Vec2::new(0.0, h)),
-            dot2(p_abs - Vec2::new(1.0, 0.0)),
-        ))
-        .sqrt()
+        dot2(p - Vec2::new(0.0, h))
+            .min(dot2(p - Vec2::new(1.0, 0.0)))
+            .sqrt()
     }
 }
 
 /// Signed distance to an egg
-pub fn egg(p: Vec2, ra: f32, rb: f32) -> f32 {
-    let mut p = p;
+pub fn sd_egg(p_in: Vec2, ra: f32, rb: f32) -> f32 {
+    let mut p = p_in;
     let k = (3.0_f32).sqrt();
     p.x = p.x.abs();
     let r = ra - rb;
     (if p.y < 0.0 {
         p.length() - r
     } else if k * (p.x + r) < p.y {
-        (p - Vec2::new(0.0, k * r)).length()
+        Vec2::new(p.x, p.y - k * r).length()
     } else {
-        (p - Vec2::new(-r, 0.0)).length() - 2.0 * r
+        Vec2::new(p.x + r, p.y).length() - 2.0 * r
     }) - rb
 }
 
-/// Signed distance to a heart
-pub fn heart(p: Vec2) -> f32 {
-    let mut p = p;
+/// Signed distance to a heart about one unit tall, with its tip at the origin
+pub fn sd_heart(p_in: Vec2) -> f32 {
+    let mut p = p_in;
     p.x = p.x.abs();
 
     if p.y + p.x > 1.0 {
-        return (dot2(p - Vec2::new(0.25, 0.75))).sqrt() - (2.0_f32).sqrt() / 4.0;
+        return dot2(p - Vec2::new(0.25, 0.75)).sqrt() - (2.0_f32).sqrt() / 4.0;
     }
 
-    (f32::min(
-        dot2(p - Vec2::new(0.0, 1.0)),
-        dot2(Vec2::new(
-            p.x - 0.5 * f32::max(p.x + p.y, 0.0),
-            p.y - 0.5 * f32::max(p.x + p.y, 0.0),
-        )),
-    ))
-    .sqrt()
+    dot2(p - Vec2::new(0.0, 1.0))
+        .min(dot2(p - 0.5 * (p.x + p.y).max(0.0)))
+        .sqrt()
         * sign(p.x - p.y)
 }
 
 /// Signed distance to a cross
-pub fn cross(p: Vec2, b: Vec2, r: f32) -> f32 {
-    let mut p = p.abs();
+pub fn sd_cross(p_in: Vec2, b: Vec2, r: f32) -> f32 {
+    let mut p = p_in.abs();
     p = if p.y > p.x { Vec2::new(p.y, p.x) } else { p };
     let q = p - b;
-    let k = f32::max(q.y, q.x);
+    let k = q.y.max(q.x);
     let w = if k > 0.0 { q } else { Vec2::new(b.y - p.x, -k) };
     sign(k) * w.max(Vec2::ZERO).length() + r
 }
 
 /// Signed distance to a rounded X
-pub fn rounded_x(p: Vec2, w: f32, r: f32) -> f32 {
-    let p_abs = p.abs();
-    (Vec2::new(
-        p_abs.x - f32::min(p_abs.x + p_abs.y, w) * 0.5,
-        p_abs.y - f32::min(p_abs.x + p_abs.y, w) * 0.5,
-    ))
-    .length()
-        - r
+pub fn sd_rounded_x(p_in: Vec2, w: f32, r: f32) -> f32 {
+    let p = p_in.abs();
+    (p - (p.x + p.y).min(w) * 0.5).length() - r
+}
+
+/// One edge of [`sd_polygon`], from `previous` to `vertex`
+///
+/// `ds` is the squared distance and the sign so far. WGSL functions can't take arrays of any
+/// length, so `shape_params::sd_polygon` is built from this.
+pub fn sd_polygon_edge(p: Vec2, previous: Vec2, vertex: Vec2, ds: Vec2) -> Vec2 {
+    let e = previous - vertex;
+    let w = p - vertex;
+    let ee = e.dot(e);
+    let h = if ee > 0.0 {
+        clamp(w.dot(e) / ee, 0.0, 1.0)
+    } else {
+        0.0
+    };
+    let b = w - e * h;
+    let c1 = p.y >= vertex.y;
+    let c2 = p.y < previous.y;
+    let c3 = e.x * w.y > e.y * w.x;
+    let crosses = (c1 && c2 && c3) || (!c1 && !c2 && !c3);
+    Vec2::new(ds.x.min(b.dot(b)), if crosses { -ds.y } else { ds.y })
+}
+
+/// Signed distance to a polygon, like `shape_params::sd_polygon` in WGSL
+///
+/// The polygon doesn't need to be convex, and is closed between the last and first vertex.
+/// Self-intersecting polygons use the even-odd rule.
+pub fn sd_polygon(p: Vec2, vertices: &[Vec2]) -> f32 {
+    let (Some(&first), Some(&last)) = (vertices.first(), vertices.last()) else {
+        return 1e10;
+    };
+    let mut ds = Vec2::new(dot2(p - first), 1.0);
+    let mut previous = last;
+    for &vertex in vertices {
+        ds = sd_polygon_edge(p, previous, vertex, ds);
+        previous = vertex;
+    }
+    ds.y * ds.x.sqrt()
+}
+
+/// Distance to the open line through the vertices, like `shape_params::sd_polyline` in WGSL
+pub fn sd_polyline(p: Vec2, vertices: &[Vec2]) -> f32 {
+    let Some(&first) = vertices.first() else {
+        return 1e10;
+    };
+    vertices.windows(2).fold((p - first).length(), |d, edge| {
+        d.min(sd_segment(p, edge[0], edge[1]))
+    })
 }
 
 /// Signed distance to an ellipse
-pub fn ellipse(p: Vec2, a: f32, b: f32) -> f32 {
-    let mut p = p.abs();
+///
+/// Unstable when close to a circle or very eccentric.
+pub fn sd_ellipse(p_in: Vec2, a: f32, b: f32) -> f32 {
+    let mut p = p_in.abs();
     let mut ab = Vec2::new(a, b);
     if p.x > p.y {
         p = Vec2::new(p.y, p.x);
@@ -490,13 +595,13 @@ pub fn ellipse(p: Vec2, a: f32, b: f32) -> f32 {
         let rm = (rx * rx + ry * ry).sqrt();
         (ry / (rm - rx).sqrt() + 2.0 * g / rm - m) / 2.0
     };
-    let r = Vec2::new(ab.x * co, ab.y * (1.0_f32 - co * co).sqrt());
+    let r = ab * Vec2::new(co, (1.0 - co * co).sqrt());
     (r - p).length() * sign(p.y - r.y)
 }
 
 /// Signed distance to a parabola
-pub fn parabola(p: Vec2, k: f32) -> f32 {
-    let mut pos = p;
+pub fn sd_parabola(p_in: Vec2, k: f32) -> f32 {
+    let mut pos = p_in;
     pos.x = pos.x.abs();
     let ik = 1.0 / k;
     let p = ik * (pos.y - 0.5 * ik) / 3.0;
@@ -506,14 +611,14 @@ pub fn parabola(p: Vec2, k: f32) -> f32 {
     let x = if h > 0.0 {
         (q + r).powf(1.0 / 3.0) - (q - r).abs().powf(1.0 / 3.0) * sign(r - q)
     } else {
-        2.0 * ((r / q).atan() / 3.0).cos() * p.sqrt()
+        2.0 * (r.atan2(q) / 3.0).cos() * p.sqrt()
     };
     (pos - Vec2::new(x, k * x * x)).length() * sign(pos.x - x)
 }
 
 /// Signed distance to a parabola segment
-pub fn parabola_segment(p: Vec2, wi: f32, he: f32) -> f32 {
-    let mut pos = p;
+pub fn sd_parabola_segment(p_in: Vec2, wi: f32, he: f32) -> f32 {
+    let mut pos = p_in;
     pos.x = pos.x.abs();
     let ik = wi * wi / he;
     let p = ik * (he - pos.y - 0.5 * ik) / 3.0;
@@ -529,13 +634,44 @@ pub fn parabola_segment(p: Vec2, wi: f32, he: f32) -> f32 {
     (pos - Vec2::new(x, he - x * x / ik)).length() * sign(ik * (pos.y - he) + pos.x * pos.x)
 }
 
+/// Distance to a quadratic bezier curve from `a` to `c`, with control point `b`
+pub fn sd_bezier(pos: Vec2, a: Vec2, b: Vec2, c: Vec2) -> f32 {
+    // Points are upper case in WGSL, reuse the lower case names like it does
+    let (a, b, c, d) = (b - a, a - 2.0 * b + c, (b - a) * 2.0, a - pos);
+    let kk = 1.0 / b.dot(b);
+    let kx = kk * a.dot(b);
+    let ky = kk * (2.0 * a.dot(a) + d.dot(b)) / 3.0;
+    let kz = kk * d.dot(a);
+    let p = ky - kx * kx;
+    let p3 = p * p * p;
+    let q = kx * (2.0 * kx * kx - 3.0 * ky) + kz;
+    let mut h = q * q + 4.0 * p3;
+    let res = if h >= 0.0 {
+        h = h.sqrt();
+        let x = (Vec2::new(h, -h) - q) / 2.0;
+        let uv = Vec2::new(
+            sign(x.x) * x.x.abs().powf(1.0 / 3.0),
+            sign(x.y) * x.y.abs().powf(1.0 / 3.0),
+        );
+        let t = clamp(uv.x + uv.y - kx, 0.0, 1.0);
+        dot2(d + (c + b * t) * t)
+    } else {
+        let z = (-p).sqrt();
+        let v = (q / (p * z * 2.0)).acos() / 3.0;
+        let m = v.cos();
+        let n = v.sin() * 1.732_050_8;
+        let u = Vec3::new(m + m, -n - m, n - m) * z - Vec3::splat(kx);
+        let t = u.clamp(Vec3::ZERO, Vec3::ONE);
+        // the third root cannot be the closest
+        dot2(d + (c + b * t.x) * t.x).min(dot2(d + (c + b * t.y) * t.y))
+    };
+    res.sqrt()
+}
+
 /// Signed distance to a blobby cross
-pub fn blobby_cross(p: Vec2, he: f32) -> f32 {
-    let mut pos = p.abs();
-    pos = Vec2::new(
-        (pos.x - pos.y).abs(),
-        (1.0 - pos.x - pos.y) / (2.0_f32).sqrt(),
-    );
+pub fn sd_blobby_cross(p_in: Vec2, he: f32) -> f32 {
+    let mut pos = p_in.abs();
+    pos = Vec2::new((pos.x - pos.y).abs(), 1.0 - pos.x - pos.y) / (2.0_f32).sqrt();
 
     let p = (he - pos.y - 0.25 / he) / (6.0 * he);
     let q = pos.x / (he * he * 16.0);
@@ -550,88 +686,257 @@ pub fn blobby_cross(p: Vec2, he: f32) -> f32 {
     };
     let x = x.min((2.0_f32).sqrt() / 2.0);
 
-    let z = Vec2::new(x, he * (1.0 - 2.0 * x * x) - pos.y);
+    let z = Vec2::new(x, he * (1.0 - 2.0 * x * x)) - pos;
     z.length() * sign(z.y)
 }
 
 /// Signed distance to a tunnel
-pub fn tunnel(p: Vec2, wh: Vec2) -> f32 {
-    let p_new = Vec2::new(p.x.abs(), -p.y);
-    let mut q = p_new - wh;
+pub fn sd_tunnel(p_in: Vec2, wh: Vec2) -> f32 {
+    let p = Vec2::new(p_in.x.abs(), -p_in.y);
+    let mut q = p - wh;
 
-    let d1 = dot2(Vec2::new(f32::max(q.x, 0.0), q.y));
-    q.x = if p_new.y > 0.0 {
-        p_new.length() - wh.x
-    } else {
-        q.x
-    };
-    let d2 = dot2(Vec2::new(q.x, f32::max(q.y, 0.0)));
-    let d = (f32::min(d1, d2)).sqrt();
+    let d1 = dot2(Vec2::new(q.x.max(0.0), q.y));
+    q.x = if p.y > 0.0 { q.x } else { p.length() - wh.x };
+    let d2 = dot2(Vec2::new(q.x, q.y.max(0.0)));
+    let d = d1.min(d2).sqrt();
 
-    if f32::max(q.x, q.y) < 0.0 { -d } else { d }
+    if q.x.max(q.y) < 0.0 { -d } else { d }
 }
 
-/// Signed distance to stairs
-pub fn stairs(p: Vec2, wh: Vec2, n: f32) -> f32 {
-    let mut p = p;
+/// Signed distance to `n` stairs, each `wh` in size
+pub fn sd_stairs(p_in: Vec2, wh: Vec2, n: f32) -> f32 {
+    let mut p = p_in;
     let ba = wh * n;
     let mut d = f32::min(
-        dot2(Vec2::new(p.x - clamp(p.x, 0.0, ba.x), p.y)),
-        dot2(Vec2::new(p.x - ba.x, p.y - clamp(p.y, 0.0, ba.y))),
+        dot2(p - Vec2::new(clamp(p.x, 0.0, ba.x), 0.0)),
+        dot2(p - Vec2::new(ba.x, clamp(p.y, 0.0, ba.y))),
     );
     let mut s = sign(f32::max(-p.y, p.x - ba.x));
 
     let dia = wh.length();
-    p = Vec2::new(
-        (wh.x * p.x - wh.y * p.y) / dia,
-        (wh.y * p.x + wh.x * p.y) / dia,
-    );
-    let id = clamp((p.x / dia).round(), 0.0, n - 1.0);
+    p = Vec2::new(wh.x * p.x + wh.y * p.y, -wh.y * p.x + wh.x * p.y) / dia;
+    // WGSL rounds half to even
+    let id = clamp((p.x / dia).round_ties_even(), 0.0, n - 1.0);
     p.x -= id * dia;
-    p = Vec2::new(
-        (wh.x * p.x + wh.y * p.y) / dia,
-        (-wh.y * p.x + wh.x * p.y) / dia,
-    );
+    p = Vec2::new(wh.x * p.x - wh.y * p.y, wh.y * p.x + wh.x * p.y) / dia;
 
     let hh = wh.y / 2.0;
     p.y -= hh;
     if p.y > hh * sign(p.x) {
         s = 1.0;
     }
-    p = if id < 0.5 || p.x > 0.0 {
-        p
-    } else {
-        Vec2::new(-p.x, -p.y)
-    };
-    d = f32::min(d, dot2(Vec2::new(p.x, p.y - clamp(p.y, -hh, hh))));
-    d = f32::min(d, dot2(Vec2::new(p.x - clamp(p.x, 0.0, wh.x), p.y - hh)));
+    p = if id < 0.5 || p.x > 0.0 { p } else { -p };
+    d = d.min(dot2(p - Vec2::new(0.0, clamp(p.y, -hh, hh))));
+    d = d.min(dot2(p - Vec2::new(clamp(p.x, 0.0, wh.x), hh)));
 
     d.sqrt() * s
 }
 
 /// Signed distance to a vesica (lens shape)
-pub fn vesica(p: Vec2, r: f32, d: f32) -> f32 {
-    let p_abs = p.abs();
+pub fn sd_vesica(p_in: Vec2, r: f32, d: f32) -> f32 {
+    let p = p_in.abs();
     let b = (r * r - d * d).sqrt();
-    if (p_abs.y - b) * d > p_abs.x * b {
-        (p_abs - Vec2::new(0.0, b)).length()
+    if (p.y - b) * d > p.x * b {
+        (p - Vec2::new(0.0, b)).length()
     } else {
-        (p_abs - Vec2::new(-d, 0.0)).length() - r
+        (p - Vec2::new(-d, 0.0)).length() - r
     }
 }
 
 /// Signed distance to a moon (crescent)
-pub fn moon(p: Vec2, d: f32, ra: f32, rb: f32) -> f32 {
-    let mut p = p;
+pub fn sd_moon(p_in: Vec2, d: f32, ra: f32, rb: f32) -> f32 {
+    let mut p = p_in;
     p.y = p.y.abs();
     let a = (ra * ra - rb * rb + d * d) / (2.0 * d);
-    let b = (f32::max(ra * ra - a * a, 0.0)).sqrt();
+    let b = (ra * ra - a * a).max(0.0).sqrt();
 
-    if d * (p.x * b - p.y * a) > d * d * f32::max(b - p.y, 0.0) {
-        (p - Vec2::new(a, b)).length()
-    } else {
-        f32::max(p.length() - ra, -((p - Vec2::new(d, 0.0)).length() - rb))
+    if d * (p.x * b - p.y * a) > d * d * (b - p.y).max(0.0) {
+        return (p - Vec2::new(a, b)).length();
     }
+
+    f32::max(p.length() - ra, -((p - Vec2::new(d, 0.0)).length() - rb))
+}
+
+// Gradients
+
+/// Distance to a circle in x, and its gradient in yz
+///
+/// The gradient functions are like iq's `sdgXXX` functions. Their gradient is zero where it
+/// isn't defined, like at the center of a circle.
+pub fn sdg_circle(p: Vec2, r: f32) -> Vec3 {
+    let l = p.length();
+    let g = if l > 0.0 { p / l } else { Vec2::ZERO };
+    dg(l - r, g)
+}
+
+/// Distance to an annulus in x, and its gradient in yz, see [`sdg_circle`]
+pub fn sdg_annulus(p: Vec2, outer_radius: f32, inner_radius: f32) -> Vec3 {
+    let middle_radius = (outer_radius + inner_radius) * 0.5;
+    let thickness = (outer_radius - inner_radius) * 0.5;
+    let c = sdg_circle(p, middle_radius);
+    dg(c.x.abs() - thickness, c.yz() * sign(c.x))
+}
+
+/// Distance to a box in x, and its gradient in yz, see [`sdg_circle`]
+pub fn sdg_box(p: Vec2, b: Vec2) -> Vec3 {
+    let w = p.abs() - b;
+    let s = Vec2::new(
+        if p.x < 0.0 { -1.0 } else { 1.0 },
+        if p.y < 0.0 { -1.0 } else { 1.0 },
+    );
+    let g = w.x.max(w.y);
+    let q = w.max(Vec2::ZERO);
+    let l = q.length();
+    if g > 0.0 {
+        return dg(l, s * q / l);
+    }
+    let inside = if w.x > w.y { Vec2::X } else { Vec2::Y };
+    dg(g, s * inside)
+}
+
+/// Distance to a line segment in x, and its gradient in yz, see [`sdg_circle`]
+///
+/// When `a == b`, this is the distance to that point.
+pub fn sdg_segment(p: Vec2, a: Vec2, b: Vec2) -> Vec3 {
+    let pa = p - a;
+    let ba = b - a;
+    let ba_len_sq = ba.dot(ba);
+    let h = if ba_len_sq == 0.0 {
+        0.0
+    } else {
+        clamp(pa.dot(ba) / ba_len_sq, 0.0, 1.0)
+    };
+    let q = pa - ba * h;
+    let d = q.length();
+    let g = if d > 0.0 { q / d } else { Vec2::ZERO };
+    dg(d, g)
+}
+
+/// Distance to a vertical capsule in x, and its gradient in yz, see [`sdg_circle`]
+pub fn sdg_capsule(p: Vec2, radius: f32, half_length: f32) -> Vec3 {
+    let s = sdg_segment(p, Vec2::new(0.0, -half_length), Vec2::new(0.0, half_length));
+    dg(s.x - radius, s.yz())
+}
+
+/// Direction of the gradient of any distance function, by central differences
+///
+/// Zero where it can't be determined, like at the center of a circle. Prefer one of the
+/// `sdg_*` functions when there is one for the shape.
+pub fn estimate_gradient(distance: impl Fn(Vec2) -> f32, p: Vec2) -> Vec2 {
+    const EPSILON: f32 = 1e-3;
+    let dx = Vec2::new(EPSILON, 0.0);
+    let dy = Vec2::new(0.0, EPSILON);
+    Vec2::new(
+        distance(p + dx) - distance(p - dx),
+        distance(p + dy) - distance(p - dy),
+    )
+    .normalize_or_zero()
+}
+
+// Fill helpers
+
+/// Map a uv coordinate from `0..1` to `-1..1`
+pub fn renormalize_uv(uv: Vec2) -> Vec2 {
+    uv * 2.0 - Vec2::new(1.0, 1.0)
+}
+
+/// Falls off from 1 at distance 0 to 0 at distance `size`, with the given power
+pub fn exponential_falloff(d: f32, size: f32, power: f32) -> f32 {
+    let a = (size - d) / size;
+    clamp(a, 0.0, 1.0).powf(power)
+}
+
+/// [`exponential_falloff`] with a power of 3, `smud::exponential_falloff_3_` in WGSL
+pub fn exponential_falloff_3(d: f32, size: f32) -> f32 {
+    let a = clamp((size - d) / size, 0.0, 1.0);
+    a * a * a
+}
+
+/// Corrects a distance measured in shape space for non-uniform scale and skew
+///
+/// `transform` is the 2x2 matrix from shape space to world space (x axis in xy, y axis in zw).
+/// For shapes in 3d, it's the matrix that scales and skews like the transform does within the
+/// plane of the shape, see `shape_plane_matrix2`. Instead of the position, this takes the screen space derivatives the WGSL version gets from
+/// it: `pos_dpdx` and `pos_dpdy` are the derivatives of the position, and `d_dpd` the
+/// derivatives of the distance along x and y. For uniformly scaled shapes, this returns `d`.
+pub fn correct_distance(
+    d: f32,
+    transform: Vec4,
+    pos_dpdx: Vec2,
+    pos_dpdy: Vec2,
+    d_dpd: Vec2,
+) -> f32 {
+    let (dx, dy, dd) = (pos_dpdx, pos_dpdy, d_dpd);
+
+    let j_det = dx.x * dy.y - dx.y * dy.x;
+    let g = Vec2::new(dy.y * dd.x - dx.y * dd.y, -dy.x * dd.x + dx.x * dd.y) / j_det;
+
+    let det = transform.x * transform.w - transform.z * transform.y;
+    // inverse transpose of the transform applied to the local gradient direction
+    let n = g.normalize();
+    let w = Vec2::new(
+        transform.w * n.x - transform.y * n.y,
+        -transform.z * n.x + transform.x * n.y,
+    ) / det;
+    let corrected = d / (det.abs().sqrt() * w.length());
+
+    let valid = j_det.abs() > 1e-12 && det.abs() > 1e-12 && g.dot(g) > 1e-12;
+    if valid { corrected } else { d }
+}
+
+/// Direction in which the distance grows the fastest, in shape space
+///
+/// Instead of the distance and position, this takes the screen space derivatives the WGSL
+/// version gets from them, like [`correct_distance`]. Returns zero where the direction can't be
+/// determined.
+pub fn distance_gradient(pos_dpdx: Vec2, pos_dpdy: Vec2, d_dpd: Vec2) -> Vec2 {
+    let (dx, dy, dd) = (pos_dpdx, pos_dpdy, d_dpd);
+
+    let j_det = dx.x * dy.y - dx.y * dy.x;
+    let g = Vec2::new(dy.y * dd.x - dx.y * dd.y, -dy.x * dd.x + dx.x * dd.y) / j_det;
+
+    let valid = j_det.abs() > 1e-12 && g.dot(g) > 1e-12;
+    if valid { g.normalize() } else { Vec2::ZERO }
+}
+
+/// Anti-aliased alpha of a shape, fading out over `fwidth` around the edge
+///
+/// `fwidth` is the sum of the absolute screen space derivatives of `distance`, see `fwidth` in
+/// WGSL.
+pub fn sd_fill_alpha_fwidth(distance: f32, fwidth: f32) -> f32 {
+    let aaf = 0.71 * fwidth;
+    smoothstep(aaf, -aaf, distance)
+}
+
+/// Anti-aliased alpha of a shape, `dpd` are the screen space derivatives of `distance` along
+/// x and y
+pub fn sd_fill_alpha_dpd(distance: f32, dpd: Vec2) -> f32 {
+    let aaf = dpd.length() * 0.71;
+    smoothstep(aaf, -aaf, distance)
+}
+
+/// Alpha of a shape without anti-aliasing
+pub fn sd_fill_alpha_nearest(distance: f32) -> f32 {
+    if 0.0 >= -distance { 1.0 } else { 0.0 }
+}
+
+/// Fades from `fill_color` to `falloff_color` at the edge, and then out over `falloff_size`,
+/// `smud::sd_fill_with_falloff_3_` in WGSL
+///
+/// `fwidth` is the sum of the absolute screen space derivatives of `d`.
+pub fn sd_fill_with_falloff_3(
+    d: f32,
+    falloff_size: f32,
+    falloff_color: Vec4,
+    fill_color: Vec4,
+    fwidth: f32,
+) -> Vec4 {
+    let aaf = 0.7 / fwidth;
+    let t_color = clamp(d * aaf, 0.0, 1.0);
+    let mut color = fill_color.lerp(falloff_color, t_color);
+    let falloff = exponential_falloff_3(d, falloff_size);
+    color.w *= falloff;
+    color
 }
 
 // Operations for combining SDF shapes
@@ -651,38 +956,511 @@ pub fn op_intersect(d1: f32, d2: f32) -> f32 {
     d1.max(d2)
 }
 
-/// Smooth union of two SDF shapes
-pub fn op_smooth_union(d1: f32, d2: f32, k: f32) -> f32 {
-    let h = clamp(0.5 + 0.5 * (d2 - d1) / k, 0.0, 1.0);
-    d2 * (1.0 - h) + d1 * h - k * h * (1.0 - h)
+// Rotations
+
+/// Sine and cosine of an angle, in that order
+pub fn sin_cos(a: f32) -> Vec2 {
+    let (s, c) = a.sin_cos();
+    Vec2::new(s, c)
+}
+
+/// Counter-clockwise rotation given a sin/cos vector, see [`sin_cos`]
+///
+/// Rotating the position of a shape rotates the shape the other way.
+pub fn rotate(p: Vec2, sc: Vec2) -> Vec2 {
+    let s = sc.x;
+    let c = sc.y;
+    Vec2::new(p.x * c + p.y * s, -p.x * s + p.y * c)
+}
+
+/// [`rotate`] by an angle in radians
+pub fn rotate_rad(p: Vec2, a: f32) -> Vec2 {
+    rotate(p, sin_cos(a))
+}
+
+/// [`rotate`] by 45 degrees, `smud::rotate_45_` in WGSL
+pub fn rotate_45(p: Vec2) -> Vec2 {
+    let c = std::f32::consts::FRAC_1_SQRT_2;
+    let xc = p.x * c;
+    let yc = p.y * c;
+    Vec2::new(xc + yc, -xc + yc)
 }
 
+// Smooth operations
+
 /// Smooth subtraction of two SDF shapes
 pub fn op_smooth_subtract(d1: f32, d2: f32, k: f32) -> f32 {
     let h = clamp(0.5 - 0.5 * (d2 + d1) / k, 0.0, 1.0);
-    d2 * (1.0 - h) + (-d1) * h + k * h * (1.0 - h)
+    mix(d2, -d1, h) + k * h * (1.0 - h)
+}
+
+/// Smooth union of two SDF shapes
+pub fn op_smooth_union(d1: f32, d2: f32, k: f32) -> f32 {
+    let h = clamp(0.5 + 0.5 * (d2 - d1) / k, 0.0, 1.0);
+    mix(d2, d1, h) - k * h * (1.0 - h)
 }
 
 /// Smooth intersection of two SDF shapes
 pub fn op_smooth_intersect(d1: f32, d2: f32, k: f32) -> f32 {
     let h = clamp(0.5 - 0.5 * (d2 - d1) / k, 0.0, 1.0);
-    d2 * (1.0 - h) + d1 * h + k * h * (1.0 - h)
+    mix(d2, d1, h) + k * h * (1.0 - h)
+}
+
+// Domain operations, these transform the position passed to a distance function
+
+/// Infinite copies on a grid with the given spacing
+///
+/// Only exact if the shape fits in a single cell.
+///
+/// ```
+/// # use bevy::math::Vec2;
+/// # use bevy_smud::sdf;
+/// let dots = |p| sdf::sd_circle(sdf::op_repeat(p, Vec2::splat(50.0)), 10.0);
+/// assert_eq!(dots(Vec2::new(100.0, -50.0)), -10.0);
+/// ```
+pub fn op_repeat(p: Vec2, spacing: Vec2) -> Vec2 {
+    p - spacing * round(p / spacing)
+}
+
+/// Like [`op_repeat`], but only `limit` copies on each side of the original, per axis
+pub fn op_repeat_limited(p: Vec2, spacing: Vec2, limit: Vec2) -> Vec2 {
+    p - spacing * round(p / spacing).clamp(-limit, limit)
+}
+
+/// Mirrors the right half of a shape onto the left half
+pub fn op_mirror_x(p: Vec2) -> Vec2 {
+    Vec2::new(p.x.abs(), p.y)
+}
+
+/// Mirrors the top half of a shape onto the bottom half
+pub fn op_mirror_y(p: Vec2) -> Vec2 {
+    Vec2::new(p.x, p.y.abs())
+}
+
+/// `count` copies around the origin of the slice of a shape around the positive x axis
+pub fn op_polar_repeat(p: Vec2, count: i32) -> Vec2 {
+    let sector = 2.0 * PI / count as f32;
+    let angle = modulo(p.y.atan2(p.x) + sector * 0.5, sector) - sector * 0.5;
+    Vec2::new(angle.cos(), angle.sin()) * p.length()
+}
+
+/// Stretches a shape by `h` in both directions from the origin
+///
+/// Only exact outside the shape.
+pub fn op_elongate(p: Vec2, h: Vec2) -> Vec2 {
+    p - p.clamp(-h, h)
+}
+
+// Distance operations, these change the distance returned by a distance function
+
+/// Hollows out a shape, leaving `thickness` on each side of its edge
+pub fn op_onion(d: f32, thickness: f32) -> f32 {
+    d.abs() - thickness
+}
+
+/// Grows a shape by `radius`, rounding its corners
+pub fn op_round(d: f32, radius: f32) -> f32 {
+    d - radius
+}
+
+/// Adds a displacement, like noise or a sine wave, to a distance
+///
+/// The result is usually not an exact distance anymore, so keep the displacement small and
+/// smooth.
+pub fn op_displace(d: f32, displacement: f32) -> f32 {
+    d + displacement
+}
+
+// Deprecated names, from before the functions were named like in WGSL
+
+macro_rules! deprecated_alias {
+    ($($old:ident => $new:ident($($arg:ident: $ty:ty),*);)*) => {
+        $(
+            #[doc = concat!("Renamed to [`", stringify!($new), "`]")]
+            #[deprecated(note = "renamed to the name of the function in smud.wgsl")]
+            pub fn $old($($arg: $ty),*) -> f32 {
+                $new($($arg),*)
+            }
+        )*
+    };
+}
+
+deprecated_alias! {
+    circle => sd_circle(p: Vec2, radius: f32);
+    annulus => sd_annulus(p: Vec2, outer_radius: f32, inner_radius: f32);
+    capsule => sd_capsule(p: Vec2, radius: f32, half_length: f32);
+    segment => sd_segment(p: Vec2, a: Vec2, b: Vec2);
+    rhombus => sd_rhombus(p: Vec2, b: Vec2);
+    trapezoid => sd_trapezoid(p: Vec2, r1: f32, r2: f32, he: f32);
+    parallelogram => sd_parallelogram(p: Vec2, wi: f32, he: f32, sk: f32);
+    equilateral_triangle => sd_equilateral_triangle(p: Vec2, r: f32);
+    triangle_isosceles => sd_triangle_isosceles(p: Vec2, q: Vec2);
+    triangle => sd_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2);
+    uneven_capsule => sd_uneven_capsule(p: Vec2, r1: f32, r2: f32, h: f32);
+    pentagon => sd_pentagon(p: Vec2, r: f32);
+    hexagon => sd_hexagon(p: Vec2, r: f32);
+    octagon => sd_octagon(p: Vec2, r: f32);
+    hexagram => sd_hexagram(p: Vec2, r: f32);
+    star_5 => sd_star_5(p: Vec2, r: f32, rf: f32);
+    regular_polygon => sd_regular_polygon(p: Vec2, radius: f32, sides: i32);
+    star => sd_star(p: Vec2, r: f32, n: i32, m: f32);
+    pie => sd_pie(p: Vec2, c: Vec2, r: f32);
+    cut_disk => sd_cut_disk(p: Vec2, r: f32, h: f32);
+    arc => sd_arc(p: Vec2, sc: Vec2, ra: f32, rb: f32);
+    horseshoe => sd_horseshoe(p: Vec2, c: Vec2, r: f32, w: Vec2);
+    rounded_cross => sd_rounded_cross(p: Vec2, h: f32);
+    egg => sd_egg(p: Vec2, ra: f32, rb: f32);
+    heart => sd_heart(p: Vec2);
+    cross => sd_cross(p: Vec2, b: Vec2, r: f32);
+    rounded_x => sd_rounded_x(p: Vec2, w: f32, r: f32);
+    ellipse => sd_ellipse(p: Vec2, a: f32, b: f32);
+    parabola => sd_parabola(p: Vec2, k: f32);
+    parabola_segment => sd_parabola_segment(p: Vec2, wi: f32, he: f32);
+    blobby_cross => sd_blobby_cross(p: Vec2, he: f32);
+    tunnel => sd_tunnel(p: Vec2, wh: Vec2);
+    stairs => sd_stairs(p: Vec2, wh: Vec2, n: f32);
+    vesica => sd_vesica(p: Vec2, r: f32, d: f32);
+    moon => sd_moon(p: Vec2, d: f32, ra: f32, rb: f32);
+}
+
+/// Signed distance to a box with all corners rounded by `r`
+#[deprecated(
+    note = "use `sd_box(p, b) - r`, or `sd_rounded_box` to round each corner like in \
+                     smud.wgsl"
+)]
+pub fn rounded_box(p: Vec2, b: Vec2, r: f32) -> f32 {
+    sd_box(p, b) - r
+}
+
+/// Signed distance to an oriented box, `th` is half the thickness
+#[deprecated(note = "use `sd_oriented_box`, which takes the full thickness like in smud.wgsl")]
+pub fn oriented_box(p: Vec2, a: Vec2, b: Vec2, th: f32) -> f32 {
+    sd_oriented_box(p, a, b, th * 2.0)
+}
+
+// Baking
+
+/// Pixel format of an image baked by [`bake_sdf_to_image`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BakeFormat {
+    /// Signed distance in [`TextureFormat::R32Float`], in the units of the distance function
+    #[default]
+    Distance,
+    /// White with anti-aliased coverage in alpha, in [`TextureFormat::Rgba8UnormSrgb`]
+    ///
+    /// Coverage fades out over one pixel around the edge of the shape.
+    Coverage,
+}
+
+/// Evaluate a distance function at the center of each pixel of a grid, and store the result
+/// in an [`Image`]
+///
+/// `area` is the part of the distance function's space covered by the image. The first row of
+/// the image is the top of the area, so the image isn't flipped when displayed.
+///
+/// Any distance function works, for instance one of the functions in this module, or the
+/// `distance_fn` of a `SmudPickingShape`:
+///
+/// ```
+/// # use bevy::math::{Rect, UVec2};
+/// # use bevy_smud::sdf::{self, BakeFormat};
+/// let image = sdf::bake_sdf_to_image(
+///     |p| sdf::sd_circle(p, 50.),
+///     Rect::new(-64., -64., 64., 64.),
+///     UVec2::new(128, 128),
+///     BakeFormat::Distance,
+/// );
+/// ```
+///
+/// The image is kept in the main world, so its data can be read back with
+/// [`Image::get_color_at`] or by reading the data directly.
+pub fn bake_sdf_to_image(
+    distance: impl Fn(Vec2) -> f32,
+    area: Rect,
+    size: UVec2,
+    format: BakeFormat,
+) -> Image {
+    let pixel_size = area.size() / size.as_vec2();
+    // Both formats have four bytes per pixel
+    let mut data = Vec::with_capacity((size.x * size.y * 4) as usize);
+
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let p = Vec2::new(
+                area.min.x + (x as f32 + 0.5) * pixel_size.x,
+                area.max.y - (y as f32 + 0.5) * pixel_size.y,
+            );
+            let d = distance(p);
+            match format {
+                BakeFormat::Distance => data.extend_from_slice(&d.to_le_bytes()),
+                BakeFormat::Coverage => {
+                    let coverage = clamp(0.5 - d / pixel_size.min_element(), 0.0, 1.0);
+                    data.extend_from_slice(&[255, 255, 255, (coverage * 255.0).round() as u8]);
+                }
+            }
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        match format {
+            BakeFormat::Distance => TextureFormat::R32Float,
+            BakeFormat::Coverage => TextureFormat::Rgba8UnormSrgb,
+        },
+        RenderAssetUsages::default(),
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn assert_distance(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {expected}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn test_polygon() {
+        let square = [
+            Vec2::new(-10.0, -10.0),
+            Vec2::new(10.0, -10.0),
+            Vec2::new(10.0, 10.0),
+            Vec2::new(-10.0, 10.0),
+        ];
+        for p in [
+            Vec2::ZERO,
+            Vec2::new(3.0, -8.0),
+            Vec2::new(25.0, 4.0),
+            Vec2::new(15.0, 15.0),
+        ] {
+            assert_distance(sd_polygon(p, &square), sd_box(p, Vec2::splat(10.0)));
+        }
+
+        // Winding order doesn't matter
+        let mut reversed = square;
+        reversed.reverse();
+        assert_distance(sd_polygon(Vec2::new(3.0, -8.0), &reversed), -2.0);
+
+        // An L shape, concave at the origin
+        let l = [
+            Vec2::new(-10.0, -10.0),
+            Vec2::new(10.0, -10.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::ZERO,
+            Vec2::new(0.0, 10.0),
+            Vec2::new(-10.0, 10.0),
+        ];
+        assert_distance(sd_polygon(Vec2::new(-5.0, 5.0), &l), -5.0);
+        assert_distance(sd_polygon(Vec2::new(5.0, 5.0), &l), 5.0);
+        assert_distance(sd_polygon(Vec2::new(5.0, 2.0), &l), 2.0);
+
+        // Repeating the first vertex at the end leaves the shape as it is
+        let closed = [&square[..], &square[..1]].concat();
+        assert_distance(sd_polygon(Vec2::new(3.0, -8.0), &closed), -2.0);
+    }
+
+    #[test]
+    fn test_polyline() {
+        let zigzag = [
+            Vec2::new(-20.0, 0.0),
+            Vec2::new(0.0, 10.0),
+            Vec2::new(20.0, 0.0),
+        ];
+        assert_distance(sd_polyline(Vec2::new(0.0, 10.0), &zigzag), 0.0);
+        assert_distance(sd_polyline(Vec2::new(-25.0, 0.0), &zigzag), 5.0);
+        // Not closed, so the first and last vertex aren't connected
+        assert_distance(sd_polyline(Vec2::new(0.0, -5.0), &zigzag), 180.0_f32.sqrt());
+        // A single vertex is a point
+        assert_distance(sd_polyline(Vec2::new(-17.0, 4.0), &zigzag[..1]), 5.0);
+    }
+
+    #[test]
+    fn test_op_repeat() {
+        let dots = |p| sd_circle(op_repeat(p, Vec2::new(50.0, 40.0)), 10.0);
+        assert_distance(dots(Vec2::ZERO), -10.0);
+        assert_distance(dots(Vec2::new(-100.0, 120.0)), -10.0);
+        assert_distance(dots(Vec2::new(55.0, 0.0)), -5.0);
+        // Halfway between two copies
+        assert_distance(dots(Vec2::new(25.0, 0.0)), 15.0);
+    }
+
+    #[test]
+    fn test_op_repeat_limited() {
+        let row = |p| {
+            sd_circle(
+                op_repeat_limited(p, Vec2::splat(50.0), Vec2::new(1.0, 0.0)),
+                10.0,
+            )
+        };
+        assert_distance(row(Vec2::new(-50.0, 0.0)), -10.0);
+        assert_distance(row(Vec2::new(50.0, 0.0)), -10.0);
+        // Past the last copy, and outside the only row
+        assert_distance(row(Vec2::new(100.0, 0.0)), 40.0);
+        assert_distance(row(Vec2::new(0.0, 50.0)), 40.0);
+    }
+
+    #[test]
+    fn test_op_mirror() {
+        let right = |p: Vec2| sd_circle(p - Vec2::new(20.0, 10.0), 5.0);
+        assert_distance(right(op_mirror_x(Vec2::new(-20.0, 10.0))), -5.0);
+        assert_distance(right(op_mirror_x(Vec2::new(-20.0, -10.0))), 15.0);
+        assert_distance(right(op_mirror_y(Vec2::new(20.0, -10.0))), -5.0);
+        assert_distance(right(op_mirror_y(Vec2::new(-20.0, -10.0))), 35.0);
+    }
+
+    #[test]
+    fn test_op_polar_repeat() {
+        let petals = |p| sd_circle(op_polar_repeat(p, 4) - Vec2::new(30.0, 0.0), 5.0);
+        assert_distance(petals(Vec2::new(30.0, 0.0)), -5.0);
+        assert_distance(petals(Vec2::new(0.0, 30.0)), -5.0);
+        assert_distance(petals(Vec2::new(-30.0, 0.0)), -5.0);
+        assert_distance(petals(Vec2::new(0.0, -35.0)), 0.0);
+        // Between two petals
+        let between = Vec2::splat(30.0 * std::f32::consts::FRAC_1_SQRT_2);
+        assert_distance(
+            petals(between),
+            (between - Vec2::new(30.0, 0.0)).length() - 5.0,
+        );
+    }
+
+    #[test]
+    fn test_op_elongate() {
+        let pill = |p| sd_circle(op_elongate(p, Vec2::new(20.0, 0.0)), 10.0);
+        assert_distance(pill(Vec2::ZERO), -10.0);
+        assert_distance(pill(Vec2::new(25.0, 0.0)), -5.0);
+        assert_distance(pill(Vec2::new(-30.0, 0.0)), 0.0);
+        assert_distance(pill(Vec2::new(15.0, 15.0)), 5.0);
+        assert_distance(pill(Vec2::new(35.0, 0.0)), 5.0);
+    }
+
+    #[test]
+    fn test_distance_operations() {
+        // Ring between radius 18 and 22
+        let ring = |p| op_onion(sd_circle(p, 20.0), 2.0);
+        assert_distance(ring(Vec2::ZERO), 18.0);
+        assert_distance(ring(Vec2::new(20.0, 0.0)), -2.0);
+        assert_distance(ring(Vec2::new(0.0, 25.0)), 3.0);
+
+        let rounded = |p| op_round(sd_box(p, Vec2::splat(10.0)), 5.0);
+        assert_distance(rounded(Vec2::new(20.0, 0.0)), 5.0);
+        assert_distance(rounded(Vec2::new(15.0, 15.0)), 50.0_f32.sqrt() - 5.0);
+
+        assert_distance(op_displace(sd_circle(Vec2::new(10.0, 0.0), 5.0), -2.0), 3.0);
+    }
+
+    #[test]
+    fn test_gradients_match_distance_functions() {
+        type Shape = (&'static str, fn(Vec2) -> Vec3, fn(Vec2) -> f32);
+        let shapes: [Shape; 5] = [
+            ("circle", |p| sdg_circle(p, 30.0), |p| sd_circle(p, 30.0)),
+            (
+                "annulus",
+                |p| sdg_annulus(p, 30.0, 20.0),
+                |p| sd_annulus(p, 30.0, 20.0),
+            ),
+            (
+                "box",
+                |p| sdg_box(p, Vec2::new(30.0, 20.0)),
+                |p| sd_box(p, Vec2::new(30.0, 20.0)),
+            ),
+            (
+                "segment",
+                |p| sdg_segment(p, Vec2::new(-20.0, -10.0), Vec2::new(25.0, 15.0)),
+                |p| sd_segment(p, Vec2::new(-20.0, -10.0), Vec2::new(25.0, 15.0)),
+            ),
+            (
+                "capsule",
+                |p| sdg_capsule(p, 10.0, 20.0),
+                |p| sd_capsule(p, 10.0, 20.0),
+            ),
+        ];
+
+        for (name, sdg, sd) in shapes {
+            for x in -4..=4 {
+                for y in -4..=4 {
+                    let p = Vec2::new(x as f32 * 11.3 + 0.7, y as f32 * 10.1 - 0.3);
+                    let dg = sdg(p);
+                    assert!((dg.x - sd(p)).abs() < 1e-4, "{name} distance at {p}");
+                    let estimate = estimate_gradient(sd, p);
+                    assert!(
+                        dg.yz().distance(estimate) < 1e-2,
+                        "{name} gradient at {p}: {} vs {estimate}",
+                        dg.yz()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_bake_distance() {
+        let image = bake_sdf_to_image(
+            |p| sd_circle(p, 1.0),
+            Rect::new(-2.0, -2.0, 2.0, 2.0),
+            UVec2::new(4, 4),
+            BakeFormat::Distance,
+        );
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R32Float);
+
+        let data = image.data.as_ref().unwrap();
+        assert_eq!(data.len(), 4 * 4 * 4);
+        let pixel = |x: usize, y: usize| {
+            let i = (y * 4 + x) * 4;
+            f32::from_le_bytes(data[i..i + 4].try_into().unwrap())
+        };
+
+        // Pixel centers are at +-0.5 and +-1.5
+        let inner = Vec2::splat(0.5).length() - 1.0;
+        let outer = Vec2::splat(1.5).length() - 1.0;
+        assert!((pixel(1, 1) - inner).abs() < 1e-6);
+        assert!((pixel(2, 2) - inner).abs() < 1e-6);
+        assert!((pixel(0, 0) - outer).abs() < 1e-6);
+        assert!((pixel(3, 3) - outer).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_bake_coverage_is_not_flipped() {
+        // A circle in the top half of the area
+        let image = bake_sdf_to_image(
+            |p| sd_circle(p - Vec2::new(0.0, 5.0), 2.0),
+            Rect::new(-10.0, -10.0, 10.0, 10.0),
+            UVec2::new(20, 20),
+            BakeFormat::Coverage,
+        );
+        assert_eq!(
+            image.texture_descriptor.format,
+            TextureFormat::Rgba8UnormSrgb
+        );
+
+        let data = image.data.as_ref().unwrap();
+        assert_eq!(data.len(), 20 * 20 * 4);
+        let alpha = |x: usize, y: usize| data[(y * 20 + x) * 4 + 3];
+
+        // The circle is centered between pixels 9 and 10 horizontally, and at row 4.5
+        assert_eq!(alpha(9, 4), 255);
+        assert_eq!(alpha(9, 15), 0);
+        assert_eq!(alpha(0, 0), 0);
+    }
+
     #[test]
     fn test_circle() {
         // Point at center should be -radius
-        assert_eq!(circle(Vec2::ZERO, 1.0), -1.0);
+        assert_eq!(sd_circle(Vec2::ZERO, 1.0), -1.0);
 
         // Point on circle should be 0
-        assert!((circle(Vec2::new(1.0, 0.0), 1.0)).abs() < f32::EPSILON);
+        assert!((sd_circle(Vec2::new(1.0, 0.0), 1.0)).abs() < f32::EPSILON);
 
         // Point outside circle should be positive
-        assert!(circle(Vec2::new(2.0, 0.0), 1.0) > 0.0);
+        assert!(sd_circle(Vec2::new(2.0, 0.0), 1.0) > 0.0);
     }
 
     #[test]
@@ -692,7 +1470,7 @@ mod tests {
         let b = Vec2::new(5.0, 5.0);
 
         // Distance from origin to the point (5, 5)
-        let result = segment(Vec2::ZERO, a, b);
+        let result = sd_segment(Vec2::ZERO, a, b);
         assert!(
             result.is_finite(),
             "segment should not return NaN for a == b"
@@ -701,7 +1479,7 @@ mod tests {
         assert!((result - expected).abs() < 0.001);
 
         // Distance from a point to itself should be 0
-        let result = segment(Vec2::new(5.0, 5.0), a, b);
+        let result = sd_segment(Vec2::new(5.0, 5.0), a, b);
         assert!(result.is_finite());
         assert!(result.abs() < f32::EPSILON);
     }
@@ -713,17 +1491,17 @@ mod tests {
         let half_length = 0.0;
 
         // Point at center should be -radius
-        let result = capsule(Vec2::ZERO, radius, half_length);
+        let result = sd_capsule(Vec2::ZERO, radius, half_length);
         assert!(result.is_finite(), "capsule should not return NaN");
         assert_eq!(result, -radius);
 
         // Point on the circle edge
-        let result = capsule(Vec2::new(radius, 0.0), radius, half_length);
+        let result = sd_capsule(Vec2::new(radius, 0.0), radius, half_length);
         assert!(result.is_finite(), "capsule should not return NaN");
         assert!(result.abs() < 0.001);
 
         // Point outside
-        let result = capsule(Vec2::new(30.0, 0.0), radius, half_length);
+        let result = sd_capsule(Vec2::new(30.0, 0.0), radius, half_length);
         assert!(result.is_finite(), "capsule should not return NaN");
         assert!(result > 0.0);
     }
@@ -735,8 +1513,38 @@ mod tests {
         let half_length = 10.0;
 
         // Point at center
-        let result = capsule(Vec2::ZERO, radius, half_length);
+        let result = sd_capsule(Vec2::ZERO, radius, half_length);
         assert!(result.is_finite());
         assert_eq!(result, -radius);
     }
+
+    #[test]
+    fn test_correct_distance_rotated_out_of_plane() {
+        use crate::util::shape_plane_matrix2;
+        use bevy::{
+            math::Quat,
+            transform::components::{GlobalTransform, Transform},
+        };
+        use std::f32::consts::FRAC_PI_2;
+
+        // Like the flat star in the 3d example, lying in the xz plane
+        let transform = GlobalTransform::from(
+            Transform::from_rotation(Quat::from_rotation_x(-FRAC_PI_2))
+                .with_scale(Vec3::splat(0.03)),
+        );
+        let matrix = Vec4::from_array(shape_plane_matrix2(&transform).to_cols_array());
+
+        for p in [
+            Vec2::new(30.0, 0.0),
+            Vec2::new(0.0, 30.0),
+            Vec2::new(-20.0, 25.0),
+            Vec2::new(5.0, -3.0),
+        ] {
+            let d = sd_circle(p, 10.0);
+            // With the position as the screen coordinates, the derivatives of the distance
+            // are its gradient in shape space
+            let corrected = correct_distance(d, matrix, Vec2::X, Vec2::Y, p.normalize());
+            assert_distance(corrected, d);
+        }
+    }
 }
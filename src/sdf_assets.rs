@@ -1,6 +1,6 @@
 use bevy::prelude::*;
 
-use crate::util::generate_shader_id;
+use crate::{SdfExpr, util::generate_shader_id};
 
 /// Extension trait for Assets<Shader> for conveniently creating new shaders from code
 pub trait SdfAssets {
@@ -8,6 +8,8 @@ pub trait SdfAssets {
     fn add_sdf_body<T: Into<String>>(&mut self, sdf: T) -> Handle<Shader>;
     /// Create a sdf shader from the given wgsl expression
     fn add_sdf_expr<T: Into<String>>(&mut self, sdf: T) -> Handle<Shader>;
+    /// Create a sdf shader from an [`SdfExpr`] tree
+    fn add_sdf(&mut self, sdf: &SdfExpr) -> Handle<Shader>;
     /// Create a fill shader from the given wgsl body
     fn add_fill_body<T: Into<String>>(&mut self, fill: T) -> Handle<Shader>;
     /// Create a fill shader from the given wgsl expression
@@ -24,6 +26,7 @@ impl SdfAssets for Assets<Shader> {
 #define_import_path smud::sdf{id}
 
 #import smud
+#import smud::shape_params
 
 fn sdf(input: smud::SdfInput) -> f32 {{
     // Backward compatibility aliases for the old API
@@ -49,6 +52,8 @@ fn sdf(input: smud::SdfInput) -> f32 {{
 #define_import_path smud::fill{id}
 
 #import smud
+#import smud::shape_params
+#import smud::fill_texture
 #import bevy_render::globals::Globals
 
 @group(0) @binding(1) var<uniform> globals: Globals;
@@ -57,6 +62,7 @@ fn fill(input: smud::FillInput) -> vec4<f32> {{
     let time = globals.time;
     let p = input.pos;
     let params = input.params;
+    let fill_params = input.fill_params;
     let d = input.distance;
     {body}
 }}
@@ -72,6 +78,10 @@ fn fill(input: smud::FillInput) -> vec4<f32> {{
         self.add_sdf_body(format!("return {e};"))
     }
 
+    fn add_sdf(&mut self, sdf: &SdfExpr) -> Handle<Shader> {
+        self.add_sdf_body(sdf.to_wgsl())
+    }
+
     fn add_fill_expr<T: Into<String>>(&mut self, fill: T) -> Handle<Shader> {
         let e = fill.into();
         self.add_fill_body(format!("return {e};"))
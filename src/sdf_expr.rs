@@ -0,0 +1,460 @@
+//! Sdf expression trees that run both as wgsl and on the cpu
+
+use std::fmt::Write;
+
+use bevy::math::Vec2;
+
+use crate::sdf;
+
+/// A signed distance function built from primitives and operations
+///
+/// The same tree generates the wgsl for a shape's sdf shader, and evaluates the distance on the
+/// cpu, so picking and other cpu side code can't drift apart from what is drawn.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_smud::prelude::*;
+/// fn setup(mut commands: Commands, mut shaders: ResMut<Assets<Shader>>) {
+///     let sdf = SdfExpr::circle(40.)
+///         .smooth_union(SdfExpr::rectangle(Vec2::new(60., 10.)), 10.)
+///         .rotate(0.3);
+///
+///     commands.spawn((
+///         SmudShape {
+///             sdf: shaders.add_sdf(&sdf),
+///             bounds: Rectangle::from_length(160.),
+///             ..default()
+///         },
+///         SmudPickingShape::from(sdf),
+///     ));
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum SdfExpr {
+    /// Circle centered on the origin, see [`sdf::sd_circle`]
+    Circle {
+        /// Radius of the circle
+        radius: f32,
+    },
+    /// Axis aligned rectangle centered on the origin, see [`sdf::sd_box`]
+    Rectangle {
+        /// Half the width and height
+        half_size: Vec2,
+    },
+    /// Line segment without thickness, see [`sdf::sd_segment`]
+    ///
+    /// Use [`SdfExpr::round`] to give it a thickness.
+    Segment {
+        /// Start of the segment
+        a: Vec2,
+        /// End of the segment
+        b: Vec2,
+    },
+    /// Vertical capsule centered on the origin, see [`sdf::sd_capsule`]
+    Capsule {
+        /// Radius of the capsule
+        radius: f32,
+        /// Half the distance between the centers of the two end caps
+        half_length: f32,
+    },
+    /// Equilateral triangle pointing up, see [`sdf::sd_equilateral_triangle`]
+    EquilateralTriangle {
+        /// Half the length of a side
+        radius: f32,
+    },
+    /// Regular hexagon, see [`sdf::sd_hexagon`]
+    Hexagon {
+        /// Distance from the center to the middle of an edge
+        radius: f32,
+    },
+    /// Five pointed star, see [`sdf::sd_star_5`]
+    Star5 {
+        /// Distance from the center to the tips
+        radius: f32,
+        /// How far the inner corners reach, relative to the tips
+        inner_factor: f32,
+    },
+    /// Heart about one unit tall, with its tip at the origin, see [`sdf::sd_heart`]
+    Heart,
+    /// Both shapes
+    Union(Box<SdfExpr>, Box<SdfExpr>),
+    /// The first shape, with the second one cut out
+    Subtract(Box<SdfExpr>, Box<SdfExpr>),
+    /// The overlap of both shapes
+    Intersect(Box<SdfExpr>, Box<SdfExpr>),
+    /// Union blended over a distance
+    SmoothUnion(Box<SdfExpr>, Box<SdfExpr>, f32),
+    /// Subtraction blended over a distance
+    SmoothSubtract(Box<SdfExpr>, Box<SdfExpr>, f32),
+    /// Intersection blended over a distance
+    SmoothIntersect(Box<SdfExpr>, Box<SdfExpr>, f32),
+    /// Moves the shape
+    Translate(Box<SdfExpr>, Vec2),
+    /// Rotates the shape counter-clockwise, in radians
+    Rotate(Box<SdfExpr>, f32),
+    /// Scales the shape uniformly
+    Scale(Box<SdfExpr>, f32),
+    /// Repeats the shape infinitely, on a grid with the given spacing
+    ///
+    /// Only exact when the shape fits in a single cell.
+    Repeat(Box<SdfExpr>, Vec2),
+    /// Grows the shape by a distance, rounding its corners
+    Round(Box<SdfExpr>, f32),
+}
+
+impl SdfExpr {
+    /// Circle centered on the origin
+    pub fn circle(radius: f32) -> Self {
+        Self::Circle { radius }
+    }
+
+    /// Axis aligned rectangle centered on the origin
+    pub fn rectangle(half_size: Vec2) -> Self {
+        Self::Rectangle { half_size }
+    }
+
+    /// Rectangle with rounded corners, the same size as the rectangle without rounding
+    pub fn rounded_rectangle(half_size: Vec2, radius: f32) -> Self {
+        Self::rectangle(half_size - radius).round(radius)
+    }
+
+    /// Line segment without thickness
+    pub fn segment(a: Vec2, b: Vec2) -> Self {
+        Self::Segment { a, b }
+    }
+
+    /// Vertical capsule centered on the origin
+    pub fn capsule(radius: f32, half_length: f32) -> Self {
+        Self::Capsule {
+            radius,
+            half_length,
+        }
+    }
+
+    /// Equilateral triangle pointing up
+    pub fn equilateral_triangle(radius: f32) -> Self {
+        Self::EquilateralTriangle { radius }
+    }
+
+    /// Regular hexagon
+    pub fn hexagon(radius: f32) -> Self {
+        Self::Hexagon { radius }
+    }
+
+    /// Five pointed star
+    pub fn star_5(radius: f32, inner_factor: f32) -> Self {
+        Self::Star5 {
+            radius,
+            inner_factor,
+        }
+    }
+
+    /// Union with another shape
+    pub fn union(self, other: SdfExpr) -> Self {
+        Self::Union(Box::new(self), Box::new(other))
+    }
+
+    /// Cut another shape out of this one
+    pub fn subtract(self, other: SdfExpr) -> Self {
+        Self::Subtract(Box::new(self), Box::new(other))
+    }
+
+    /// Intersection with another shape
+    pub fn intersect(self, other: SdfExpr) -> Self {
+        Self::Intersect(Box::new(self), Box::new(other))
+    }
+
+    /// Union with another shape, blended over the distance `k`
+    pub fn smooth_union(self, other: SdfExpr, k: f32) -> Self {
+        Self::SmoothUnion(Box::new(self), Box::new(other), k)
+    }
+
+    /// Cut another shape out of this one, blended over the distance `k`
+    pub fn smooth_subtract(self, other: SdfExpr, k: f32) -> Self {
+        Self::SmoothSubtract(Box::new(self), Box::new(other), k)
+    }
+
+    /// Intersection with another shape, blended over the distance `k`
+    pub fn smooth_intersect(self, other: SdfExpr, k: f32) -> Self {
+        Self::SmoothIntersect(Box::new(self), Box::new(other), k)
+    }
+
+    /// Move the shape
+    pub fn translate(self, offset: Vec2) -> Self {
+        Self::Translate(Box::new(self), offset)
+    }
+
+    /// Rotate the shape counter-clockwise, in radians
+    pub fn rotate(self, angle: f32) -> Self {
+        Self::Rotate(Box::new(self), angle)
+    }
+
+    /// Scale the shape uniformly
+    pub fn scale(self, factor: f32) -> Self {
+        Self::Scale(Box::new(self), factor)
+    }
+
+    /// Repeat the shape infinitely, on a grid with the given spacing
+    pub fn repeat(self, period: Vec2) -> Self {
+        Self::Repeat(Box::new(self), period)
+    }
+
+    /// Grow the shape by `radius`, rounding its corners
+    pub fn round(self, radius: f32) -> Self {
+        Self::Round(Box::new(self), radius)
+    }
+
+    /// Evaluate the signed distance at a point on the cpu
+    pub fn distance(&self, p: Vec2) -> f32 {
+        match self {
+            Self::Circle { radius } => sdf::sd_circle(p, *radius),
+            Self::Rectangle { half_size } => sdf::sd_box(p, *half_size),
+            Self::Segment { a, b } => sdf::sd_segment(p, *a, *b),
+            Self::Capsule {
+                radius,
+                half_length,
+            } => sdf::sd_capsule(p, *radius, *half_length),
+            Self::EquilateralTriangle { radius } => sdf::sd_equilateral_triangle(p, *radius),
+            Self::Hexagon { radius } => sdf::sd_hexagon(p, *radius),
+            Self::Star5 {
+                radius,
+                inner_factor,
+            } => sdf::sd_star_5(p, *radius, *inner_factor),
+            Self::Heart => sdf::sd_heart(p),
+            Self::Union(a, b) => sdf::op_union(a.distance(p), b.distance(p)),
+            Self::Subtract(a, b) => sdf::op_subtract(b.distance(p), a.distance(p)),
+            Self::Intersect(a, b) => sdf::op_intersect(a.distance(p), b.distance(p)),
+            Self::SmoothUnion(a, b, k) => sdf::op_smooth_union(a.distance(p), b.distance(p), *k),
+            Self::SmoothSubtract(a, b, k) => {
+                sdf::op_smooth_subtract(b.distance(p), a.distance(p), *k)
+            }
+            Self::SmoothIntersect(a, b, k) => {
+                sdf::op_smooth_intersect(a.distance(p), b.distance(p), *k)
+            }
+            Self::Translate(shape, offset) => shape.distance(p - *offset),
+            Self::Rotate(shape, angle) => {
+                // Same as smud::rotate_rad
+                let (s, c) = angle.sin_cos();
+                shape.distance(Vec2::new(p.x * c + p.y * s, -p.x * s + p.y * c))
+            }
+            Self::Scale(shape, factor) => shape.distance(p / *factor) * *factor,
+            Self::Repeat(shape, period) => shape.distance(sdf::op_repeat(p, *period)),
+            Self::Round(shape, radius) => sdf::op_round(shape.distance(p), *radius),
+        }
+    }
+
+    /// Generate the body of a wgsl `sdf` function, for use with
+    /// [`SdfAssets::add_sdf_body`](crate::prelude::SdfAssets::add_sdf_body)
+    ///
+    /// [`SdfAssets::add_sdf`](crate::prelude::SdfAssets::add_sdf) does this for you. Panics if a
+    /// parameter isn't finite.
+    pub fn to_wgsl(&self) -> String {
+        let mut writer = WgslWriter::default();
+        let distance = self.write_wgsl("input.pos", &mut writer);
+        writeln!(writer.body, "return {distance};").unwrap();
+        writer.body
+    }
+
+    /// Write the statements computing the distance at `p`, and return the variable holding it
+    fn write_wgsl(&self, p: &str, writer: &mut WgslWriter) -> String {
+        match self {
+            Self::Circle { radius } => {
+                writer.var(format!("smud::sd_circle({p}, {})", float(*radius)))
+            }
+            Self::Rectangle { half_size } => {
+                writer.var(format!("smud::sd_box({p}, {})", vec2(*half_size)))
+            }
+            Self::Segment { a, b } => {
+                writer.var(format!("smud::sd_segment({p}, {}, {})", vec2(*a), vec2(*b)))
+            }
+            Self::Capsule {
+                radius,
+                half_length,
+            } => writer.var(format!(
+                "smud::sd_capsule({p}, {}, {})",
+                float(*radius),
+                float(*half_length)
+            )),
+            Self::EquilateralTriangle { radius } => writer.var(format!(
+                "smud::sd_equilateral_triangle({p}, {})",
+                float(*radius)
+            )),
+            Self::Hexagon { radius } => {
+                writer.var(format!("smud::sd_hexagon({p}, {})", float(*radius)))
+            }
+            Self::Star5 {
+                radius,
+                inner_factor,
+            } => writer.var(format!(
+                "smud::sd_star_5_({p}, {}, {})",
+                float(*radius),
+                float(*inner_factor)
+            )),
+            Self::Heart => writer.var(format!("smud::sd_heart({p})")),
+            Self::Union(a, b) => {
+                let (a, b) = (a.write_wgsl(p, writer), b.write_wgsl(p, writer));
+                writer.var(format!("smud::op_union({a}, {b})"))
+            }
+            Self::Subtract(a, b) => {
+                let (a, b) = (a.write_wgsl(p, writer), b.write_wgsl(p, writer));
+                writer.var(format!("smud::op_subtract({b}, {a})"))
+            }
+            Self::Intersect(a, b) => {
+                let (a, b) = (a.write_wgsl(p, writer), b.write_wgsl(p, writer));
+                writer.var(format!("smud::op_intersect({a}, {b})"))
+            }
+            Self::SmoothUnion(a, b, k) => {
+                let (a, b) = (a.write_wgsl(p, writer), b.write_wgsl(p, writer));
+                writer.var(format!("smud::op_smooth_union({a}, {b}, {})", float(*k)))
+            }
+            Self::SmoothSubtract(a, b, k) => {
+                let (a, b) = (a.write_wgsl(p, writer), b.write_wgsl(p, writer));
+                writer.var(format!("smud::op_smooth_subtract({b}, {a}, {})", float(*k)))
+            }
+            Self::SmoothIntersect(a, b, k) => {
+                let (a, b) = (a.write_wgsl(p, writer), b.write_wgsl(p, writer));
+                writer.var(format!(
+                    "smud::op_smooth_intersect({a}, {b}, {})",
+                    float(*k)
+                ))
+            }
+            Self::Translate(shape, offset) => {
+                let p = writer.var(format!("{p} - {}", vec2(*offset)));
+                shape.write_wgsl(&p, writer)
+            }
+            Self::Rotate(shape, angle) => {
+                let p = writer.var(format!("smud::rotate_rad({p}, {})", float(*angle)));
+                shape.write_wgsl(&p, writer)
+            }
+            Self::Scale(shape, factor) => {
+                let factor = float(*factor);
+                let p = writer.var(format!("{p} / {factor}"));
+                let d = shape.write_wgsl(&p, writer);
+                writer.var(format!("{d} * {factor}"))
+            }
+            Self::Repeat(shape, period) => {
+                let p = writer.var(format!("smud::op_repeat({p}, {})", vec2(*period)));
+                shape.write_wgsl(&p, writer)
+            }
+            Self::Round(shape, radius) => {
+                let d = shape.write_wgsl(p, writer);
+                writer.var(format!("smud::op_round({d}, {})", float(*radius)))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bevy_picking")]
+impl From<SdfExpr> for crate::prelude::SmudPickingShape {
+    fn from(sdf: SdfExpr) -> Self {
+        Self::new(move |p| sdf.distance(p))
+    }
+}
+
+/// Function body being generated, one `let` per node
+#[derive(Default)]
+struct WgslWriter {
+    body: String,
+    vars: usize,
+}
+
+impl WgslWriter {
+    fn var(&mut self, expr: String) -> String {
+        let name = format!("v{}", self.vars);
+        self.vars += 1;
+        writeln!(self.body, "let {name} = {expr};").unwrap();
+        name
+    }
+}
+
+/// A wgsl float literal, debug formatting always includes a decimal point or exponent
+///
+/// Panics if the value isn't finite, since wgsl has no literal for it.
+fn float(value: f32) -> String {
+    assert!(
+        value.is_finite(),
+        "sdf parameters must be finite, got {value}"
+    );
+    format!("{value:?}")
+}
+
+fn vec2(value: Vec2) -> String {
+    format!("vec2<f32>({}, {})", float(value.x), float(value.y))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primitives_match_sdf_module() {
+        let p = Vec2::new(3.0, -2.0);
+        assert_eq!(SdfExpr::circle(5.0).distance(p), sdf::sd_circle(p, 5.0));
+        assert_eq!(
+            SdfExpr::rectangle(Vec2::new(4.0, 1.0)).distance(p),
+            sdf::sd_box(p, Vec2::new(4.0, 1.0))
+        );
+        assert_eq!(
+            SdfExpr::star_5(6.0, 2.0).distance(p),
+            sdf::sd_star_5(p, 6.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn test_operations() {
+        let shape = SdfExpr::circle(1.0)
+            .translate(Vec2::new(10.0, 0.0))
+            .union(SdfExpr::rectangle(Vec2::splat(2.0)))
+            .subtract(SdfExpr::circle(0.5));
+
+        // Inside the translated circle
+        assert!((shape.distance(Vec2::new(10.0, 0.0)) + 1.0).abs() < 1e-6);
+        // Inside the hole cut out of the rectangle
+        assert!((shape.distance(Vec2::ZERO) - 0.5).abs() < 1e-6);
+        // Inside the rectangle, outside the hole
+        assert!(shape.distance(Vec2::new(1.5, 1.5)) < 0.0);
+    }
+
+    #[test]
+    fn test_transforms() {
+        // A rectangle rotated a quarter turn is tall instead of wide
+        let tall = SdfExpr::rectangle(Vec2::new(4.0, 1.0)).rotate(std::f32::consts::FRAC_PI_2);
+        assert!(tall.distance(Vec2::new(0.0, 3.0)) < 0.0);
+        assert!(tall.distance(Vec2::new(3.0, 0.0)) > 0.0);
+
+        // Scaling keeps distances exact
+        let big = SdfExpr::circle(1.0).scale(3.0);
+        assert!((big.distance(Vec2::new(5.0, 0.0)) - 2.0).abs() < 1e-6);
+
+        let grid = SdfExpr::circle(1.0).repeat(Vec2::splat(10.0));
+        assert!((grid.distance(Vec2::new(30.0, -20.0)) + 1.0).abs() < 1e-6);
+        assert!((grid.distance(Vec2::new(34.0, 0.0)) - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_rounded_rectangle_keeps_size() {
+        let shape = SdfExpr::rounded_rectangle(Vec2::new(10.0, 5.0), 2.0);
+        assert!(shape.distance(Vec2::new(10.0, 0.0)).abs() < 1e-6);
+        assert!(shape.distance(Vec2::new(0.0, 5.0)).abs() < 1e-6);
+        // The corner is cut
+        assert!(shape.distance(Vec2::new(9.9, 4.9)) > 0.0);
+    }
+
+    #[test]
+    fn test_wgsl() {
+        let shape = SdfExpr::circle(10.0)
+            .translate(Vec2::new(5.0, 0.0))
+            .smooth_union(SdfExpr::rectangle(Vec2::new(1.0, 2.5)), 4.0);
+
+        assert_eq!(
+            shape.to_wgsl(),
+            "\
+let v0 = input.pos - vec2<f32>(5.0, 0.0);
+let v1 = smud::sd_circle(v0, 10.0);
+let v2 = smud::sd_box(input.pos, vec2<f32>(1.0, 2.5));
+let v3 = smud::op_smooth_union(v1, v2, 4.0);
+return v3;
+"
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Reporting of shapes whose shader failed to compile
+
+use bevy::{
+    platform::collections::HashSet,
+    prelude::*,
+    render::{
+        Extract, MainWorld,
+        render_resource::{CachedPipelineState, CachedRenderPipelineId, PipelineCache},
+        sync_world::MainEntity,
+    },
+};
+
+use crate::{
+    GeneratedShaders, SmudShape,
+    shader_loading::{FALLBACK_FILL_HANDLE, FALLBACK_SDF_HANDLE},
+};
+
+/// Written when the shader generated for a shape fails to compile
+///
+/// The error is reported once per shape. If the shader is fixed, for instance by editing a hot
+/// reloaded `.wgsl` file, and later breaks again, it's reported again.
+///
+/// ```no_run
+/// # use bevy::prelude::*;
+/// # use bevy_smud::SmudShaderError;
+/// fn log_shader_errors(mut errors: MessageReader<SmudShaderError>) {
+///     for error in errors.read() {
+///         error!("shape {} failed to compile: {}", error.entity, error.error);
+///     }
+/// }
+/// ```
+#[derive(Message, Debug, Clone)]
+pub struct SmudShaderError {
+    /// The shape entity
+    pub entity: Entity,
+    /// The sdf shader of the shape
+    pub sdf: Handle<Shader>,
+    /// The fill shader of the shape
+    pub fill: Handle<Shader>,
+    /// The error message from the shader compiler
+    pub error: String,
+}
+
+/// Sdf and fill drawn in place of shapes whose shader failed to compile
+///
+/// Defaults to a magenta checkered box covering the bounds of the shape. Remove the resource to
+/// not draw anything instead.
+#[derive(Resource, Debug, Clone)]
+pub struct SmudFallbackShape {
+    /// Shader containing the `sdf` function of the fallback
+    pub sdf: Handle<Shader>,
+    /// Shader containing the `fill` function of the fallback
+    pub fill: Handle<Shader>,
+}
+
+impl Default for SmudFallbackShape {
+    fn default() -> Self {
+        Self {
+            sdf: FALLBACK_SDF_HANDLE,
+            fill: FALLBACK_FILL_HANDLE,
+        }
+    }
+}
+
+/// Shader errors found by the queue systems, waiting to be sent to the main world
+#[derive(Resource, Default)]
+pub(crate) struct ShaderErrors {
+    /// Generated shader for [`SmudFallbackShape`], if it exists
+    pub fallback: Option<Handle<Shader>>,
+    /// Shapes with a failed pipeline that were already reported
+    reported: HashSet<MainEntity>,
+    pending: Vec<(MainEntity, AssetId<Shader>, String)>,
+}
+
+impl ShaderErrors {
+    /// Whether the pipeline of a shape failed to compile
+    ///
+    /// The error is queued for reporting the first time it's seen for a shape.
+    pub fn failed(
+        &mut self,
+        pipeline_cache: &PipelineCache,
+        pipeline: CachedRenderPipelineId,
+        main_entity: MainEntity,
+        shader: &Handle<Shader>,
+    ) -> bool {
+        match pipeline_cache.get_render_pipeline_state(pipeline) {
+            CachedPipelineState::Err(err) => {
+                if self.reported.insert(main_entity) {
+                    self.pending
+                        .push((main_entity, shader.id(), err.to_string()));
+                }
+                true
+            }
+            _ => {
+                // Report the shape again if it breaks after being fixed
+                if !self.reported.is_empty() {
+                    self.reported.remove(&main_entity);
+                }
+                false
+            }
+        }
+    }
+}
+
+/// Forget reported errors of despawned shapes
+pub(crate) fn extract_removed_shader_errors(
+    mut shader_errors: ResMut<ShaderErrors>,
+    mut removed_shapes: Extract<RemovedComponents<SmudShape>>,
+    #[cfg(feature = "bevy_ui")] mut removed_ui_shapes: Extract<
+        RemovedComponents<crate::ui::UiShape>,
+    >,
+) {
+    let removed = removed_shapes.read();
+    #[cfg(feature = "bevy_ui")]
+    let removed = removed.chain(removed_ui_shapes.read());
+    for entity in removed {
+        shader_errors.reported.remove(&MainEntity::from(entity));
+    }
+}
+
+/// Generate the fallback shader, and write queued errors as [`SmudShaderError`] messages
+pub(crate) fn extract_shader_errors(
+    mut main_world: ResMut<MainWorld>,
+    mut generated_shaders: ResMut<GeneratedShaders>,
+    mut shader_errors: ResMut<ShaderErrors>,
+) {
+    main_world.resource_scope(|world, mut shaders: Mut<Assets<Shader>>| {
+        // The fallback is only generated again if it was replaced, or its sdf or fill modified
+        match world.get_resource::<SmudFallbackShape>() {
+            Some(fallback) => {
+                let key = (fallback.sdf.id(), fallback.fill.id());
+                let generated = generated_shaders.get(key.0, key.1).map(Handle::id);
+                if generated.is_none()
+                    || generated != shader_errors.fallback.as_ref().map(Handle::id)
+                {
+                    shader_errors.fallback =
+                        generated_shaders.try_generate(&fallback.sdf, &fallback.fill, &mut shaders);
+                    generated_shaders.fallback = Some(key);
+                }
+            }
+            None => {
+                shader_errors.fallback = None;
+                generated_shaders.fallback = None;
+            }
+        }
+
+        for (main_entity, shader, error) in shader_errors.pending.drain(..) {
+            // Find the sdf and fill the failed shader was generated from
+            let Some((sdf, fill)) = generated_shaders.source(shader) else {
+                continue;
+            };
+            let (Some(sdf), Some(fill)) = (
+                shaders.get_strong_handle(sdf),
+                shaders.get_strong_handle(fill),
+            ) else {
+                continue;
+            };
+
+            world.write_message(SmudShaderError {
+                entity: main_entity.id(),
+                sdf,
+                fill,
+                error,
+            });
+        }
+    });
+}
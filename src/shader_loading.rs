@@ -8,6 +8,12 @@ const SMUD_SHADER_HANDLE: Handle<Shader> = uuid_handle!("eca0ed31-c377-41be-94a8
 const VIEW_BINDINGS_SHADER_HANDLE: Handle<Shader> =
     uuid_handle!("f973016d-a9cc-469a-afb6-64ad829cd838");
 
+const SHAPE_PARAMS_SHADER_HANDLE: Handle<Shader> =
+    uuid_handle!("5c1f4e2a-8d0b-4b7e-9a63-2f7d1c8e4b90");
+
+const FILL_TEXTURE_SHADER_HANDLE: Handle<Shader> =
+    uuid_handle!("a3e07b54-19c6-4f2d-8b3e-6d4c2f9a1e75");
+
 pub const VERTEX_SHADER_HANDLE: Handle<Shader> =
     uuid_handle!("27b9d87f-6a69-49ee-a2e8-c0bc08ee4f61");
 
@@ -18,6 +24,14 @@ pub const DEFAULT_FILL_HANDLE: Handle<Shader> =
 /// Simple single-colored filled fill
 pub const SIMPLE_FILL_HANDLE: Handle<Shader> = uuid_handle!("cef2d2c2-1a68-4418-a815-5a8ac361f140");
 
+/// Shape drawn in place of shapes whose shader failed to compile
+pub const FALLBACK_SDF_HANDLE: Handle<Shader> =
+    uuid_handle!("6f0d2b8e-4c71-4a39-b5e2-9d8a17c3f064");
+
+/// Fill drawn in place of fills that failed to compile
+pub const FALLBACK_FILL_HANDLE: Handle<Shader> =
+    uuid_handle!("d2946a1c-83be-4f5d-a07e-3b1c5e8f9a27");
+
 pub struct ShaderLoadingPlugin;
 
 impl Plugin for ShaderLoadingPlugin {
@@ -36,6 +50,20 @@ impl Plugin for ShaderLoadingPlugin {
             Shader::from_wgsl
         );
 
+        load_internal_asset!(
+            app,
+            SHAPE_PARAMS_SHADER_HANDLE,
+            "../assets/shape_params.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            FILL_TEXTURE_SHADER_HANDLE,
+            "../assets/fill_texture.wgsl",
+            Shader::from_wgsl
+        );
+
         load_internal_asset!(
             app,
             VERTEX_SHADER_HANDLE,
@@ -56,5 +84,19 @@ impl Plugin for ShaderLoadingPlugin {
             "../assets/fills/simple.wgsl",
             Shader::from_wgsl
         );
+
+        load_internal_asset!(
+            app,
+            FALLBACK_SDF_HANDLE,
+            "../assets/fallback_shape.wgsl",
+            Shader::from_wgsl
+        );
+
+        load_internal_asset!(
+            app,
+            FALLBACK_FILL_HANDLE,
+            "../assets/fills/fallback.wgsl",
+            Shader::from_wgsl
+        );
     }
 }
@@ -0,0 +1,107 @@
+//! Render world storage for [`SmudParams`](crate::SmudParams)
+
+use std::num::NonZeroU64;
+
+use bevy::{
+    prelude::*,
+    render::{
+        render_resource::{
+            BindGroupLayoutDescriptor, BindGroupLayoutEntries, BindingResource, BufferUsages,
+            RawBufferVec, ShaderStages,
+            binding_types::{storage_buffer_read_only_sized, uniform_buffer_sized},
+        },
+        renderer::{RenderDevice, RenderQueue},
+    },
+};
+
+/// Number of vec4s in the params array when falling back to a uniform buffer
+///
+/// Must match the array size in `shape_params.wgsl`.
+pub(crate) const PARAMS_UNIFORM_CAPACITY: usize = 1024;
+
+/// Shader def used when storage buffers are not supported (WebGL2)
+pub(crate) const PARAMS_UNIFORM_SHADER_DEF: &str = "SMUD_PARAMS_UNIFORM";
+
+/// Whether the params need to go in a uniform buffer instead of a storage buffer
+pub(crate) fn params_use_uniform(render_device: &RenderDevice) -> bool {
+    render_device.limits().max_storage_buffers_per_shader_stage == 0
+}
+
+/// Layout for the bind group containing the extra params of all shapes
+pub(crate) fn params_layout(label: &'static str, uniform: bool) -> BindGroupLayoutDescriptor {
+    let entries = if uniform {
+        BindGroupLayoutEntries::single(
+            ShaderStages::FRAGMENT,
+            uniform_buffer_sized(
+                false,
+                NonZeroU64::new((PARAMS_UNIFORM_CAPACITY * size_of::<Vec4>()) as u64),
+            ),
+        )
+    } else {
+        BindGroupLayoutEntries::single(
+            ShaderStages::FRAGMENT,
+            storage_buffer_read_only_sized(false, None),
+        )
+    };
+    BindGroupLayoutDescriptor::new(label, &entries)
+}
+
+/// The extra params of all shapes, packed into one buffer
+pub(crate) struct ParamsBuffer {
+    values: RawBufferVec<Vec4>,
+    uniform: bool,
+}
+
+impl ParamsBuffer {
+    pub fn new(uniform: bool) -> Self {
+        let usage = if uniform {
+            BufferUsages::UNIFORM
+        } else {
+            BufferUsages::STORAGE
+        };
+        Self {
+            values: RawBufferVec::new(usage),
+            uniform,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+    }
+
+    /// Add the params of a shape, returning the (offset, count) to pass to the shader
+    pub fn push(&mut self, params: &[Vec4]) -> [u32; 2] {
+        let offset = self.values.len();
+        let mut count = params.len();
+        if self.uniform && offset + count > PARAMS_UNIFORM_CAPACITY {
+            warn_once!(
+                "bevy_smud: more than {PARAMS_UNIFORM_CAPACITY} extra params in total are not \
+                 supported without storage buffers, the rest are ignored"
+            );
+            count = PARAMS_UNIFORM_CAPACITY.saturating_sub(offset);
+        }
+        for param in &params[..count] {
+            self.values.push(*param);
+        }
+        [offset as u32, count as u32]
+    }
+
+    pub fn write_buffer(&mut self, render_device: &RenderDevice, render_queue: &RenderQueue) {
+        // Uniform buffers need to match the size in the shader, and empty buffers can't be bound
+        let min_len = if self.uniform {
+            PARAMS_UNIFORM_CAPACITY
+        } else {
+            1
+        };
+        while self.values.len() < min_len {
+            self.values.push(Vec4::ZERO);
+        }
+        self.values.write_buffer(render_device, render_queue);
+    }
+
+    pub fn binding(&self) -> Option<BindingResource<'_>> {
+        self.values
+            .buffer()
+            .map(|buffer| buffer.as_entire_binding())
+    }
+}
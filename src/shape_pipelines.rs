@@ -0,0 +1,179 @@
+//! Specialized pipelines of generated shaders
+//!
+//! Bevy's `SpecializedRenderPipelines` keeps every key it has seen, including the ones of evicted
+//! shaders. [`ShapePipelines`] groups them by generated shader instead, and drops the group when
+//! [`GeneratedShaders`] removes the shader.
+
+use bevy::{
+    platform::collections::HashMap,
+    prelude::*,
+    render::render_resource::{CachedRenderPipelineId, PipelineCache, SpecializedRenderPipeline},
+};
+
+use crate::GeneratedShaders;
+
+/// Pipeline key containing the generated shader it's specialized for
+pub(crate) trait ShaderKey: Send + Sync + 'static {
+    /// The generated shader of the key
+    fn shader(&self) -> AssetId<Shader>;
+}
+
+/// Specialized pipelines of `P`, grouped by generated shader
+#[derive(Resource)]
+pub(crate) struct ShapePipelines<P: SpecializedRenderPipeline> {
+    shaders: HashMap<AssetId<Shader>, HashMap<P::Key, CachedRenderPipelineId>>,
+}
+
+impl<P: SpecializedRenderPipeline> Default for ShapePipelines<P> {
+    fn default() -> Self {
+        Self {
+            shaders: HashMap::default(),
+        }
+    }
+}
+
+impl<P: SpecializedRenderPipeline<Key: ShaderKey>> ShapePipelines<P> {
+    /// Specialize `pipeline` for `key`, or get the pipeline specialized for it earlier
+    pub fn specialize(
+        &mut self,
+        pipeline_cache: &PipelineCache,
+        pipeline: &P,
+        key: P::Key,
+    ) -> CachedRenderPipelineId {
+        self.get_or_insert_with(key, |key| {
+            pipeline_cache.queue_render_pipeline(pipeline.specialize(key))
+        })
+    }
+
+    fn get_or_insert_with(
+        &mut self,
+        key: P::Key,
+        queue: impl FnOnce(P::Key) -> CachedRenderPipelineId,
+    ) -> CachedRenderPipelineId {
+        let pipelines = self.shaders.entry(key.shader()).or_default();
+        if let Some(&id) = pipelines.get(&key) {
+            return id;
+        }
+        let id = queue(key.clone());
+        pipelines.insert(key, id);
+        id
+    }
+
+    /// Number of specialized pipelines
+    pub fn len(&self) -> usize {
+        self.shaders.values().map(HashMap::len).sum()
+    }
+
+    /// Drop the pipelines of shaders that [`GeneratedShaders`] removed this frame
+    fn remove_shaders(&mut self, generated_shaders: &GeneratedShaders) {
+        for shader in &generated_shaders.removed {
+            self.shaders.remove(shader);
+        }
+    }
+}
+
+/// Drop the pipelines of removed generated shaders
+pub(crate) fn remove_shape_pipelines<P: SpecializedRenderPipeline<Key: ShaderKey> + 'static>(
+    generated_shaders: Res<GeneratedShaders>,
+    mut pipelines: ResMut<ShapePipelines<P>>,
+) {
+    if !generated_shaders.removed.is_empty() {
+        pipelines.remove_shaders(&generated_shaders);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{PipelineKey, SmudPipeline, SmudPipelineKey, SmudShaderCache};
+
+    /// Add an sdf and a fill, and specialize two pipelines for the shader generated from them
+    fn generate(
+        shaders: &mut Assets<Shader>,
+        generated_shaders: &mut GeneratedShaders,
+        pipelines: &mut ShapePipelines<SmudPipeline>,
+    ) -> (Handle<Shader>, Handle<Shader>) {
+        // Without an import path, the sdf is imported through a copy
+        let sdf = shaders.add(Shader::from_wgsl(
+            "fn sdf(input: smud::SdfInput) -> f32 { return 0.; }",
+            file!(),
+        ));
+        let fill = shaders.add(Shader::from_wgsl(
+            "#define_import_path test::fill
+            fn fill(input: smud::FillInput) -> vec4<f32> { return input.color; }",
+            file!(),
+        ));
+        specialize(&sdf, &fill, shaders, generated_shaders, pipelines);
+        (sdf, fill)
+    }
+
+    fn specialize(
+        sdf: &Handle<Shader>,
+        fill: &Handle<Shader>,
+        shaders: &mut Assets<Shader>,
+        generated_shaders: &mut GeneratedShaders,
+        pipelines: &mut ShapePipelines<SmudPipeline>,
+    ) {
+        let shader = generated_shaders
+            .try_generate(sdf, fill, shaders)
+            .expect("sdf and fill are loaded");
+        for mesh in [PipelineKey::NONE, PipelineKey::VIEW_3D] {
+            let key = SmudPipelineKey {
+                mesh,
+                shader: shader.clone(),
+            };
+            pipelines.get_or_insert_with(key, |_| CachedRenderPipelineId::INVALID);
+        }
+    }
+
+    #[test]
+    fn test_evicted_shader_pipelines_are_dropped() {
+        let mut shaders = Assets::<Shader>::default();
+        let mut cache = SmudShaderCache::default();
+        let mut generated_shaders = GeneratedShaders::default();
+        let mut pipelines = ShapePipelines::<SmudPipeline>::default();
+        generate(&mut shaders, &mut generated_shaders, &mut pipelines);
+        assert_eq!(pipelines.len(), 2);
+
+        let evict_after_frames = cache.evict_after_frames;
+        let mut evict = |generated_shaders: &mut GeneratedShaders| {
+            generated_shaders.removed.clear();
+            generated_shaders.evict_unused(&mut cache, &mut shaders);
+            pipelines.remove_shaders(generated_shaders);
+            (cache.shader_count(), pipelines.len())
+        };
+        for _ in 0..evict_after_frames {
+            assert_eq!(evict(&mut generated_shaders), (1, 2));
+        }
+        assert_eq!(evict(&mut generated_shaders), (0, 0));
+    }
+
+    #[test]
+    fn test_regenerated_shader_pipelines_are_dropped() {
+        let mut shaders = Assets::<Shader>::default();
+        let mut generated_shaders = GeneratedShaders::default();
+        let mut pipelines = ShapePipelines::<SmudPipeline>::default();
+        let (sdf, fill) = generate(&mut shaders, &mut generated_shaders, &mut pipelines);
+        // The sdf, its copy, the fill and the generated shader
+        assert_eq!(shaders.len(), 4);
+
+        // Repeated edits of the sdf don't grow the shaders or the pipelines
+        for _ in 0..3 {
+            generated_shaders.removed.clear();
+            generated_shaders.modified.insert(sdf.id());
+            generated_shaders.remove_modified(&mut shaders);
+            pipelines.remove_shaders(&generated_shaders);
+            assert_eq!(pipelines.len(), 0);
+
+            specialize(
+                &sdf,
+                &fill,
+                &mut shaders,
+                &mut generated_shaders,
+                &mut pipelines,
+            );
+            assert_eq!(shaders.len(), 4);
+            assert_eq!(pipelines.len(), 2);
+        }
+    }
+}
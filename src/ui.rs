@@ -10,21 +10,22 @@ use bevy::{
     render::{
         Extract, ExtractSchedule, MainWorld, Render, RenderApp, RenderSystems,
         globals::{GlobalsBuffer, GlobalsUniform},
+        render_asset::RenderAssets,
         render_phase::{
             AddRenderCommand, DrawFunctions, PhaseItem, PhaseItemExtraIndex, RenderCommand,
             RenderCommandResult, SetItemPipeline, TrackedRenderPass, ViewSortedRenderPhases,
         },
         render_resource::{
             BindGroup, BindGroupEntries, BindGroupLayoutDescriptor, BindGroupLayoutEntries,
-            BlendComponent, BlendFactor, BlendOperation, BlendState, BufferUsages,
-            CachedPipelineState, ColorTargetState, ColorWrites, FragmentState, FrontFace,
-            MultisampleState, PipelineCache, PolygonMode, PrimitiveState, PrimitiveTopology,
-            RawBufferVec, RenderPipelineDescriptor, ShaderStages, SpecializedRenderPipeline,
-            SpecializedRenderPipelines, TextureFormat, VertexAttribute, VertexFormat, VertexState,
+            BufferUsages, CachedPipelineState, ColorTargetState, ColorWrites, FragmentState,
+            FrontFace, MultisampleState, PipelineCache, PolygonMode, PrimitiveState,
+            PrimitiveTopology, RawBufferVec, RenderPipelineDescriptor, ShaderStages,
+            SpecializedRenderPipeline, TextureFormat, VertexAttribute, VertexFormat, VertexState,
             VertexStepMode, binding_types::uniform_buffer,
         },
         renderer::{RenderDevice, RenderQueue},
         sync_world::{MainEntity, TemporaryRenderEntity},
+        texture::{FallbackImage, GpuImage},
         view::{ViewUniform, ViewUniformOffset, ViewUniforms},
     },
     ui::{ComputedNode, ComputedStackIndex, Node, UiGlobalTransform},
@@ -33,8 +34,14 @@ use bevy::{
 use bytemuck::{Pod, Zeroable};
 
 use crate::{
-    BlendMode, FloatOrd, GeneratedShaders, SIMPLE_FILL_HANDLE, VertexBufferLayout,
+    BlendMode, FloatOrd, GeneratedShaders, SIMPLE_FILL_HANDLE, SmudParams, VertexBufferLayout,
+    diagnostics::SharedRenderStats,
+    evict_generated_shaders,
+    fill_texture::{FillTextureBindGroups, fill_texture_layout},
+    shader_errors::ShaderErrors,
     shader_loading::VERTEX_SHADER_HANDLE,
+    shape_params::{PARAMS_UNIFORM_SHADER_DEF, ParamsBuffer, params_layout, params_use_uniform},
+    shape_pipelines::{ShaderKey, ShapePipelines, remove_shape_pipelines},
 };
 
 /// Component for rendering shapes in UI.
@@ -60,6 +67,12 @@ pub struct UiShape {
     /// Parameters to pass to shapes, for things such as width of a box
     pub params: Vec4,
 
+    /// Parameters to pass to the fill, for things such as outline width or gradient angle
+    pub fill_params: Vec4,
+
+    /// Image that can be sampled by the fill shader, see [`SmudShape::image`](crate::SmudShape::image)
+    pub image: Option<Handle<Image>>,
+
     /// Blend mode for the shape
     pub blend_mode: BlendMode,
 
@@ -74,6 +87,8 @@ impl Default for UiShape {
             sdf: Handle::default(),
             fill: SIMPLE_FILL_HANDLE.clone(),
             params: Vec4::ZERO,
+            fill_params: Vec4::ZERO,
+            image: None,
             blend_mode: BlendMode::default(),
             extra_bounds: 0.0,
         }
@@ -81,6 +96,12 @@ impl Default for UiShape {
 }
 
 impl UiShape {
+    /// Set the image sampled by the fill shader (builder pattern)
+    pub fn with_image(mut self, image: Handle<Image>) -> Self {
+        self.image = Some(image);
+        self
+    }
+
     /// Set the blend mode for this shape (builder pattern)
     pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
         self.blend_mode = blend_mode;
@@ -94,22 +115,28 @@ struct UiShapeVertex {
     position: [f32; 3],
     color: [f32; 4],
     params: [f32; 4],
-    rotation: [f32; 2],
-    scale: f32,
+    transform: [f32; 4],
     bounds: [f32; 4],
+    extra_params: [u32; 2],
+    fill_params: [f32; 4],
 }
 
 #[derive(Resource)]
-struct UiShapeMeta {
+pub(crate) struct UiShapeMeta {
     vertices: RawBufferVec<UiShapeVertex>,
     view_bind_group: Option<BindGroup>,
+    params: ParamsBuffer,
+    params_bind_group: Option<BindGroup>,
 }
 
-impl Default for UiShapeMeta {
-    fn default() -> Self {
+impl FromWorld for UiShapeMeta {
+    fn from_world(world: &mut World) -> Self {
+        let params_uniform = params_use_uniform(world.resource::<RenderDevice>());
         Self {
             vertices: RawBufferVec::new(BufferUsages::VERTEX),
             view_bind_group: None,
+            params: ParamsBuffer::new(params_uniform),
+            params_bind_group: None,
         }
     }
 }
@@ -124,12 +151,15 @@ struct ExtractedUiShape {
     extra_bounds: f32,
     color: Color,
     params: Vec4,
+    fill_params: Vec4,
+    extra_params: Vec<Vec4>,
     shader: Handle<Shader>,
+    image: Option<AssetId<Image>>,
     blend_mode: BlendMode,
 }
 
 #[derive(Resource, Default)]
-struct ExtractedUiShapes {
+pub(crate) struct ExtractedUiShapes {
     nodes: Vec<ExtractedUiShape>,
 }
 
@@ -159,17 +189,17 @@ fn extract_ui_shapes(
             &ComputedNode,
             &ComputedStackIndex,
             &UiGlobalTransform,
+            Option<&SmudParams>,
         )>,
     >,
 ) {
     extracted_nodes.nodes.clear();
 
-    for (entity, ui_shape, computed_node, stack_index, transform) in ui_shapes.iter() {
+    for (entity, ui_shape, computed_node, stack_index, transform, params) in ui_shapes.iter() {
         let render_entity = commands.spawn(TemporaryRenderEntity).id();
 
         let Some(shader) = generated_shaders
-            .0
-            .get(&(ui_shape.sdf.id(), ui_shape.fill.id()))
+            .get(ui_shape.sdf.id(), ui_shape.fill.id())
             .cloned()
         else {
             // Shader not yet generated - skip this node for now
@@ -188,7 +218,10 @@ fn extract_ui_shapes(
             extra_bounds: ui_shape.extra_bounds,
             color: ui_shape.color,
             params: ui_shape.params,
+            fill_params: ui_shape.fill_params,
+            extra_params: params.map(|params| params.0.clone()).unwrap_or_default(),
             shader,
+            image: ui_shape.image.as_ref().map(Handle::id),
             blend_mode: ui_shape.blend_mode,
         });
     }
@@ -196,19 +229,28 @@ fn extract_ui_shapes(
 
 /// Pipeline key for specializing UI rendering based on shaders
 #[derive(Clone, Hash, PartialEq, Eq)]
-struct UiShapePipelineKey {
+pub(crate) struct UiShapePipelineKey {
     shader: Handle<Shader>,
     blend_mode: BlendMode,
 }
 
+impl ShaderKey for UiShapePipelineKey {
+    fn shader(&self) -> AssetId<Shader> {
+        self.shader.id()
+    }
+}
+
 /// Pipeline for rendering shapes in UI.
 #[derive(Resource)]
-struct UiShapePipeline {
+pub(crate) struct UiShapePipeline {
     view_layout: BindGroupLayoutDescriptor,
+    params_layout: BindGroupLayoutDescriptor,
+    params_uniform: bool,
+    texture_layout: BindGroupLayoutDescriptor,
 }
 
-impl Default for UiShapePipeline {
-    fn default() -> Self {
+impl FromWorld for UiShapePipeline {
+    fn from_world(world: &mut World) -> Self {
         let entries = BindGroupLayoutEntries::with_indices(
             ShaderStages::VERTEX_FRAGMENT,
             (
@@ -222,7 +264,15 @@ impl Default for UiShapePipeline {
 
         let view_layout = BindGroupLayoutDescriptor::new("ui_shape_view_layout", &entries);
 
-        Self { view_layout }
+        let params_uniform = params_use_uniform(world.resource::<RenderDevice>());
+        let params_layout = params_layout("ui_shape_params_layout", params_uniform);
+
+        Self {
+            view_layout,
+            params_layout,
+            params_uniform,
+            texture_layout: fill_texture_layout(),
+        }
     }
 }
 
@@ -233,9 +283,24 @@ impl SpecializedRenderPipeline for UiShapePipeline {
         // Get the generated shader for this sdf+fill combination
         let shader = key.shader;
 
+        let mut shader_defs = Vec::new();
+        if self.params_uniform {
+            shader_defs.push(PARAMS_UNIFORM_SHADER_DEF.into());
+        }
+        if let Some(blend_def) = key.blend_mode.shader_def() {
+            shader_defs.push(blend_def.into());
+        }
+        if key.blend_mode == BlendMode::Opaque {
+            shader_defs.push("MAY_DISCARD".into());
+        }
+
         RenderPipelineDescriptor {
             label: Some("ui_shape_pipeline".into()),
-            layout: vec![self.view_layout.clone()],
+            layout: vec![
+                self.view_layout.clone(),
+                self.params_layout.clone(),
+                self.texture_layout.clone(),
+            ],
             immediate_size: 0,
             vertex: VertexState {
                 shader: VERTEX_SHADER_HANDLE,
@@ -263,48 +328,40 @@ impl SpecializedRenderPipeline for UiShapePipeline {
                             offset: 28,
                             shader_location: 2,
                         },
-                        // rotation
+                        // transform
                         VertexAttribute {
-                            format: VertexFormat::Float32x2,
+                            format: VertexFormat::Float32x4,
                             offset: 44,
                             shader_location: 3,
                         },
-                        // scale
-                        VertexAttribute {
-                            format: VertexFormat::Float32,
-                            offset: 52,
-                            shader_location: 4,
-                        },
                         // bounds
                         VertexAttribute {
                             format: VertexFormat::Float32x4,
-                            offset: 56,
+                            offset: 60,
                             shader_location: 5,
                         },
+                        // extra params
+                        VertexAttribute {
+                            format: VertexFormat::Uint32x2,
+                            offset: 76,
+                            shader_location: 6,
+                        },
+                        // fill params
+                        VertexAttribute {
+                            format: VertexFormat::Float32x4,
+                            offset: 84,
+                            shader_location: 7,
+                        },
                     ],
                 }],
             },
             fragment: Some(FragmentState {
                 shader,
-                shader_defs: vec![],
+                shader_defs,
                 entry_point: Some("fragment".into()),
                 targets: vec![Some(ColorTargetState {
                     format: TextureFormat::Rgba8UnormSrgb, // UI render target format
-                    blend: Some(match key.blend_mode {
-                        BlendMode::Alpha => BlendState::ALPHA_BLENDING,
-                        BlendMode::Additive => BlendState {
-                            color: BlendComponent {
-                                src_factor: BlendFactor::SrcAlpha,
-                                dst_factor: BlendFactor::One,
-                                operation: BlendOperation::Add,
-                            },
-                            alpha: BlendComponent {
-                                src_factor: BlendFactor::One,
-                                dst_factor: BlendFactor::One,
-                                operation: BlendOperation::Add,
-                            },
-                        },
-                    }),
+                    blend: key.blend_mode.blend_state(),
                     write_mask: ColorWrites::ALL,
                 })],
             }),
@@ -329,7 +386,7 @@ impl SpecializedRenderPipeline for UiShapePipeline {
 }
 
 /// Prepare vertex buffers - generates vertices for each extracted node
-fn prepare_ui_shapes(
+pub(crate) fn prepare_ui_shapes(
     mut ui_shape_meta: ResMut<UiShapeMeta>,
     render_device: Res<RenderDevice>,
     render_queue: Res<RenderQueue>,
@@ -338,6 +395,9 @@ fn prepare_ui_shapes(
     globals_buffer: Res<GlobalsBuffer>,
     pipeline: Res<UiShapePipeline>,
     pipeline_cache: Res<PipelineCache>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    fallback_image: Res<FallbackImage>,
+    mut texture_bind_groups: ResMut<FillTextureBindGroups>,
 ) {
     // Create view bind group
     if let (Some(view_binding), Some(globals)) = (
@@ -353,53 +413,65 @@ fn prepare_ui_shapes(
     }
 
     ui_shape_meta.vertices.clear();
+    ui_shape_meta.params.clear();
 
     // Generate one instance per node - vertex shader will use vertex_index to determine corners
     for node in &extracted_nodes.nodes {
         // Extract transform components from Affine2
         let position = node.transform.translation;
 
-        // Extract rotation and scale from the matrix
-        let x_axis = node.transform.matrix2.x_axis;
-        let y_axis = node.transform.matrix2.y_axis;
-
-        // Scale is the length of the axis vectors
-        let scale_x = x_axis.length();
-        let scale_y = y_axis.length();
-        let scale = (scale_x + scale_y) / 2.0; // Average scale (assuming uniform)
-
-        // Rotation is the direction of the x-axis (normalized)
-        let rotation = if scale_x > 0.0 {
-            let normalized = x_axis / scale_x;
-            [normalized.x, normalized.y] // [cos, sin]
-        } else {
-            [1.0, 0.0] // No rotation
-        };
+        // Pass the full matrix, so non-uniform scale and skew are preserved
+        let transform = node.transform.matrix2.to_cols_array();
 
         let bounds = node.rect.size() / 2.0;
 
+        let extra_params = ui_shape_meta.params.push(&node.extra_params);
+
+        texture_bind_groups.prepare(
+            node.image,
+            &render_device,
+            &pipeline_cache,
+            &gpu_images,
+            &fallback_image,
+        );
+
         ui_shape_meta.vertices.push(UiShapeVertex {
             position: [position.x, position.y, 0.0],
             color: node.color.to_linear().to_f32_array(),
             params: node.params.to_array(),
-            rotation,
-            scale,
+            transform,
             bounds: [bounds.x, bounds.y, node.extra_bounds, node.extra_bounds],
+            extra_params,
+            fill_params: node.fill_params.to_array(),
         });
     }
 
     ui_shape_meta
         .vertices
         .write_buffer(&render_device, &render_queue);
+
+    ui_shape_meta
+        .params
+        .write_buffer(&render_device, &render_queue);
+    let params_layout = pipeline_cache.get_bind_group_layout(&pipeline.params_layout);
+    ui_shape_meta.params_bind_group = ui_shape_meta.params.binding().map(|binding| {
+        render_device.create_bind_group(
+            "ui_shape_params_bind_group",
+            &params_layout,
+            &BindGroupEntries::single(binding),
+        )
+    });
 }
 
 fn queue_ui_shapes(
     draw_functions: Res<DrawFunctions<TransparentUi>>,
     pipeline: Res<UiShapePipeline>,
-    mut pipelines: ResMut<SpecializedRenderPipelines<UiShapePipeline>>,
+    mut pipelines: ResMut<ShapePipelines<UiShapePipeline>>,
     pipeline_cache: Res<PipelineCache>,
     mut transparent_render_phases: ResMut<ViewSortedRenderPhases<TransparentUi>>,
     extracted_nodes: Res<ExtractedUiShapes>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    mut shader_errors: ResMut<ShaderErrors>,
 ) {
     let draw_function = draw_functions.read().id::<DrawUiShapes>();
 
@@ -407,6 +479,13 @@ fn queue_ui_shapes(
     for (_view_key, transparent_phase) in transparent_render_phases.iter_mut() {
         // Add each extracted UiShape to the render phase
         for (index, node) in extracted_nodes.nodes.iter().enumerate() {
+            // Skip nodes whose image isn't loaded yet
+            if let Some(image) = node.image
+                && gpu_images.get(image).is_none()
+            {
+                continue;
+            }
+
             // Create pipeline key for this shader combination
             let key = UiShapePipelineKey {
                 shader: node.shader.clone(),
@@ -414,7 +493,18 @@ fn queue_ui_shapes(
             };
 
             // Specialize the pipeline for this shader combination
-            let pipeline_id = pipelines.specialize(&pipeline_cache, &pipeline, key);
+            let mut pipeline_id = pipelines.specialize(&pipeline_cache, &pipeline, key);
+
+            if shader_errors.failed(&pipeline_cache, pipeline_id, node.main_entity, &node.shader) {
+                let Some(fallback) = shader_errors.fallback.clone() else {
+                    continue;
+                };
+                let fallback_key = UiShapePipelineKey {
+                    shader: fallback,
+                    blend_mode: node.blend_mode,
+                };
+                pipeline_id = pipelines.specialize(&pipeline_cache, &pipeline, fallback_key);
+            }
 
             // Check if pipeline is ready - if not, skip this node
             if !matches!(
@@ -442,9 +532,45 @@ fn queue_ui_shapes(
     }
 }
 
+/// Measure UI shapes for [`SmudDiagnosticsPlugin`](crate::SmudDiagnosticsPlugin)
+pub(crate) fn collect_ui_render_stats(
+    stats: Res<SharedRenderStats>,
+    extracted_nodes: Res<ExtractedUiShapes>,
+    ui_shape_meta: Res<UiShapeMeta>,
+    draw_functions: Res<DrawFunctions<TransparentUi>>,
+    transparent_render_phases: Res<ViewSortedRenderPhases<TransparentUi>>,
+) {
+    // Every node is drawn on its own, so count the shape items in the views that have any
+    let draw_function = draw_functions.read().id::<DrawUiShapes>();
+    let (views, batches) = transparent_render_phases
+        .values()
+        .map(|phase| {
+            phase
+                .items
+                .values()
+                .filter(|item| item.draw_function == draw_function)
+                .count()
+        })
+        .filter(|&batches| batches > 0)
+        .fold((0, 0), |(views, total), batches| {
+            (views + 1, total + batches)
+        });
+
+    let mut stats = stats.0.lock().unwrap();
+    stats.ui_shapes_extracted = extracted_nodes.nodes.len();
+    stats.ui_batches_per_view = if views == 0 {
+        0.
+    } else {
+        batches as f64 / views as f64
+    };
+    stats.ui_instance_buffer_bytes = ui_shape_meta.vertices.len() * size_of::<UiShapeVertex>();
+}
+
 type DrawUiShapes = (
     SetItemPipeline,
     SetUiShapeViewBindGroup<0>,
+    SetUiShapeParamsBindGroup<1>,
+    SetUiShapeTextureBindGroup<2>,
     DrawUiShapeBatch,
 );
 
@@ -470,6 +596,53 @@ impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetUiShapeViewBindGroup<
     }
 }
 
+struct SetUiShapeParamsBindGroup<const I: usize>;
+
+impl<P: PhaseItem, const I: usize> RenderCommand<P> for SetUiShapeParamsBindGroup<I> {
+    type Param = SRes<UiShapeMeta>;
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        _item: &P,
+        _view: (),
+        _entity: Option<()>,
+        ui_shape_meta: SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(params_bind_group) = ui_shape_meta.into_inner().params_bind_group.as_ref() else {
+            return RenderCommandResult::Failure("params_bind_group not available");
+        };
+        pass.set_bind_group(I, params_bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
+struct SetUiShapeTextureBindGroup<const I: usize>;
+
+impl<const I: usize> RenderCommand<TransparentUi> for SetUiShapeTextureBindGroup<I> {
+    type Param = (SRes<ExtractedUiShapes>, SRes<FillTextureBindGroups>);
+    type ViewQuery = ();
+    type ItemQuery = ();
+
+    fn render<'w>(
+        item: &TransparentUi,
+        _view: (),
+        _entity: Option<()>,
+        (extracted_nodes, texture_bind_groups): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(node) = extracted_nodes.nodes.get(item.index) else {
+            return RenderCommandResult::Skip;
+        };
+        let Some(texture_bind_group) = texture_bind_groups.into_inner().get(node.image) else {
+            return RenderCommandResult::Failure("texture bind group not available");
+        };
+        pass.set_bind_group(I, texture_bind_group, &[]);
+        RenderCommandResult::Success
+    }
+}
+
 struct DrawUiShapeBatch;
 
 impl RenderCommand<TransparentUi> for DrawUiShapeBatch {
@@ -515,10 +688,14 @@ impl Plugin for UiShapePlugin {
                 .init_resource::<UiShapeMeta>()
                 .init_resource::<UiShapePipeline>()
                 .init_resource::<ExtractedUiShapes>()
-                .init_resource::<SpecializedRenderPipelines<UiShapePipeline>>()
+                .init_resource::<ShapePipelines<UiShapePipeline>>()
                 .add_systems(
                     ExtractSchedule,
-                    (generate_shaders, extract_ui_shapes.after(generate_shaders)),
+                    (
+                        generate_shaders.after(evict_generated_shaders),
+                        extract_ui_shapes.after(generate_shaders),
+                        remove_shape_pipelines::<UiShapePipeline>.after(evict_generated_shaders),
+                    ),
                 )
                 .add_systems(
                     Render,
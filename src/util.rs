@@ -1,5 +1,33 @@
+use bevy::{
+    math::{Mat2, Vec2, Vec3Swizzles},
+    transform::components::GlobalTransform,
+};
 use uuid::Uuid;
 
 pub fn generate_shader_id() -> String {
     Uuid::new_v4().to_string().replace('-', "_")
 }
+
+/// The 2x2 linear part (rotation, scale and skew) of a shape's transform in the xy plane
+pub fn shape_matrix2(transform: &GlobalTransform) -> Mat2 {
+    let matrix3 = transform.affine().matrix3;
+    Mat2::from_cols(matrix3.x_axis.xy(), matrix3.y_axis.xy())
+}
+
+/// A 2x2 matrix that scales and skews like a shape's transform does within its own plane
+///
+/// Unlike [`shape_matrix2`], this also holds for shapes rotated out of the xy plane, since it's
+/// built from the lengths of and the angle between the full 3d x and y axes. It's only meant
+/// for correcting distances, the orientation within the plane is lost.
+pub fn shape_plane_matrix2(transform: &GlobalTransform) -> Mat2 {
+    let matrix3 = transform.affine().matrix3;
+    let x_length = matrix3.x_axis.length();
+    if x_length <= 0. {
+        return Mat2::ZERO;
+    }
+    let skew = matrix3.x_axis.dot(matrix3.y_axis) / x_length;
+    let height = (matrix3.y_axis.length_squared() - skew * skew)
+        .max(0.)
+        .sqrt();
+    Mat2::from_cols(Vec2::new(x_length, 0.), Vec2::new(skew, height))
+}
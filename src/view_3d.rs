@@ -0,0 +1,120 @@
+//! Render path for shapes seen by 3d cameras
+//!
+//! Shapes are drawn as quads in the [`Transparent3d`] phase, sorted by distance to the camera
+//! and depth tested against 3d geometry. By default the quad lies in the xy plane of the shape's
+//! transform, with [`SmudBillboard`](crate::SmudBillboard) it faces the camera instead.
+//!
+//! All shapes go through the transparent phase, including [`BlendMode::Opaque`](crate::BlendMode::Opaque) ones, which
+//! still write depth. Shapes with a [`ShapeMaterial`](crate::ShapeMaterial) are only drawn by
+//! 2d cameras.
+
+use bevy::{
+    core_pipeline::core_3d::{Transparent3d, TransparentSortingInfo3d},
+    prelude::*,
+    render::{
+        render_asset::RenderAssets,
+        render_phase::{DrawFunctions, PhaseItemExtraIndex, ViewSortedRenderPhases},
+        render_resource::{CachedRenderPipelineId, PipelineCache},
+        texture::GpuImage,
+    },
+};
+use fixedbitset::FixedBitSet;
+
+use crate::{
+    DrawSmudShape, ExtractedShapes, PipelineKey, ShapeViews, SmudPipeline, SmudPipelineKey,
+    collect_visible_shapes, shader_errors::ShaderErrors, shape_pipelines::ShapePipelines,
+    view_pipeline_key,
+};
+
+/// Queue the shapes visible in each 3d view
+pub(crate) fn queue_shapes_3d(
+    mut view_entities: Local<FixedBitSet>,
+    draw_functions: Res<DrawFunctions<Transparent3d>>,
+    smud_pipeline: Res<SmudPipeline>,
+    mut pipelines: ResMut<ShapePipelines<SmudPipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    extracted_shapes: Res<ExtractedShapes>,
+    gpu_images: Res<RenderAssets<GpuImage>>,
+    mut shader_errors: ResMut<ShaderErrors>,
+    mut transparent_render_phases: ResMut<ViewSortedRenderPhases<Transparent3d>>,
+    mut views: ShapeViews,
+) {
+    let draw_smud_shape_function = draw_functions.read().get_id::<DrawSmudShape>().unwrap();
+
+    for (visible_entities, camera, view, msaa, tonemapping, dither) in &mut views {
+        let Some(transparent_phase) = transparent_render_phases.get_mut(&view.retained_view_entity)
+        else {
+            continue;
+        };
+
+        let view_key =
+            view_pipeline_key(camera, view, msaa, tonemapping, dither) | PipelineKey::VIEW_3D;
+        let rangefinder = view.rangefinder3d();
+
+        collect_visible_shapes(visible_entities, &mut view_entities);
+
+        for extracted_shape in &extracted_shapes.shapes {
+            if extracted_shape.material.is_some() {
+                continue;
+            }
+            if !view_entities.contains(extracted_shape.main_entity.id().index_u32() as usize) {
+                continue;
+            }
+            if let Some(image) = extracted_shape.image
+                && gpu_images.get(image).is_none()
+            {
+                debug!("Image not loaded yet, skipping");
+                continue;
+            }
+
+            let mut mesh_key = view_key | PipelineKey::from_shape(extracted_shape);
+            if extracted_shape.billboard {
+                mesh_key |= PipelineKey::BILLBOARD;
+            }
+            let specialize_key = SmudPipelineKey {
+                mesh: mesh_key,
+                shader: extracted_shape.shader.clone(),
+            };
+            let mut pipeline =
+                pipelines.specialize(&pipeline_cache, &smud_pipeline, specialize_key);
+
+            if shader_errors.failed(
+                &pipeline_cache,
+                pipeline,
+                extracted_shape.main_entity,
+                &extracted_shape.shader,
+            ) {
+                let Some(fallback) = shader_errors.fallback.clone() else {
+                    continue;
+                };
+                let fallback_key = SmudPipelineKey {
+                    mesh: mesh_key,
+                    shader: fallback,
+                };
+                pipeline = pipelines.specialize(&pipeline_cache, &smud_pipeline, fallback_key);
+            }
+
+            if pipeline == CachedRenderPipelineId::INVALID {
+                debug!("Shape not ready yet, skipping");
+                continue;
+            }
+
+            let mesh_center = extracted_shape.transform.translation();
+
+            transparent_phase.add_transient(Transparent3d {
+                sorting_info: TransparentSortingInfo3d::Sorted {
+                    mesh_center,
+                    depth_bias: 0.,
+                },
+                distance: rangefinder.distance(&mesh_center),
+                pipeline,
+                entity: (extracted_shape.render_entity, extracted_shape.main_entity),
+                draw_function: draw_smud_shape_function,
+                // batch_range will be calculated in prepare_shapes
+                batch_range: 0..0,
+                extra_index: PhaseItemExtraIndex::None,
+                indexed: true,
+            });
+        }
+    }
+}
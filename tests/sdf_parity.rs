@@ -0,0 +1,329 @@
+//! Compares the Rust functions in `bevy_smud::sdf` with their WGSL versions in `smud.wgsl`, and
+//! [`SdfExpr::distance`] with the WGSL generated by [`SdfExpr::to_wgsl`]
+//!
+//! The WGSL functions are run on the CPU by a small interpreter, see [`wgsl_interpreter`]. Each
+//! case calls a function with the same arguments in both languages, for a grid of sample
+//! points.
+
+mod wgsl_interpreter;
+
+use bevy::math::{Vec2, Vec4};
+use bevy_smud::{SdfExpr, sdf};
+use wgsl_interpreter::{Shader, Value};
+
+/// Arguments of a call, and what the Rust version returned
+struct Call {
+    arguments: Vec<Value>,
+    derivatives: Vec<(Value, Value)>,
+    expected: Value,
+}
+
+impl Call {
+    fn new(arguments: Vec<Value>, expected: impl Into<Value>) -> Self {
+        Self {
+            arguments,
+            derivatives: Vec::new(),
+            expected: expected.into(),
+        }
+    }
+
+    /// Set the `dpdx` and `dpdy` of the first arguments (builder pattern)
+    fn with_derivatives(mut self, derivatives: Vec<(Value, Value)>) -> Self {
+        self.derivatives = derivatives;
+        self
+    }
+}
+
+struct Case {
+    /// Name of the Rust function
+    name: &'static str,
+    call: fn(Vec2) -> Call,
+}
+
+/// A case calling `sdf::$f` and its WGSL version with the same arguments
+macro_rules! case {
+    ($p:ident => $f:ident($($arg:expr),* $(,)?)) => {
+        Case {
+            name: stringify!($f),
+            call: |$p| Call::new(vec![$(Value::from($arg)),*], sdf::$f($($arg),*)),
+        }
+    };
+}
+
+fn cases() -> Vec<Case> {
+    // Derivatives along x and y, for functions using dpdx, dpdy or fwidth
+    const DPD: Vec2 = Vec2::new(0.6, -0.3);
+
+    vec![
+        // Helpers
+        case!(p => ndot(p, Vec2::new(3., -2.))),
+        case!(p => dot2(p)),
+        case!(p => modulo(p.x, 7.3)),
+        // Distance functions
+        case!(p => sd_circle(p, 30.)),
+        case!(p => sd_annulus(p, 30., 20.)),
+        case!(p => sd_capsule(p, 10., 20.)),
+        case!(p => sd_rounded_box(p, Vec2::new(30., 20.), Vec4::new(2., 4., 6., 8.))),
+        case!(p => sd_box(p, Vec2::new(30., 20.))),
+        case!(p => sd_oriented_box(p, Vec2::new(-20., -10.), Vec2::new(25., 15.), 8.)),
+        case!(p => sd_segment(p, Vec2::new(-20., -10.), Vec2::new(25., 15.))),
+        case!(p => sd_rhombus(p, Vec2::new(30., 20.))),
+        case!(p => sd_trapezoid(p, 30., 15., 20.)),
+        case!(p => sd_parallelogram(p, 20., 15., 8.)),
+        case!(p => sd_equilateral_triangle(p, 30.)),
+        case!(p => sd_triangle_isosceles(p, Vec2::new(20., -35.))),
+        case!(p => sd_triangle(p, Vec2::new(-25., -20.), Vec2::new(30., -10.), Vec2::new(0., 30.))),
+        case!(p => sd_uneven_capsule(p, 15., 8., 25.)),
+        case!(p => sd_pentagon(p, 30.)),
+        case!(p => sd_hexagon(p, 30.)),
+        case!(p => sd_octagon(p, 30.)),
+        case!(p => sd_hexagram(p, 20.)),
+        case!(p => sd_star_5(p, 30., 2.)),
+        case!(p => sd_regular_polygon(p, 30., 7)),
+        case!(p => sd_star(p, 30., 6, 3.)),
+        case!(p => sd_pie(p, sdf::sin_cos(1.), 30.)),
+        case!(p => sd_cut_disk(p, 30., 10.)),
+        case!(p => sd_arc(p, sdf::sin_cos(2.), 25., 5.)),
+        case!(p => sd_arc_oriented(p, sdf::sin_cos(0.5), sdf::sin_cos(2.), 25., 5.)),
+        case!(p => sd_horseshoe(p, Vec2::new(0.4, 0.9), 20., Vec2::new(10., 5.))),
+        case!(p => sd_rounded_cross(p / 30., 0.5)),
+        case!(p => sd_egg(p, 30., 10.)),
+        case!(p => sd_heart(p / 30. + Vec2::new(0., 0.5))),
+        case!(p => sd_cross(p, Vec2::new(30., 10.), 2.)),
+        case!(p => sd_rounded_x(p, 40., 5.)),
+        case!(p => sd_polygon_edge(p, Vec2::new(-20., -10.), Vec2::new(25., 15.), Vec2::new(400., -1.))),
+        case!(p => sd_polygon_edge(p, Vec2::new(25., 15.), Vec2::new(25., 15.), Vec2::new(400., 1.))),
+        case!(p => sd_ellipse(p, 30., 20.)),
+        case!(p => sd_parabola(p, 0.05)),
+        case!(p => sd_parabola_segment(p, 30., 20.)),
+        case!(p => sd_bezier(p, Vec2::new(-30., -10.), Vec2::new(0., 40.), Vec2::new(25., -20.))),
+        case!(p => sd_blobby_cross(p / 30., 0.6)),
+        case!(p => sd_tunnel(p, Vec2::new(20., 25.))),
+        case!(p => sd_stairs(p, Vec2::new(10., 8.), 4.)),
+        case!(p => sd_vesica(p, 30., 15.)),
+        case!(p => sd_moon(p, 10., 30., 25.)),
+        // Gradients
+        case!(p => sdg_circle(p, 30.)),
+        case!(p => sdg_annulus(p, 30., 20.)),
+        case!(p => sdg_box(p, Vec2::new(30., 20.))),
+        case!(p => sdg_segment(p, Vec2::new(-20., -10.), Vec2::new(25., 15.))),
+        case!(p => sdg_segment(p, Vec2::new(25., 15.), Vec2::new(25., 15.))),
+        case!(p => sdg_capsule(p, 10., 20.)),
+        // Fills
+        case!(p => renormalize_uv(p / 40.)),
+        case!(p => exponential_falloff(p.x, 30., 2.5)),
+        case!(p => exponential_falloff_3(p.x, 30.)),
+        Case {
+            name: "correct_distance",
+            call: |p| {
+                let transform = Vec4::new(2., 0.5, -0.3, 1.);
+                let (pos_dpdx, pos_dpdy) = (Vec2::new(0.5, 0.1), Vec2::new(-0.2, 0.4));
+                let d = p.x / 10.;
+                Call::new(
+                    vec![d.into(), p.into(), transform.into()],
+                    sdf::correct_distance(d, transform, pos_dpdx, pos_dpdy, DPD),
+                )
+                .with_derivatives(vec![
+                    (DPD.x.into(), DPD.y.into()),
+                    (pos_dpdx.into(), pos_dpdy.into()),
+                ])
+            },
+        },
+        Case {
+            name: "distance_gradient",
+            call: |p| {
+                let (pos_dpdx, pos_dpdy) = (Vec2::new(0.5, 0.1), Vec2::new(-0.2, 0.4));
+                let d = p.x / 10.;
+                Call::new(
+                    vec![d.into(), p.into()],
+                    sdf::distance_gradient(pos_dpdx, pos_dpdy, DPD),
+                )
+                .with_derivatives(vec![
+                    (DPD.x.into(), DPD.y.into()),
+                    (pos_dpdx.into(), pos_dpdy.into()),
+                ])
+            },
+        },
+        Case {
+            name: "sd_fill_alpha_fwidth",
+            call: |p| {
+                let d = p.x / 10.;
+                Call::new(
+                    vec![d.into()],
+                    sdf::sd_fill_alpha_fwidth(d, DPD.abs().element_sum()),
+                )
+                .with_derivatives(vec![(DPD.x.into(), DPD.y.into())])
+            },
+        },
+        Case {
+            name: "sd_fill_alpha_dpd",
+            call: |p| {
+                let d = p.x / 10.;
+                Call::new(vec![d.into()], sdf::sd_fill_alpha_dpd(d, DPD))
+                    .with_derivatives(vec![(DPD.x.into(), DPD.y.into())])
+            },
+        },
+        case!(p => sd_fill_alpha_nearest(p.x / 10.)),
+        Case {
+            name: "sd_fill_with_falloff_3",
+            call: |p| {
+                let d = p.x / 10.;
+                let falloff_color = Vec4::new(0.1, 0.2, 0.3, 0.5);
+                let fill_color = Vec4::new(1., 0.8, 0.6, 1.);
+                Call::new(
+                    vec![
+                        d.into(),
+                        3.0_f32.into(),
+                        falloff_color.into(),
+                        fill_color.into(),
+                    ],
+                    sdf::sd_fill_with_falloff_3(
+                        d,
+                        3.,
+                        falloff_color,
+                        fill_color,
+                        DPD.abs().element_sum(),
+                    ),
+                )
+                .with_derivatives(vec![(DPD.x.into(), DPD.y.into())])
+            },
+        },
+        // Operations
+        case!(p => op_union(p.x, p.y)),
+        case!(p => op_subtract(p.x, p.y)),
+        case!(p => op_intersect(p.x, p.y)),
+        case!(p => sin_cos(p.x / 10.)),
+        case!(p => rotate(p, sdf::sin_cos(0.7))),
+        case!(p => rotate_rad(p, 0.7)),
+        case!(p => rotate_45(p)),
+        case!(p => op_smooth_subtract(p.x, p.y, 8.)),
+        case!(p => op_smooth_union(p.x, p.y, 8.)),
+        case!(p => op_smooth_intersect(p.x, p.y, 8.)),
+        case!(p => op_repeat(p, Vec2::new(25., 20.))),
+        case!(p => op_repeat_limited(p, Vec2::new(25., 20.), Vec2::new(1., 2.))),
+        case!(p => op_mirror_x(p)),
+        case!(p => op_mirror_y(p)),
+        case!(p => op_polar_repeat(p, 5)),
+        case!(p => op_elongate(p, Vec2::new(20., 10.))),
+        case!(p => op_onion(p.x, 3.)),
+        case!(p => op_round(p.x, 3.)),
+        case!(p => op_displace(p.x, p.y / 10.)),
+    ]
+}
+
+/// Name of the WGSL version of a Rust function
+///
+/// WGSL names ending with a digit have a trailing underscore, naga_oil requires it.
+fn wgsl_name(name: &str) -> String {
+    if name.ends_with(|c: char| c.is_ascii_digit()) {
+        format!("{name}_")
+    } else {
+        name.to_string()
+    }
+}
+
+/// Points in a grid around the origin, avoiding the axes where many shapes are symmetric
+fn sample_points() -> impl Iterator<Item = Vec2> {
+    (-4..=4).flat_map(|x| {
+        (-4..=4).map(move |y| Vec2::new(x as f32 * 11.3 + 0.7, y as f32 * 10.1 - 0.3))
+    })
+}
+
+fn shader() -> Shader {
+    Shader::parse(include_str!("../assets/smud.wgsl"))
+}
+
+fn approx_eq(a: f32, b: f32) -> bool {
+    (a.is_nan() && b.is_nan()) || (a - b).abs() <= 1e-4 * a.abs().max(b.abs()).max(1.)
+}
+
+#[test]
+fn rust_functions_match_wgsl() {
+    let shader = shader();
+    let mut mismatches = Vec::new();
+
+    for case in cases() {
+        for p in sample_points() {
+            let call = (case.call)(p);
+            let expected = call.expected.scalars();
+            let actual = shader
+                .call(&wgsl_name(case.name), call.arguments, &call.derivatives)
+                .scalars();
+            let matches = expected.len() == actual.len()
+                && expected.iter().zip(&actual).all(|(a, b)| approx_eq(*a, *b));
+            if !matches {
+                mismatches.push(format!(
+                    "{} at {p}: rust {expected:?}, wgsl {actual:?}",
+                    case.name
+                ));
+            }
+        }
+    }
+
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+/// Expressions using every kind of [`SdfExpr`] node
+fn sdf_exprs() -> Vec<SdfExpr> {
+    vec![
+        SdfExpr::circle(30.)
+            .translate(Vec2::new(10., -5.))
+            .union(SdfExpr::rectangle(Vec2::new(20., 10.)).rotate(0.4)),
+        SdfExpr::hexagon(25.)
+            .subtract(SdfExpr::circle(10.))
+            .intersect(SdfExpr::rounded_rectangle(Vec2::new(30., 20.), 5.)),
+        SdfExpr::capsule(8., 15.).smooth_union(
+            SdfExpr::segment(Vec2::new(-20., -10.), Vec2::new(25., 15.)),
+            5.,
+        ),
+        SdfExpr::equilateral_triangle(20.)
+            .smooth_subtract(SdfExpr::star_5(15., 2.), 4.)
+            .smooth_intersect(SdfExpr::circle(25.), 3.)
+            .scale(1.5),
+        SdfExpr::Heart
+            .translate(Vec2::new(0., 0.5))
+            .scale(30.)
+            .repeat(Vec2::new(40., 35.)),
+    ]
+}
+
+#[test]
+fn sdf_expr_distance_matches_wgsl() {
+    let mut mismatches = Vec::new();
+
+    for (index, expr) in sdf_exprs().into_iter().enumerate() {
+        // The generated body reads `input.pos`, pass the position as an argument instead
+        let body = expr
+            .to_wgsl()
+            .replace("input.pos", "pos")
+            .replace("smud::", "");
+        let source = format!(
+            "{}\nfn sdf_expr(pos: vec2<f32>) -> f32 {{\n{body}}}\n",
+            include_str!("../assets/smud.wgsl")
+        );
+        let shader = Shader::parse(&source);
+
+        for p in sample_points() {
+            let expected = expr.distance(p);
+            let actual = shader.call("sdf_expr", vec![p.into()], &[]).scalars();
+            if actual.len() != 1 || !approx_eq(expected, actual[0]) {
+                mismatches.push(format!(
+                    "expression {index} at {p}: rust {expected}, wgsl {actual:?}"
+                ));
+            }
+        }
+    }
+
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[test]
+fn every_wgsl_function_has_a_case() {
+    let cases: Vec<String> = cases().iter().map(|c| wgsl_name(c.name)).collect();
+    let shader = shader();
+    let missing: Vec<&str> = shader
+        .function_names()
+        .filter(|name| !cases.iter().any(|c| c == name))
+        .collect();
+    assert!(missing.is_empty(), "no parity test for {missing:?}");
+}
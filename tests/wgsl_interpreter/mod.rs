@@ -0,0 +1,689 @@
+//! A small interpreter for the subset of WGSL used by `smud.wgsl`
+//!
+//! The shader is parsed with naga, and functions are run by walking naga's IR with `f32` math,
+//! so Rust ports can be compared against the shader without a GPU. Anything outside the
+//! subset (textures, globals, switches, ...) panics.
+//!
+//! Derivatives can't be computed from a single invocation, so they have to be passed in for
+//! the arguments of the called function. `dpdx`, `dpdy` and `fwidth` of anything else panic.
+
+use naga::{
+    Arena, BinaryOperator, DerivativeAxis, Expression, Function, Handle, Literal, LocalVariable,
+    MathFunction, Module, RelationalFunction, ScalarKind, Statement, TypeInner, UnaryOperator,
+};
+use std::collections::HashMap;
+
+/// A value of the interpreted shader
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    F32(f32),
+    I32(i32),
+    U32(u32),
+    Bool(bool),
+    /// Vector of scalars
+    Vector(Vec<Value>),
+    /// Matrix as a list of column vectors
+    Matrix(Vec<Value>),
+    Pointer(Pointer),
+}
+
+/// A local variable, or a part of one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pointer {
+    local: Handle<LocalVariable>,
+    path: Vec<usize>,
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Self::F32(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Self::I32(value)
+    }
+}
+
+impl From<bevy::math::Vec2> for Value {
+    fn from(value: bevy::math::Vec2) -> Self {
+        Self::Vector(value.to_array().map(Value::F32).to_vec())
+    }
+}
+
+impl From<bevy::math::Vec3> for Value {
+    fn from(value: bevy::math::Vec3) -> Self {
+        Self::Vector(value.to_array().map(Value::F32).to_vec())
+    }
+}
+
+impl From<bevy::math::Vec4> for Value {
+    fn from(value: bevy::math::Vec4) -> Self {
+        Self::Vector(value.to_array().map(Value::F32).to_vec())
+    }
+}
+
+impl Value {
+    fn f32(&self) -> f32 {
+        match self {
+            Self::F32(x) => *x,
+            other => panic!("expected f32, got {other:?}"),
+        }
+    }
+
+    fn bool(&self) -> bool {
+        match self {
+            Self::Bool(x) => *x,
+            other => panic!("expected bool, got {other:?}"),
+        }
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            Self::I32(i) => *i as usize,
+            Self::U32(i) => *i as usize,
+            other => panic!("expected an index, got {other:?}"),
+        }
+    }
+
+    fn components(&self) -> &[Value] {
+        match self {
+            Self::Vector(components) | Self::Matrix(components) => components,
+            other => panic!("expected a vector or matrix, got {other:?}"),
+        }
+    }
+
+    /// Scalars of a value, flattened
+    pub fn scalars(&self) -> Vec<f32> {
+        match self {
+            Self::F32(x) => vec![*x],
+            Self::Vector(components) | Self::Matrix(components) => {
+                components.iter().flat_map(Value::scalars).collect()
+            }
+            other => panic!("expected floats, got {other:?}"),
+        }
+    }
+
+    /// Apply `f` to each component, or to the value itself for scalars
+    fn map(&self, f: &impl Fn(&Value) -> Value) -> Value {
+        match self {
+            Self::Vector(components) => Self::Vector(components.iter().map(f).collect()),
+            Self::Matrix(columns) => Self::Matrix(columns.iter().map(|c| c.map(f)).collect()),
+            scalar => f(scalar),
+        }
+    }
+
+    fn map_f32(&self, f: impl Fn(f32) -> f32) -> Value {
+        self.map(&|x| Value::F32(f(x.f32())))
+    }
+}
+
+/// Combine the components of several values, scalars are splatted to the size of the vectors
+fn zip(args: &[&Value], f: &impl Fn(&[&Value]) -> Value) -> Value {
+    let len = args.iter().find_map(|a| match a {
+        Value::Vector(c) | Value::Matrix(c) => Some(c.len()),
+        _ => None,
+    });
+    match len {
+        None => f(args),
+        Some(len) => {
+            let matrix = args.iter().any(|a| matches!(a, Value::Matrix(_)));
+            let components = (0..len)
+                .map(|i| {
+                    let parts: Vec<&Value> = args
+                        .iter()
+                        .map(|a| match a {
+                            Value::Vector(c) | Value::Matrix(c) => &c[i],
+                            scalar => *scalar,
+                        })
+                        .collect();
+                    zip(&parts, f)
+                })
+                .collect();
+            if matrix {
+                Value::Matrix(components)
+            } else {
+                Value::Vector(components)
+            }
+        }
+    }
+}
+
+fn zip_f32(args: &[&Value], f: impl Fn(&[f32]) -> f32) -> Value {
+    zip(args, &|parts: &[&Value]| {
+        let floats: Vec<f32> = parts.iter().map(|p| p.f32()).collect();
+        Value::F32(f(&floats))
+    })
+}
+
+fn dot(a: &Value, b: &Value) -> f32 {
+    a.components()
+        .iter()
+        .zip(b.components())
+        .map(|(a, b)| a.f32() * b.f32())
+        .sum()
+}
+
+/// Same as `sign` in WGSL, 0 for 0
+fn sign(x: f32) -> f32 {
+    if x > 0. {
+        1.
+    } else if x < 0. {
+        -1.
+    } else {
+        0.
+    }
+}
+
+/// A parsed shader
+pub struct Shader {
+    module: Module,
+}
+
+enum Flow {
+    Next,
+    Break,
+    Continue,
+    Return(Option<Value>),
+}
+
+struct Frame<'a> {
+    module: &'a Module,
+    expressions: &'a Arena<Expression>,
+    arguments: Vec<Value>,
+    derivatives: &'a [(Value, Value)],
+    locals: HashMap<Handle<LocalVariable>, Value>,
+    values: HashMap<Handle<Expression>, Value>,
+}
+
+impl Shader {
+    /// Parse WGSL, preprocessor directives like `#define_import_path` are skipped
+    pub fn parse(source: &str) -> Self {
+        let source: String = source
+            .lines()
+            .map(|line| if line.starts_with('#') { "" } else { line })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let module = naga::front::wgsl::parse_str(&source)
+            .unwrap_or_else(|e| panic!("{}", e.emit_to_string(&source)));
+        Self { module }
+    }
+
+    /// Names of the functions in the shader
+    pub fn function_names(&self) -> impl Iterator<Item = &str> {
+        self.module
+            .functions
+            .iter()
+            .filter_map(|(_, f)| f.name.as_deref())
+    }
+
+    /// Call a function by name
+    ///
+    /// `derivatives` are the `dpdx` and `dpdy` of the arguments, in order. Arguments after the
+    /// end of the slice don't have derivatives.
+    pub fn call(&self, name: &str, arguments: Vec<Value>, derivatives: &[(Value, Value)]) -> Value {
+        let (_, function) = self
+            .module
+            .functions
+            .iter()
+            .find(|(_, f)| f.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("no function named {name}"));
+        call(&self.module, function, arguments, derivatives)
+            .unwrap_or_else(|| panic!("{name} didn't return a value"))
+    }
+}
+
+fn call(
+    module: &Module,
+    function: &Function,
+    arguments: Vec<Value>,
+    derivatives: &[(Value, Value)],
+) -> Option<Value> {
+    assert_eq!(arguments.len(), function.arguments.len());
+    let mut frame = Frame {
+        module,
+        expressions: &function.expressions,
+        arguments,
+        derivatives,
+        locals: HashMap::new(),
+        values: HashMap::new(),
+    };
+    for (handle, local) in function.local_variables.iter() {
+        let value = match local.init {
+            Some(init) => frame.eval(init),
+            None => zero_value(module, local.ty),
+        };
+        frame.locals.insert(handle, value);
+    }
+    match frame.run(&function.body) {
+        Flow::Return(value) => value,
+        Flow::Next => None,
+        Flow::Break | Flow::Continue => panic!("break or continue outside of a loop"),
+    }
+}
+
+fn zero_value(module: &Module, ty: Handle<naga::Type>) -> Value {
+    let zero = |kind| match kind {
+        ScalarKind::Float => Value::F32(0.),
+        ScalarKind::Sint => Value::I32(0),
+        ScalarKind::Uint => Value::U32(0),
+        ScalarKind::Bool => Value::Bool(false),
+        other => panic!("unsupported scalar kind {other:?}"),
+    };
+    match module.types[ty].inner {
+        TypeInner::Scalar(scalar) => zero(scalar.kind),
+        TypeInner::Vector { size, scalar } => Value::Vector(vec![zero(scalar.kind); size as usize]),
+        TypeInner::Matrix { columns, rows, .. } => {
+            Value::Matrix(vec![
+                Value::Vector(vec![Value::F32(0.); rows as usize]);
+                columns as usize
+            ])
+        }
+        ref other => panic!("unsupported type {other:?}"),
+    }
+}
+
+impl Frame<'_> {
+    fn run(&mut self, block: &naga::Block) -> Flow {
+        for statement in block.iter() {
+            let flow = match statement {
+                Statement::Emit(range) => {
+                    for handle in range.clone() {
+                        let value = self.compute(handle);
+                        self.values.insert(handle, value);
+                    }
+                    Flow::Next
+                }
+                Statement::Block(block) => self.run(block),
+                Statement::If {
+                    condition,
+                    accept,
+                    reject,
+                } => {
+                    if self.eval(*condition).bool() {
+                        self.run(accept)
+                    } else {
+                        self.run(reject)
+                    }
+                }
+                Statement::Loop {
+                    body,
+                    continuing,
+                    break_if,
+                } => loop {
+                    match self.run(body) {
+                        Flow::Break => break Flow::Next,
+                        Flow::Return(value) => break Flow::Return(value),
+                        Flow::Next | Flow::Continue => {}
+                    }
+                    self.run(continuing);
+                    if break_if.is_some_and(|b| self.eval(b).bool()) {
+                        break Flow::Next;
+                    }
+                },
+                Statement::Break => Flow::Break,
+                Statement::Continue => Flow::Continue,
+                Statement::Return { value } => Flow::Return(value.map(|v| self.eval(v))),
+                Statement::Store { pointer, value } => {
+                    let Value::Pointer(pointer) = self.eval(*pointer) else {
+                        panic!("store to something that isn't a pointer");
+                    };
+                    let value = self.eval(*value);
+                    let mut target = self.locals.get_mut(&pointer.local).unwrap();
+                    for &i in &pointer.path {
+                        target = match target {
+                            Value::Vector(c) | Value::Matrix(c) => &mut c[i],
+                            other => panic!("can't index {other:?}"),
+                        };
+                    }
+                    *target = value;
+                    Flow::Next
+                }
+                Statement::Call {
+                    function,
+                    arguments,
+                    result,
+                } => {
+                    let arguments = arguments.iter().map(|a| self.eval(*a)).collect();
+                    let function = &self.module.functions[*function];
+                    let value = call(self.module, function, arguments, &[]);
+                    if let Some(result) = result {
+                        self.values
+                            .insert(*result, value.expect("function returned nothing"));
+                    }
+                    Flow::Next
+                }
+                other => panic!("unsupported statement {other:?}"),
+            };
+            if !matches!(flow, Flow::Next) {
+                return flow;
+            }
+        }
+        Flow::Next
+    }
+
+    /// Value of an expression, emitted expressions have to be run first
+    fn eval(&mut self, handle: Handle<Expression>) -> Value {
+        match self.values.get(&handle) {
+            Some(value) => value.clone(),
+            None => self.compute(handle),
+        }
+    }
+
+    fn compute(&mut self, handle: Handle<Expression>) -> Value {
+        match self.expressions[handle] {
+            Expression::Literal(literal) => match literal {
+                Literal::F32(x) => Value::F32(x),
+                Literal::AbstractFloat(x) => Value::F32(x as f32),
+                Literal::I32(x) => Value::I32(x),
+                Literal::AbstractInt(x) => Value::I32(x as i32),
+                Literal::U32(x) => Value::U32(x),
+                Literal::Bool(x) => Value::Bool(x),
+                other => panic!("unsupported literal {other:?}"),
+            },
+            Expression::Constant(constant) => {
+                let module = self.module;
+                let mut global = Frame {
+                    module,
+                    expressions: &module.global_expressions,
+                    arguments: Vec::new(),
+                    derivatives: &[],
+                    locals: HashMap::new(),
+                    values: HashMap::new(),
+                };
+                global.eval(module.constants[constant].init)
+            }
+            Expression::ZeroValue(ty) => zero_value(self.module, ty),
+            Expression::Compose { ty, ref components } => {
+                let components: Vec<Value> = components.iter().map(|c| self.eval(*c)).collect();
+                let scalars = || {
+                    components
+                        .iter()
+                        .flat_map(|c| match c {
+                            Value::Vector(c) => c.clone(),
+                            scalar => vec![scalar.clone()],
+                        })
+                        .collect::<Vec<_>>()
+                };
+                match self.module.types[ty].inner {
+                    TypeInner::Vector { .. } => Value::Vector(scalars()),
+                    TypeInner::Matrix { rows, .. } => {
+                        if components.iter().all(|c| matches!(c, Value::Vector(_))) {
+                            Value::Matrix(components)
+                        } else {
+                            Value::Matrix(
+                                scalars()
+                                    .chunks(rows as usize)
+                                    .map(|c| Value::Vector(c.to_vec()))
+                                    .collect(),
+                            )
+                        }
+                    }
+                    ref other => panic!("unsupported composite {other:?}"),
+                }
+            }
+            Expression::Access { base, index } => {
+                let index = self.eval(index).index();
+                self.access(base, index)
+            }
+            Expression::AccessIndex { base, index } => self.access(base, index as usize),
+            Expression::Splat { size, value } => {
+                Value::Vector(vec![self.eval(value); size as usize])
+            }
+            Expression::Swizzle {
+                size,
+                vector,
+                pattern,
+            } => {
+                let vector = self.eval(vector);
+                let components = vector.components();
+                Value::Vector(
+                    pattern[..size as usize]
+                        .iter()
+                        .map(|&c| components[c as usize].clone())
+                        .collect(),
+                )
+            }
+            Expression::FunctionArgument(i) => self.arguments[i as usize].clone(),
+            Expression::LocalVariable(local) => Value::Pointer(Pointer {
+                local,
+                path: Vec::new(),
+            }),
+            Expression::Load { pointer } => {
+                let Value::Pointer(pointer) = self.eval(pointer) else {
+                    panic!("load from something that isn't a pointer");
+                };
+                let mut value = &self.locals[&pointer.local];
+                for &i in &pointer.path {
+                    value = &value.components()[i];
+                }
+                value.clone()
+            }
+            Expression::Unary { op, expr } => {
+                let value = self.eval(expr);
+                match op {
+                    UnaryOperator::Negate => value.map(&|x| match x {
+                        Value::F32(x) => Value::F32(-x),
+                        Value::I32(x) => Value::I32(-x),
+                        other => panic!("can't negate {other:?}"),
+                    }),
+                    UnaryOperator::LogicalNot => value.map(&|x| Value::Bool(!x.bool())),
+                    UnaryOperator::BitwiseNot => panic!("unsupported operator {op:?}"),
+                }
+            }
+            Expression::Binary { op, left, right } => {
+                let left = self.eval(left);
+                let right = self.eval(right);
+                binary(op, &left, &right)
+            }
+            Expression::Select {
+                condition,
+                accept,
+                reject,
+            } => {
+                let condition = self.eval(condition);
+                let accept = self.eval(accept);
+                let reject = self.eval(reject);
+                match condition {
+                    Value::Bool(true) => accept,
+                    Value::Bool(false) => reject,
+                    condition => zip(&[&condition, &accept, &reject], &|c: &[&Value]| {
+                        (if c[0].bool() { c[1] } else { c[2] }).clone()
+                    }),
+                }
+            }
+            Expression::Derivative { axis, expr, .. } => {
+                let Expression::FunctionArgument(i) = self.expressions[expr] else {
+                    panic!("derivatives are only supported for function arguments");
+                };
+                let (dx, dy) = self
+                    .derivatives
+                    .get(i as usize)
+                    .unwrap_or_else(|| panic!("no derivatives passed for argument {i}"));
+                match axis {
+                    DerivativeAxis::X => dx.clone(),
+                    DerivativeAxis::Y => dy.clone(),
+                    DerivativeAxis::Width => zip_f32(&[dx, dy], |d| d[0].abs() + d[1].abs()),
+                }
+            }
+            Expression::Relational { fun, argument } => {
+                let argument = self.eval(argument);
+                let bools = argument.components().iter().map(Value::bool);
+                match fun {
+                    RelationalFunction::All => Value::Bool(bools.clone().all(|b| b)),
+                    RelationalFunction::Any => Value::Bool(bools.clone().any(|b| b)),
+                    other => panic!("unsupported relational function {other:?}"),
+                }
+            }
+            Expression::Math {
+                fun,
+                arg,
+                arg1,
+                arg2,
+                ..
+            } => {
+                let args: Vec<Value> = [Some(arg), arg1, arg2]
+                    .into_iter()
+                    .flatten()
+                    .map(|a| self.eval(a))
+                    .collect();
+                math(fun, &args)
+            }
+            Expression::As {
+                expr,
+                kind,
+                convert,
+            } => {
+                assert!(convert.is_some(), "bitcasts are not supported");
+                self.eval(expr).map(&|x| {
+                    let x = match x {
+                        Value::F32(x) => *x as f64,
+                        Value::I32(x) => *x as f64,
+                        Value::U32(x) => *x as f64,
+                        Value::Bool(x) => *x as u8 as f64,
+                        other => panic!("can't convert {other:?}"),
+                    };
+                    match kind {
+                        ScalarKind::Float => Value::F32(x as f32),
+                        ScalarKind::Sint => Value::I32(x as i32),
+                        ScalarKind::Uint => Value::U32(x as u32),
+                        ScalarKind::Bool => Value::Bool(x != 0.),
+                        other => panic!("unsupported scalar kind {other:?}"),
+                    }
+                })
+            }
+            Expression::CallResult(_) => panic!("result of a function that wasn't called"),
+            ref other => panic!("unsupported expression {other:?}"),
+        }
+    }
+
+    fn access(&mut self, base: Handle<Expression>, index: usize) -> Value {
+        match self.eval(base) {
+            Value::Pointer(mut pointer) => {
+                pointer.path.push(index);
+                Value::Pointer(pointer)
+            }
+            value => value.components()[index].clone(),
+        }
+    }
+}
+
+fn binary(op: BinaryOperator, left: &Value, right: &Value) -> Value {
+    use BinaryOperator as Op;
+    match (op, left, right) {
+        // Linear algebra, everything else is per component
+        (Op::Multiply, Value::Matrix(columns), Value::Vector(v)) => {
+            let rows = columns[0].components().len();
+            Value::Vector(
+                (0..rows)
+                    .map(|row| {
+                        Value::F32(
+                            columns
+                                .iter()
+                                .zip(v)
+                                .map(|(column, x)| column.components()[row].f32() * x.f32())
+                                .sum(),
+                        )
+                    })
+                    .collect(),
+            )
+        }
+        (Op::Multiply, Value::Vector(_), Value::Matrix(columns)) => {
+            Value::Vector(columns.iter().map(|c| Value::F32(dot(left, c))).collect())
+        }
+        (Op::Multiply, Value::Matrix(_), Value::Matrix(_)) => {
+            panic!("matrix products are not supported")
+        }
+        _ => zip(&[left, right], &|v: &[&Value]| match (v[0], v[1]) {
+            (Value::F32(a), Value::F32(b)) => match op {
+                Op::Add => Value::F32(a + b),
+                Op::Subtract => Value::F32(a - b),
+                Op::Multiply => Value::F32(a * b),
+                Op::Divide => Value::F32(a / b),
+                Op::Modulo => Value::F32(a % b),
+                Op::Equal => Value::Bool(a == b),
+                Op::NotEqual => Value::Bool(a != b),
+                Op::Less => Value::Bool(a < b),
+                Op::LessEqual => Value::Bool(a <= b),
+                Op::Greater => Value::Bool(a > b),
+                Op::GreaterEqual => Value::Bool(a >= b),
+                _ => panic!("unsupported operator {op:?} for floats"),
+            },
+            (Value::I32(a), Value::I32(b)) => match op {
+                Op::Add => Value::I32(a.wrapping_add(*b)),
+                Op::Subtract => Value::I32(a.wrapping_sub(*b)),
+                Op::Multiply => Value::I32(a.wrapping_mul(*b)),
+                Op::Divide => Value::I32(a / b),
+                Op::Modulo => Value::I32(a % b),
+                Op::Equal => Value::Bool(a == b),
+                Op::NotEqual => Value::Bool(a != b),
+                Op::Less => Value::Bool(a < b),
+                Op::LessEqual => Value::Bool(a <= b),
+                Op::Greater => Value::Bool(a > b),
+                Op::GreaterEqual => Value::Bool(a >= b),
+                _ => panic!("unsupported operator {op:?} for integers"),
+            },
+            (Value::Bool(a), Value::Bool(b)) => match op {
+                Op::LogicalAnd | Op::And => Value::Bool(*a && *b),
+                Op::LogicalOr | Op::InclusiveOr => Value::Bool(*a || *b),
+                Op::Equal => Value::Bool(a == b),
+                Op::NotEqual => Value::Bool(a != b),
+                _ => panic!("unsupported operator {op:?} for booleans"),
+            },
+            (a, b) => panic!("unsupported operands {a:?} {op:?} {b:?}"),
+        }),
+    }
+}
+
+fn math(fun: MathFunction, args: &[Value]) -> Value {
+    use MathFunction as F;
+    let a = &args[0];
+    match fun {
+        F::Abs => a.map_f32(f32::abs),
+        F::Min => zip_f32(&[a, &args[1]], |x| x[0].min(x[1])),
+        F::Max => zip_f32(&[a, &args[1]], |x| x[0].max(x[1])),
+        F::Clamp => zip_f32(&[a, &args[1], &args[2]], |x| x[0].max(x[1]).min(x[2])),
+        F::Saturate => a.map_f32(|x| x.clamp(0., 1.)),
+        F::Cos => a.map_f32(f32::cos),
+        F::Sin => a.map_f32(f32::sin),
+        F::Tan => a.map_f32(f32::tan),
+        F::Acos => a.map_f32(f32::acos),
+        F::Asin => a.map_f32(f32::asin),
+        F::Atan => a.map_f32(f32::atan),
+        F::Atan2 => zip_f32(&[a, &args[1]], |x| x[0].atan2(x[1])),
+        F::Ceil => a.map_f32(f32::ceil),
+        F::Floor => a.map_f32(f32::floor),
+        F::Round => a.map_f32(f32::round_ties_even),
+        F::Fract => a.map_f32(|x| x - x.floor()),
+        F::Trunc => a.map_f32(f32::trunc),
+        F::Exp => a.map_f32(f32::exp),
+        F::Exp2 => a.map_f32(f32::exp2),
+        F::Log => a.map_f32(f32::ln),
+        F::Log2 => a.map_f32(f32::log2),
+        F::Pow => zip_f32(&[a, &args[1]], |x| x[0].powf(x[1])),
+        F::Sqrt => a.map_f32(f32::sqrt),
+        F::InverseSqrt => a.map_f32(|x| 1. / x.sqrt()),
+        F::Sign => a.map_f32(sign),
+        F::Step => zip_f32(&[a, &args[1]], |x| if x[1] >= x[0] { 1. } else { 0. }),
+        F::Mix => zip_f32(&[a, &args[1], &args[2]], |x| {
+            x[0] * (1. - x[2]) + x[1] * x[2]
+        }),
+        F::SmoothStep => zip_f32(&[a, &args[1], &args[2]], |x| {
+            let t = ((x[2] - x[0]) / (x[1] - x[0])).clamp(0., 1.);
+            t * t * (3. - 2. * t)
+        }),
+        F::Fma => zip_f32(&[a, &args[1], &args[2]], |x| x[0] * x[1] + x[2]),
+        F::Dot => Value::F32(dot(a, &args[1])),
+        F::Length => Value::F32(dot(a, a).sqrt()),
+        F::Distance => {
+            let d = binary(BinaryOperator::Subtract, a, &args[1]);
+            Value::F32(dot(&d, &d).sqrt())
+        }
+        F::Normalize => {
+            let length = dot(a, a).sqrt();
+            a.map_f32(|x| x / length)
+        }
+        other => panic!("unsupported math function {other:?}"),
+    }
+}